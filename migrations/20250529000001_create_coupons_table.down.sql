@@ -0,0 +1,10 @@
+-- Migration: 20250529000001_create_coupons_table
+-- Description: Drops the coupons table created by the up migration
+-- Author: RinKokonoe
+
+-- Down Migration
+DROP INDEX IF EXISTS idx_coupons_expiry;
+DROP INDEX IF EXISTS idx_coupons_posted;
+DROP INDEX IF EXISTS idx_coupons_valid;
+DROP INDEX IF EXISTS idx_coupons_source;
+DROP TABLE IF EXISTS coupons;
@@ -40,12 +40,3 @@ CREATE INDEX IF NOT EXISTS idx_coupons_posted ON coupons(is_posted);
 
 -- Index for expiry to quickly find and clean up expired coupons
 CREATE INDEX IF NOT EXISTS idx_coupons_expiry ON coupons(expiry);
-
--- Down Migration
--- In case we need to roll back the migration
-DROP INDEX IF EXISTS idx_coupons_expiry;
-DROP INDEX IF EXISTS idx_coupons_posted;
-DROP INDEX IF EXISTS idx_coupons_valid;
-DROP INDEX IF EXISTS idx_coupons_source;
-DROP TABLE IF EXISTS coupons;
-
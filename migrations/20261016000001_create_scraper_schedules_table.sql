@@ -0,0 +1,13 @@
+-- Migration: 20261016000001_create_scraper_schedules_table
+-- Description: Creates the scraper_schedules table for persisting per-scraper run times
+-- Author: RinKokonoe
+
+-- Up Migration
+CREATE TABLE IF NOT EXISTS scraper_schedules (
+    -- Scraper name, as returned by Scraper::name()
+    scraper TEXT PRIMARY KEY NOT NULL,
+    
+    -- Timing information
+    last_run TEXT,             -- ISO 8601 / RFC 3339 timestamp of the last run, NULL if never run
+    next_run TEXT NOT NULL     -- ISO 8601 / RFC 3339 timestamp of the next scheduled run
+);
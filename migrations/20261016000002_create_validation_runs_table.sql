@@ -0,0 +1,25 @@
+-- Migration: 20261016000002_create_validation_runs_table
+-- Description: Creates the validation_runs table recording every validation attempt
+-- Author: RinKokonoe
+
+-- Up Migration
+CREATE TABLE IF NOT EXISTS validation_runs (
+    -- Primary key
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    
+    -- Validated coupon
+    coupon_id INTEGER NOT NULL REFERENCES coupons(id) ON DELETE CASCADE,
+    
+    -- Outcome
+    validator TEXT NOT NULL,       -- Name of the validator that ran
+    is_valid INTEGER,              -- 0 = false, 1 = true, NULL if the validator failed with an error
+    message TEXT,                  -- Validator message or error
+    http_status INTEGER,           -- Status of the page checked, if any
+    
+    -- Timing information
+    duration_ms INTEGER NOT NULL,  -- How long the validation took
+    validated_at TEXT NOT NULL     -- ISO 8601 / RFC 3339 timestamp
+);
+
+-- Index for reading a coupon's history, newest first
+CREATE INDEX IF NOT EXISTS idx_validation_runs_coupon_id ON validation_runs(coupon_id, validated_at);
@@ -0,0 +1,19 @@
+-- Migration: 20261016000003_create_discord_messages_table
+-- Description: Creates the discord_messages table linking coupons to the Discord posts announcing them
+-- Author: RinKokonoe
+
+-- Up Migration
+CREATE TABLE IF NOT EXISTS discord_messages (
+    -- Announced coupon
+    coupon_id INTEGER NOT NULL REFERENCES coupons(id) ON DELETE CASCADE,
+    
+    -- Discord identifiers, stored as text since snowflakes are unsigned 64-bit
+    message_id TEXT NOT NULL,
+    channel_id TEXT NOT NULL,
+    via_webhook INTEGER NOT NULL DEFAULT 0,  -- 1 if sent through the webhook, 0 if by the bot
+    
+    -- Timing information
+    posted_at TEXT NOT NULL,   -- ISO 8601 / RFC 3339 timestamp
+    
+    PRIMARY KEY (coupon_id, message_id)
+);
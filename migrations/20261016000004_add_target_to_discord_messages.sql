@@ -0,0 +1,7 @@
+-- Migration: 20261016000004_add_target_to_discord_messages
+-- Description: Records which notification target each Discord message was sent to
+-- Author: RinKokonoe
+
+-- Up Migration
+-- Messages posted before targets existed went to the default webhook or channel
+ALTER TABLE discord_messages ADD COLUMN target TEXT NOT NULL DEFAULT 'default';
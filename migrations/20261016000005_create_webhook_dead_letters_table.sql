@@ -0,0 +1,23 @@
+-- Migration: 20261016000005_create_webhook_dead_letters_table
+-- Description: Creates the webhook_dead_letters table keeping webhook events that could not be delivered
+-- Author: RinKokonoe
+
+-- Up Migration
+CREATE TABLE IF NOT EXISTS webhook_dead_letters (
+    -- Primary key
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    
+    -- Event that failed, kept verbatim so it can be replayed
+    url TEXT NOT NULL,             -- Webhook URL the event was sent to
+    event_id TEXT NOT NULL,        -- Unique id of the event, also sent as X-Rin-Delivery
+    event_type TEXT NOT NULL,      -- e.g. coupon.discovered
+    payload TEXT NOT NULL,         -- JSON body
+    
+    -- Outcome of the last attempt
+    attempts INTEGER NOT NULL,     -- Number of delivery attempts made
+    last_error TEXT NOT NULL,      -- Error or response of the last attempt
+    http_status INTEGER,           -- Status of the last response, if any
+    
+    -- Timing information
+    failed_at TEXT NOT NULL        -- ISO 8601 / RFC 3339 timestamp
+);
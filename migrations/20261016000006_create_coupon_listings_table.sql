@@ -0,0 +1,26 @@
+-- Migration: 20261016000006_create_coupon_listings_table
+-- Description: Creates the coupon_listings table recording near-duplicate listings merged into a coupon
+-- Author: RinKokonoe
+
+-- Up Migration
+CREATE TABLE IF NOT EXISTS coupon_listings (
+    -- Primary key
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    
+    -- Canonical coupon the listing was merged into
+    coupon_id INTEGER NOT NULL REFERENCES coupons(id) ON DELETE CASCADE,
+    
+    -- Listing details as scraped
+    source TEXT NOT NULL,      -- The source where the listing was found
+    name TEXT NOT NULL,
+    url TEXT NOT NULL,
+    
+    -- Deduplication
+    hash TEXT NOT NULL UNIQUE, -- Identity hash of the listing, so it is recognized on later scrapes
+    
+    -- Timing information
+    first_seen TEXT NOT NULL   -- ISO 8601 / RFC 3339 timestamp
+);
+
+-- Index for listing everywhere a coupon was seen
+CREATE INDEX IF NOT EXISTS idx_coupon_listings_coupon_id ON coupon_listings(coupon_id);
@@ -0,0 +1,32 @@
+-- Migration: 20261016000007_create_coupon_sightings_table
+-- Description: Records every scrape that found a coupon and tracks when coupons were first and last seen
+-- Author: RinKokonoe
+
+-- Up Migration
+-- Sighting summary
+ALTER TABLE coupons ADD COLUMN first_seen TEXT;   -- ISO 8601 / RFC 3339 timestamp of the first scrape that found the coupon
+ALTER TABLE coupons ADD COLUMN last_seen TEXT;    -- ISO 8601 / RFC 3339 timestamp of the latest scrape that found the coupon
+ALTER TABLE coupons ADD COLUMN likely_dead INTEGER NOT NULL DEFAULT 0;  -- 1 if the coupon disappeared from its sources
+
+-- Coupons stored before sightings were tracked were last seen when they were added
+UPDATE coupons SET first_seen = created_at, last_seen = created_at;
+
+CREATE TABLE IF NOT EXISTS coupon_sightings (
+    -- Primary key
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    
+    -- Coupon that was found
+    coupon_id INTEGER NOT NULL REFERENCES coupons(id) ON DELETE CASCADE,
+    
+    -- Where it was found
+    scraper TEXT NOT NULL,     -- Name of the scraper that found it
+    source TEXT NOT NULL,      -- Source reported by the scraper
+    
+    -- Timing information
+    seen_at TEXT NOT NULL,     -- ISO 8601 / RFC 3339 start time of the scrape run
+    
+    UNIQUE (coupon_id, scraper, seen_at)
+);
+
+-- Index for finding the runs of a scraper
+CREATE INDEX IF NOT EXISTS idx_coupon_sightings_scraper ON coupon_sightings(scraper, seen_at);
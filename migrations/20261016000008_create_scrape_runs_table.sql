@@ -0,0 +1,26 @@
+-- Migration: 20261016000008_create_scrape_runs_table
+-- Description: Creates the scrape_runs table recording every run of every scraper
+-- Author: RinKokonoe
+
+-- Up Migration
+CREATE TABLE IF NOT EXISTS scrape_runs (
+    -- Primary key
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    
+    -- Scraper that ran
+    scraper TEXT NOT NULL,
+    
+    -- Timing information
+    started_at TEXT NOT NULL,         -- ISO 8601 / RFC 3339 timestamp
+    finished_at TEXT NOT NULL,        -- ISO 8601 / RFC 3339 timestamp
+    
+    -- Outcome
+    status TEXT NOT NULL,             -- success, empty, failed or timed_out
+    coupons_found INTEGER NOT NULL,
+    http_statuses TEXT NOT NULL,      -- JSON array of the HTTP statuses received, in order
+    bytes_fetched INTEGER NOT NULL,   -- Total size of the response bodies read
+    error TEXT                        -- Error message if the run failed or timed out
+);
+
+-- Index for reading a scraper's recent runs
+CREATE INDEX IF NOT EXISTS idx_scrape_runs_scraper ON scrape_runs(scraper, started_at);
@@ -0,0 +1,29 @@
+-- Migration: 20261016000009_create_notification_outbox_table
+-- Description: Creates the notification_outbox table of coupon announcements waiting to be delivered
+-- Author: RinKokonoe
+
+-- Up Migration
+CREATE TABLE IF NOT EXISTS notification_outbox (
+    -- Primary key
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    
+    -- Coupon to announce
+    coupon_id INTEGER NOT NULL REFERENCES coupons(id) ON DELETE CASCADE,
+    
+    -- Identifies the announcement, so it is queued once and backends can drop retried duplicates
+    idempotency_key TEXT NOT NULL UNIQUE,
+    
+    -- Delivery state
+    status TEXT NOT NULL,             -- pending, delivered, skipped or failed
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,                  -- Error of the last failed attempt, or why it was skipped
+    
+    -- Timing information
+    created_at TEXT NOT NULL,         -- ISO 8601 / RFC 3339 timestamp
+    next_attempt_at TEXT NOT NULL,    -- ISO 8601 / RFC 3339 timestamp
+    last_attempt_at TEXT,             -- ISO 8601 / RFC 3339 timestamp
+    delivered_at TEXT                 -- ISO 8601 / RFC 3339 timestamp
+);
+
+-- Index for finding the announcements that are due
+CREATE INDEX IF NOT EXISTS idx_notification_outbox_due ON notification_outbox(status, next_attempt_at);
@@ -0,0 +1,8 @@
+-- Migration: 20261016000010_add_embed_position_to_discord_messages
+-- Description: Records where a coupon's embed sits in Discord messages that announce several coupons
+-- Author: RinKokonoe
+
+-- Up Migration
+-- Messages posted before batching carried a single embed
+ALTER TABLE discord_messages ADD COLUMN embed_index INTEGER NOT NULL DEFAULT 0;  -- Position of the coupon's embed
+ALTER TABLE discord_messages ADD COLUMN embed_count INTEGER NOT NULL DEFAULT 1;  -- Number of embeds in the message
@@ -0,0 +1,22 @@
+-- Migration: 20261016000011_create_digest_runs_table
+-- Description: Creates the digest_runs table recording the windows covered by daily and weekly digests
+-- Author: RinKokonoe
+
+-- Up Migration
+CREATE TABLE IF NOT EXISTS digest_runs (
+    -- Primary key
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    
+    -- Digest information
+    frequency TEXT NOT NULL,          -- daily or weekly
+    coupon_count INTEGER NOT NULL,    -- Coupons in the digest; 0 if nothing was posted
+    targets INTEGER NOT NULL,         -- Notification targets the digest was posted to
+    
+    -- Timing information
+    window_start TEXT NOT NULL,       -- ISO 8601 / RFC 3339 timestamp
+    window_end TEXT NOT NULL,         -- ISO 8601 / RFC 3339 timestamp; the next window starts here
+    posted_at TEXT NOT NULL           -- ISO 8601 / RFC 3339 timestamp
+);
+
+-- Index for finding the latest run of each frequency
+CREATE INDEX IF NOT EXISTS idx_digest_runs_frequency ON digest_runs(frequency, window_end);
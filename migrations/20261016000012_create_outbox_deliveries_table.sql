@@ -0,0 +1,17 @@
+-- Migration: 20261016000012_create_outbox_deliveries_table
+-- Description: Creates the outbox_deliveries table of notification targets that already received an announcement
+-- Author: RinKokonoe
+
+-- Up Migration
+CREATE TABLE IF NOT EXISTS outbox_deliveries (
+    -- Announcement that was delivered
+    outbox_id INTEGER NOT NULL REFERENCES notification_outbox(id) ON DELETE CASCADE,
+    
+    -- Notifier or Discord target it was delivered to, e.g. slack or discord:default
+    target TEXT NOT NULL,
+    
+    -- Timing information
+    delivered_at TEXT NOT NULL,       -- ISO 8601 / RFC 3339 timestamp
+    
+    PRIMARY KEY (outbox_id, target)
+);
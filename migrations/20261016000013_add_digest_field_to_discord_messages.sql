@@ -0,0 +1,7 @@
+-- Migration: 20261016000013_add_digest_field_to_discord_messages
+-- Description: Records which field of a digest message lists a coupon
+-- Author: RinKokonoe
+
+-- Up Migration
+-- NULL for messages in which the coupon has an embed of its own
+ALTER TABLE discord_messages ADD COLUMN field_index INTEGER;  -- Position of the coupon's field in the digest embed
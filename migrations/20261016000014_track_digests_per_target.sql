@@ -0,0 +1,10 @@
+-- Migration: 20261016000014_track_digests_per_target
+-- Description: Records digest runs per Discord target and indexes the coupons queued for each target's digest
+-- Author: RinKokonoe
+
+-- Up Migration
+-- Runs recorded before this migration covered every target and have an empty target
+ALTER TABLE digest_runs ADD COLUMN target TEXT NOT NULL DEFAULT '';  -- Discord target the digest was posted to
+
+-- Index for finding the coupons a digest target accepted during a window
+CREATE INDEX IF NOT EXISTS idx_outbox_deliveries_target ON outbox_deliveries(target, delivered_at);
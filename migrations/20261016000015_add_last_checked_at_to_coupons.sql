@@ -0,0 +1,9 @@
+-- Migration: 20261016000015_add_last_checked_at_to_coupons
+-- Description: Records when a coupon was last checked, including checks that failed without a result
+-- Author: RinKokonoe
+
+-- Up Migration
+ALTER TABLE coupons ADD COLUMN last_checked_at TEXT;  -- ISO 8601 / RFC 3339 timestamp of the latest validation attempt
+
+-- Coupons validated before this migration were last checked when they were validated
+UPDATE coupons SET last_checked_at = validated_at;
@@ -0,0 +1,12 @@
+-- Migration: 20261016000016_create_data_migrations_table
+-- Description: Creates the data_migrations table recording the data migrations run in Rust at startup
+-- Author: RinKokonoe
+
+-- Up Migration
+CREATE TABLE IF NOT EXISTS data_migrations (
+    -- Name of the data migration
+    name TEXT PRIMARY KEY,
+    
+    -- Timing information
+    applied_at TEXT NOT NULL  -- ISO 8601 / RFC 3339 timestamp
+);
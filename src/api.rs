@@ -0,0 +1,539 @@
+use anyhow::{Context as AnyhowContext, Result};
+use axum::{
+    extract::{ConnectInfo, Path, Query, State},
+    http::{header, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tower_http::cors::CorsLayer;
+use tower_http::trace::TraceLayer;
+use tracing::{debug, error, info};
+
+use crate::commands::HEALTH_WINDOW;
+use crate::db;
+use crate::metrics;
+use crate::models::{
+    AppState, Config, Coupon, CouponFilter, CouponListing, CouponSighting, DiscordAuth,
+    QueueSizes, ScrapeRun, ScrapeStatus, ScraperHealth, ValidationRun,
+};
+
+/// Default number of coupons returned per page
+const DEFAULT_PER_PAGE: i64 = 20;
+
+/// Maximum number of coupons a client may request per page
+const MAX_PER_PAGE: i64 = 100;
+
+/// Shared state for API handlers
+#[derive(Clone)]
+struct ApiState {
+    app: Arc<Mutex<AppState>>,
+    rate_limiter: Arc<RateLimiter>,
+}
+
+/// Errors returned by API handlers
+#[derive(Debug, thiserror::Error)]
+enum ApiError {
+    #[error("Coupon not found")]
+    NotFound,
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("Rate limit exceeded, try again later")]
+    RateLimited,
+    #[error("Internal server error")]
+    Internal(#[from] anyhow::Error),
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            ApiError::NotFound => StatusCode::NOT_FOUND,
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Internal(e) => {
+                error!("API request failed: {:#}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+
+        let body = Json(serde_json::json!({ "error": self.to_string() }));
+        (status, body).into_response()
+    }
+}
+
+/// Fixed-window rate limiter keyed by client IP address
+struct RateLimiter {
+    limit: u64,
+    window: Duration,
+    clients: std::sync::Mutex<HashMap<IpAddr, (Instant, u64)>>,
+}
+
+impl RateLimiter {
+    /// Create a rate limiter allowing `limit` requests per minute (0 disables limiting)
+    fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            window: Duration::from_secs(60),
+            clients: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record a request from the given address and return whether it is allowed
+    fn check(&self, addr: IpAddr) -> bool {
+        if self.limit == 0 {
+            return true;
+        }
+
+        let now = Instant::now();
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+
+        // Forget clients whose window has passed so the map doesn't grow unbounded
+        if clients.len() > 10_000 {
+            let window = self.window;
+            clients.retain(|_, (start, _)| now.duration_since(*start) < window);
+        }
+
+        let entry = clients.entry(addr).or_insert((now, 0));
+        if now.duration_since(entry.0) >= self.window {
+            *entry = (now, 0);
+        }
+
+        entry.1 += 1;
+        entry.1 <= self.limit
+    }
+}
+
+/// Query parameters accepted by the coupon listing endpoint
+#[derive(Debug, Deserialize)]
+struct CouponQuery {
+    source: Option<String>,
+    valid: Option<bool>,
+    expired: Option<bool>,
+    expires_before: Option<DateTime<Utc>>,
+    page: Option<i64>,
+    per_page: Option<i64>,
+}
+
+/// Query parameters accepted by the validation, sighting and scrape run history endpoints
+#[derive(Debug, Deserialize)]
+struct HistoryQuery {
+    limit: Option<i64>,
+}
+
+/// Outcome of a single readiness check
+#[derive(Debug, Serialize)]
+struct Check {
+    ok: bool,
+    detail: String,
+}
+
+impl From<Result<String>> for Check {
+    fn from(result: Result<String>) -> Self {
+        match result {
+            Ok(detail) => Check { ok: true, detail },
+            Err(e) => Check {
+                ok: false,
+                detail: format!("{:#}", e),
+            },
+        }
+    }
+}
+
+/// Body of the readiness endpoint
+#[derive(Debug, Serialize)]
+struct Readiness {
+    ready: bool,
+    checks: BTreeMap<&'static str, Check>,
+}
+
+/// A scraper's latest run and next scheduled run
+#[derive(Debug, Serialize)]
+struct ScraperStatus {
+    scraper: String,
+    healthy: bool,
+    last_run: Option<DateTime<Utc>>,
+    last_status: Option<ScrapeStatus>,
+    next_run: Option<DateTime<Utc>>,
+}
+
+/// Body of the status endpoint
+#[derive(Debug, Serialize)]
+struct StatusReport {
+    version: &'static str,
+    started_at: DateTime<Utc>,
+    uptime_seconds: i64,
+    discord: DiscordAuth,
+    scrapers: Vec<ScraperStatus>,
+    queues: QueueSizes,
+}
+
+/// A page of coupons returned by the listing endpoint
+#[derive(Debug, Serialize)]
+struct CouponPage {
+    coupons: Vec<Coupon>,
+    page: i64,
+    per_page: i64,
+    total: i64,
+}
+
+/// Build the API router. The metrics and health endpoints are added after the rate
+/// limit layer so Prometheus and health checks polling them don't count against `api.rate_limit`
+fn create_router(state: ApiState) -> Router {
+    Router::new()
+        .route("/api/coupons", get(list_coupons))
+        .route("/api/coupons/:id", get(get_coupon))
+        .route("/api/coupons/:id/validations", get(list_validations))
+        .route("/api/coupons/:id/listings", get(list_listings))
+        .route("/api/coupons/:id/sightings", get(list_sightings))
+        .route("/api/scrapers", get(list_scrapers))
+        .route("/api/scrapers/:name/runs", get(list_scrape_runs))
+        .layer(middleware::from_fn_with_state(state.clone(), rate_limit))
+        .route("/metrics", get(export_metrics))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/status", get(status))
+        .layer(TraceLayer::new_for_http())
+        .layer(CorsLayer::permissive())
+        .with_state(state)
+}
+
+/// Reject requests from clients that exceeded `api.rate_limit`
+async fn rate_limit<B>(
+    State(state): State<ApiState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, ApiError> {
+    if !state.rate_limiter.check(addr.ip()) {
+        debug!("Rate limit exceeded for {}", addr.ip());
+        return Err(ApiError::RateLimited);
+    }
+
+    Ok(next.run(request).await)
+}
+
+/// List coupons with optional filtering and pagination
+async fn list_coupons(
+    State(state): State<ApiState>,
+    Query(query): Query<CouponQuery>,
+) -> Result<Json<CouponPage>, ApiError> {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query
+        .per_page
+        .unwrap_or(DEFAULT_PER_PAGE)
+        .clamp(1, MAX_PER_PAGE);
+
+    let filter = CouponFilter {
+        source: query.source,
+        is_valid: query.valid,
+        expired: query.expired,
+        expires_before: query.expires_before,
+        limit: per_page,
+        offset: page_offset(page, per_page)?,
+    };
+
+    let db_pool = state.app.lock().await.db_pool.clone();
+    let (coupons, total) = db::get_coupons_filtered(&db_pool, &filter).await?;
+
+    Ok(Json(CouponPage {
+        coupons,
+        page,
+        per_page,
+        total,
+    }))
+}
+
+/// Number of coupons before a page, rejecting pages too far out to count
+fn page_offset(page: i64, per_page: i64) -> Result<i64, ApiError> {
+    (page - 1)
+        .checked_mul(per_page)
+        .ok_or_else(|| ApiError::BadRequest(format!("Page {} is out of range", page)))
+}
+
+/// Get a single coupon by id
+async fn get_coupon(
+    State(state): State<ApiState>,
+    Path(id): Path<i64>,
+) -> Result<Json<Coupon>, ApiError> {
+    let db_pool = state.app.lock().await.db_pool.clone();
+
+    db::get_coupon_by_id(&db_pool, id)
+        .await?
+        .map(Json)
+        .ok_or(ApiError::NotFound)
+}
+
+/// List a coupon's validation attempts, newest first
+async fn list_validations(
+    State(state): State<ApiState>,
+    Path(id): Path<i64>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Json<Vec<ValidationRun>>, ApiError> {
+    let limit = query.limit.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+    let db_pool = state.app.lock().await.db_pool.clone();
+
+    if db::get_coupon_by_id(&db_pool, id).await?.is_none() {
+        return Err(ApiError::NotFound);
+    }
+
+    let runs = db::get_validation_runs(&db_pool, id, limit).await?;
+    Ok(Json(runs))
+}
+
+/// List the near-duplicate listings merged into a coupon, oldest first
+async fn list_listings(
+    State(state): State<ApiState>,
+    Path(id): Path<i64>,
+) -> Result<Json<Vec<CouponListing>>, ApiError> {
+    let db_pool = state.app.lock().await.db_pool.clone();
+
+    if db::get_coupon_by_id(&db_pool, id).await?.is_none() {
+        return Err(ApiError::NotFound);
+    }
+
+    let listings = db::get_coupon_listings(&db_pool, id).await?;
+    Ok(Json(listings))
+}
+
+/// List the scrape runs that found a coupon, newest first
+async fn list_sightings(
+    State(state): State<ApiState>,
+    Path(id): Path<i64>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Json<Vec<CouponSighting>>, ApiError> {
+    let limit = query.limit.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+    let db_pool = state.app.lock().await.db_pool.clone();
+
+    if db::get_coupon_by_id(&db_pool, id).await?.is_none() {
+        return Err(ApiError::NotFound);
+    }
+
+    let sightings = db::get_coupon_sightings(&db_pool, id, limit).await?;
+    Ok(Json(sightings))
+}
+
+/// Export metrics in the Prometheus text format
+async fn export_metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        metrics::render(),
+    )
+}
+
+/// Liveness probe: answers as long as the process is running
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+/// Readiness probe: checks that the database answers, all migrations are applied and
+/// the Discord bot token, if set, was accepted. Responds `503` if any check fails.
+async fn readyz(State(state): State<ApiState>) -> (StatusCode, Json<Readiness>) {
+    let app = state.app.lock().await;
+    let db_pool = app.db_pool.clone();
+    let discord_auth = app.discord_auth;
+    drop(app);
+
+    let mut checks = BTreeMap::new();
+
+    let database = db::ping(&db_pool).await.map(|()| "reachable".to_string());
+    checks.insert("database", Check::from(database));
+
+    let migrations = match db::get_pending_migrations(&db_pool).await {
+        Ok(0) => Ok("up to date".to_string()),
+        Ok(pending) => Err(anyhow::anyhow!("{} migrations pending", pending)),
+        Err(e) => Err(e),
+    };
+    checks.insert("migrations", Check::from(migrations));
+
+    checks.insert(
+        "discord",
+        Check {
+            ok: discord_auth != DiscordAuth::Failed,
+            detail: discord_auth.as_str().to_string(),
+        },
+    );
+
+    let ready = checks.values().all(|check| check.ok);
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(Readiness { ready, checks }))
+}
+
+/// Report the version, uptime, Discord authentication, every scraper's last and next run
+/// and the queue sizes
+async fn status(State(state): State<ApiState>) -> Result<Json<StatusReport>, ApiError> {
+    let app = state.app.lock().await;
+    let db_pool = app.db_pool.clone();
+    let unhealthy_after = app.config.scraping.unhealthy_after;
+    let started_at = app.started_at;
+    let discord = app.discord_auth;
+    drop(app);
+
+    let health = db::get_scraper_health(&db_pool, HEALTH_WINDOW, unhealthy_after).await?;
+    let schedules = db::get_scraper_schedules(&db_pool).await?;
+
+    let mut scrapers: Vec<ScraperStatus> = health
+        .into_iter()
+        .map(|health| ScraperStatus {
+            next_run: schedules
+                .iter()
+                .find(|schedule| schedule.scraper == health.scraper)
+                .map(|schedule| schedule.next_run),
+            scraper: health.scraper,
+            healthy: health.healthy,
+            last_run: health.last_run,
+            last_status: health.last_status,
+        })
+        .collect();
+
+    // Scrapers that are scheduled but haven't run yet
+    for schedule in &schedules {
+        if !scrapers.iter().any(|scraper| scraper.scraper == schedule.scraper) {
+            scrapers.push(ScraperStatus {
+                scraper: schedule.scraper.clone(),
+                healthy: true,
+                last_run: None,
+                last_status: None,
+                next_run: Some(schedule.next_run),
+            });
+        }
+    }
+    scrapers.sort_by(|a, b| a.scraper.cmp(&b.scraper));
+
+    Ok(Json(StatusReport {
+        version: env!("CARGO_PKG_VERSION"),
+        started_at,
+        uptime_seconds: (Utc::now() - started_at).num_seconds(),
+        discord,
+        scrapers,
+        queues: db::get_queue_sizes(&db_pool).await?,
+    }))
+}
+
+/// Summarize the health of every scraper from its recent runs
+async fn list_scrapers(State(state): State<ApiState>) -> Result<Json<Vec<ScraperHealth>>, ApiError> {
+    let app = state.app.lock().await;
+    let db_pool = app.db_pool.clone();
+    let unhealthy_after = app.config.scraping.unhealthy_after;
+    drop(app);
+
+    let health = db::get_scraper_health(&db_pool, HEALTH_WINDOW, unhealthy_after).await?;
+    Ok(Json(health))
+}
+
+/// List a scraper's runs, newest first
+async fn list_scrape_runs(
+    State(state): State<ApiState>,
+    Path(name): Path<String>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Json<Vec<ScrapeRun>>, ApiError> {
+    let limit = query.limit.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+    let db_pool = state.app.lock().await.db_pool.clone();
+
+    let runs = db::get_scrape_runs(&db_pool, &name, limit).await?;
+    if runs.is_empty() {
+        return Err(ApiError::NotFound);
+    }
+
+    Ok(Json(runs))
+}
+
+/// Start the HTTP API server
+pub async fn start_api_server(
+    state: Arc<Mutex<AppState>>,
+    config: &Config,
+    shutdown: CancellationToken,
+) -> Result<JoinHandle<()>> {
+    let addr = SocketAddr::from(([0, 0, 0, 0], config.api.port));
+
+    let api_state = ApiState {
+        app: state,
+        rate_limiter: Arc::new(RateLimiter::new(config.api.rate_limit)),
+    };
+    let router = create_router(api_state);
+
+    let server = axum::Server::try_bind(&addr)
+        .with_context(|| format!("Failed to bind API server to {}", addr))?
+        .serve(router.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown.cancelled_owned());
+    info!("API server listening on {}", addr);
+
+    let handle = tokio::spawn(async move {
+        if let Err(e) = server.await {
+            error!("API server error: {}", e);
+        }
+    });
+
+    Ok(handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    const OTHER_CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+
+    #[test]
+    fn pages_out_of_range_are_rejected() {
+        assert_eq!(page_offset(1, 20).unwrap(), 0);
+        assert_eq!(page_offset(3, 20).unwrap(), 40);
+        assert!(matches!(page_offset(i64::MAX, 20), Err(ApiError::BadRequest(_))));
+    }
+
+    #[test]
+    fn rate_limiter_rejects_requests_over_the_limit() {
+        let limiter = RateLimiter::new(2);
+
+        assert!(limiter.check(CLIENT));
+        assert!(limiter.check(CLIENT));
+        assert!(!limiter.check(CLIENT));
+        assert!(!limiter.check(CLIENT));
+    }
+
+    #[test]
+    fn rate_limiter_counts_clients_separately() {
+        let limiter = RateLimiter::new(1);
+
+        assert!(limiter.check(CLIENT));
+        assert!(!limiter.check(CLIENT));
+        assert!(limiter.check(OTHER_CLIENT));
+    }
+
+    #[test]
+    fn rate_limiter_resets_after_the_window() {
+        let limiter = RateLimiter {
+            window: Duration::from_millis(20),
+            ..RateLimiter::new(1)
+        };
+
+        assert!(limiter.check(CLIENT));
+        assert!(!limiter.check(CLIENT));
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(limiter.check(CLIENT));
+    }
+
+    #[test]
+    fn zero_limit_disables_rate_limiting() {
+        let limiter = RateLimiter::new(0);
+
+        for _ in 0..1000 {
+            assert!(limiter.check(CLIENT));
+        }
+    }
+}
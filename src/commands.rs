@@ -0,0 +1,372 @@
+use anyhow::Result;
+use chrono::Utc;
+use serenity::{
+    builder::{CreateApplicationCommands, CreateEmbed},
+    model::application::{
+        command::CommandOptionType,
+        interaction::application_command::{ApplicationCommandInteraction, CommandDataOptionValue},
+    },
+};
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::db;
+use crate::discord::create_coupon_embed;
+use crate::models::{AppState, Coupon, CouponFilter};
+
+/// Maximum number of coupons listed by `/coupons`
+const MAX_LISTED_COUPONS: i64 = 10;
+
+/// Number of recent runs summarized by `/scrapers`
+pub const HEALTH_WINDOW: i64 = 20;
+
+/// Maximum number of other listings shown by `/coupon`, keeping the field under Discord's limit
+const MAX_LISTED_LISTINGS: usize = 5;
+
+/// Register the bot's slash commands
+pub fn register_commands(commands: &mut CreateApplicationCommands) -> &mut CreateApplicationCommands {
+    commands
+        .create_application_command(|command| {
+            command
+                .name("coupons")
+                .description("List current valid coupons")
+                .create_option(|option| {
+                    option
+                        .name("source")
+                        .description("Only show coupons from this source, e.g. \"Cursor AI\"")
+                        .kind(CommandOptionType::String)
+                        .required(false)
+                })
+        })
+        .create_application_command(|command| {
+            command
+                .name("coupon")
+                .description("Show a single coupon")
+                .create_option(|option| {
+                    option
+                        .name("id")
+                        .description("Coupon id")
+                        .kind(CommandOptionType::Integer)
+                        .min_int_value(1)
+                        .required(true)
+                })
+        })
+        .create_application_command(|command| {
+            command
+                .name("sources")
+                .description("List coupon sources and how many coupons each has")
+        })
+        .create_application_command(|command| {
+            command
+                .name("status")
+                .description("Show the bot's scraping status")
+        })
+        .create_application_command(|command| {
+            command
+                .name("scrapers")
+                .description("Show the health of each scraper")
+        })
+}
+
+/// Run a slash command and build the embed to reply with
+pub async fn handle_command(
+    state: &Arc<Mutex<AppState>>,
+    command: &ApplicationCommandInteraction,
+) -> Result<CreateEmbed> {
+    let db_pool = state.lock().await.db_pool.clone();
+
+    match command.data.name.as_str() {
+        "coupons" => {
+            let source = match option_value(command, "source") {
+                Some(CommandDataOptionValue::String(source)) => Some(source.clone()),
+                _ => None,
+            };
+            list_coupons(&db_pool, source).await
+        }
+        "coupon" => match option_value(command, "id") {
+            Some(CommandDataOptionValue::Integer(id)) => show_coupon(&db_pool, *id).await,
+            _ => Ok(error_embed("Please provide a coupon id")),
+        },
+        "sources" => list_sources(&db_pool).await,
+        "status" => show_status(state, &db_pool).await,
+        "scrapers" => show_scrapers(state, &db_pool).await,
+        other => Ok(error_embed(&format!("Unknown command: /{}", other))),
+    }
+}
+
+/// Look up the resolved value of a command option
+fn option_value<'a>(
+    command: &'a ApplicationCommandInteraction,
+    name: &str,
+) -> Option<&'a CommandDataOptionValue> {
+    command
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| option.resolved.as_ref())
+}
+
+/// `/coupons [source]`: list the newest valid, unexpired coupons
+async fn list_coupons(db_pool: &SqlitePool, source: Option<String>) -> Result<CreateEmbed> {
+    let filter = CouponFilter {
+        source: source.clone(),
+        is_valid: Some(true),
+        expired: Some(false),
+        expires_before: None,
+        limit: MAX_LISTED_COUPONS,
+        offset: 0,
+    };
+    let (coupons, total) = db::get_coupons_filtered(db_pool, &filter).await?;
+
+    let mut embed = CreateEmbed::default();
+    match &source {
+        Some(source) => embed.title(format!("Current coupons from {}", source)),
+        None => embed.title("Current coupons"),
+    };
+
+    if coupons.is_empty() {
+        embed.description("No valid coupons right now. Check back later!");
+    } else {
+        for coupon in &coupons {
+            embed.field(
+                format!("#{} {}", coupon.id.unwrap_or_default(), coupon.name),
+                summarize_coupon(coupon),
+                false,
+            );
+        }
+
+        if total > coupons.len() as i64 {
+            embed.footer(|f| {
+                f.text(format!(
+                    "Showing {} of {} coupons. Use /coupon <id> for details.",
+                    coupons.len(),
+                    total
+                ))
+            });
+        }
+    }
+
+    embed.color(0x00_c8_ff);
+    Ok(embed)
+}
+
+/// `/coupon <id>`: show a single coupon with the same embed used for notifications,
+/// plus the outcome of its latest validation and when and where else it was found
+async fn show_coupon(db_pool: &SqlitePool, id: i64) -> Result<CreateEmbed> {
+    let coupon = match db::get_coupon_by_id(db_pool, id).await? {
+        Some(coupon) => coupon,
+        None => return Ok(error_embed(&format!("No coupon with id {}", id))),
+    };
+
+    let mut embed = create_coupon_embed(&coupon);
+    if let Some(run) = db::get_latest_validation_run(db_pool, id).await? {
+        let outcome = match run.is_valid {
+            Some(true) => "Valid",
+            Some(false) => "Invalid",
+            None => "Error",
+        };
+        embed.field(
+            "Last validation",
+            format!(
+                "{} <t:{}:R> by {}\n{}",
+                outcome,
+                run.validated_at.timestamp(),
+                run.validator,
+                run.message.as_deref().unwrap_or("No details")
+            ),
+            false,
+        );
+    }
+
+    if let (Some(first_seen), Some(last_seen)) = (coupon.first_seen, coupon.last_seen) {
+        let mut seen = format!(
+            "First <t:{}:R>, last <t:{}:R>",
+            first_seen.timestamp(),
+            last_seen.timestamp()
+        );
+        if coupon.likely_dead {
+            seen.push_str("\n⚠️ No longer listed by its sources");
+        }
+        embed.field("Seen", seen, false);
+    }
+
+    let listings = db::get_coupon_listings(db_pool, id).await?;
+    if !listings.is_empty() {
+        let lines: Vec<String> = listings
+            .iter()
+            .take(MAX_LISTED_LISTINGS)
+            .map(|listing| {
+                format!(
+                    "[{}]({}) <t:{}:R>",
+                    listing.source,
+                    listing.url,
+                    listing.first_seen.timestamp()
+                )
+            })
+            .collect();
+        embed.field("Also listed on", lines.join("\n"), false);
+    }
+
+    Ok(embed)
+}
+
+/// `/sources`: list every source with its coupon counts
+async fn list_sources(db_pool: &SqlitePool) -> Result<CreateEmbed> {
+    let sources = db::get_source_stats(db_pool).await?;
+
+    let mut embed = CreateEmbed::default();
+    embed.title("Coupon sources");
+
+    if sources.is_empty() {
+        embed.description("No coupons have been found yet.");
+    } else {
+        let lines: Vec<String> = sources
+            .iter()
+            .map(|s| format!("**{}**: {} valid / {} total", s.source, s.valid, s.total))
+            .collect();
+        embed.description(lines.join("\n"));
+    }
+
+    embed.color(0x00_c8_ff);
+    Ok(embed)
+}
+
+/// `/status`: show scrape timing, coupon counts and failing scrapers
+async fn show_status(state: &Arc<Mutex<AppState>>, db_pool: &SqlitePool) -> Result<CreateEmbed> {
+    let unhealthy_after = state.lock().await.config.scraping.unhealthy_after;
+    let last_scrape = db::get_last_scrape_time(db_pool).await?;
+    let stats = db::get_coupon_stats(db_pool).await?;
+    let schedules = db::get_scraper_schedules(db_pool).await?;
+    let health = db::get_scraper_health(db_pool, HEALTH_WINDOW, unhealthy_after).await?;
+
+    let mut embed = CreateEmbed::default();
+    embed.title("RinKokonoe status");
+
+    embed.field(
+        "Last scrape",
+        last_scrape
+            .map(|t| format!("<t:{}:R>", t.timestamp()))
+            .unwrap_or_else(|| "Not yet".to_string()),
+        true,
+    );
+    embed.field(
+        "Coupons",
+        format!("{} valid / {} total, {} posted", stats.valid, stats.total, stats.posted),
+        true,
+    );
+
+    if let Some(next) = schedules.iter().min_by_key(|s| s.next_run) {
+        embed.field(
+            "Next scrape",
+            format!("{} <t:{}:R>", next.scraper, next.next_run.timestamp()),
+            true,
+        );
+    }
+
+    let unhealthy: Vec<&str> = health
+        .iter()
+        .filter(|h| !h.healthy)
+        .map(|h| h.scraper.as_str())
+        .collect();
+    if !unhealthy.is_empty() {
+        embed.field(
+            "⚠️ Failing scrapers",
+            format!("{}\nUse /scrapers for details.", unhealthy.join(", ")),
+            false,
+        );
+    }
+
+    embed.footer(|f| f.text(format!("RinKokonoe v{}", env!("CARGO_PKG_VERSION"))));
+    embed.color(0x00_c8_ff);
+    embed.timestamp(Utc::now());
+    Ok(embed)
+}
+
+/// `/scrapers`: show each scraper's latest run, failure streak and success rate
+async fn show_scrapers(state: &Arc<Mutex<AppState>>, db_pool: &SqlitePool) -> Result<CreateEmbed> {
+    let unhealthy_after = state.lock().await.config.scraping.unhealthy_after;
+    let health = db::get_scraper_health(db_pool, HEALTH_WINDOW, unhealthy_after).await?;
+
+    let mut embed = CreateEmbed::default();
+    embed.title("Scraper health");
+
+    if health.is_empty() {
+        embed.description("No scraper has run yet.");
+    }
+
+    for scraper in &health {
+        let mut lines = Vec::new();
+        if let (Some(last_run), Some(status)) = (scraper.last_run, scraper.last_status) {
+            lines.push(format!(
+                "Last run <t:{}:R>: {}, {} coupons",
+                last_run.timestamp(),
+                status.as_str(),
+                scraper.last_coupons_found
+            ));
+        }
+        lines.push(format!(
+            "{:.0}% of the last {} runs found coupons",
+            scraper.success_rate * 100.0,
+            scraper.runs
+        ));
+        if scraper.consecutive_failures > 0 {
+            lines.push(format!("{} runs in a row without coupons", scraper.consecutive_failures));
+        }
+        if let Some(error) = &scraper.last_error {
+            lines.push(format!("`{}`", truncate(error, 200)));
+        }
+
+        let icon = if scraper.healthy { "✅" } else { "⚠️" };
+        embed.field(format!("{} {}", icon, scraper.scraper), lines.join("\n"), false);
+    }
+
+    let color = if health.iter().all(|h| h.healthy) {
+        0x00_c8_ff
+    } else {
+        0xff_aa_00
+    };
+    embed.color(color);
+    embed.timestamp(Utc::now());
+    Ok(embed)
+}
+
+/// Shorten text to at most `max` characters for an embed field
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+
+    let mut short: String = text.chars().take(max - 1).collect();
+    short.push('…');
+    short
+}
+
+/// One-line summary of a coupon for list embeds
+fn summarize_coupon(coupon: &Coupon) -> String {
+    let mut parts = vec![format!("Code: `{}`", coupon.code)];
+
+    if let Some(discount) = coupon.discount_percentage {
+        parts.push(format!("{}% off", discount));
+    }
+
+    if let Some(expiry) = coupon.expiry {
+        parts.push(format!("expires <t:{}:R>", expiry.timestamp()));
+    }
+
+    if coupon.likely_dead {
+        parts.push("⚠️ no longer listed".to_string());
+    }
+
+    format!("{}\n[Apply here]({})", parts.join(" • "), coupon.url)
+}
+
+/// Embed shown when a command can't be answered
+pub fn error_embed(message: &str) -> CreateEmbed {
+    let mut embed = CreateEmbed::default();
+    embed.title("⚠️ Error");
+    embed.description(message);
+    embed.color(0xff_44_44);
+    embed
+}
@@ -1,13 +1,19 @@
 use anyhow::{Context as AnyhowContext, Result};
-use config::{Config as ConfigCrate, ConfigBuilder, Environment, File};
+use config::{builder::DefaultState, Config as ConfigCrate, ConfigBuilder, ConfigError, Environment, File};
+use std::collections::HashSet;
 use std::env;
 use std::path::Path;
 use std::sync::Arc;
 use tracing::{debug, info, warn};
 
+use crate::digest::DigestSchedule;
+use crate::discord::{DEFAULT_TARGET, MAX_EMBEDS_PER_MESSAGE};
 use crate::models::{
-    ApiConfig, Config, DiscordConfig, ProxyConfig, RssConfig, ScrapingConfig, ValidationConfig,
+    ApiConfig, Config, DigestConfig, DiscordConfig, NotificationTarget, NotifierBackend, NotifierConfig,
+    OutboxConfig, ProxyConfig, RssConfig, ScrapeSchedule, ScrapingConfig, ShutdownConfig,
+    SourceConfig, ValidationConfig, WebhookConfig,
 };
+use crate::proxy::ProxyPool;
 
 /// Load configuration from files and environment variables
 pub fn load_config() -> Result<Arc<Config>> {
@@ -16,22 +22,7 @@ pub fn load_config() -> Result<Arc<Config>> {
     info!("Loading configuration from {}", config_path);
 
     // Build configuration with defaults, file, and environment variables
-    let config_builder = ConfigCrate::builder()
-        .set_default("discord.command_prefix", "!")?
-        .set_default("discord.status_message", "Scraping coupons")?
-        .set_default("scraping.default_interval", 60)?
-        .set_default("scraping.max_concurrent", 10)?
-        .set_default("scraping.user_agent", "RinKokonoe Coupon Bot/1.0")?
-        .set_default("rss.items_per_feed", 30)?
-        .set_default("rss.refresh_interval", 60)?
-        .set_default("api.enable", true)?
-        .set_default("api.port", 8080)?
-        .set_default("api.rate_limit", 60)?
-        .set_default("proxy.enable", false)?
-        .set_default("proxy.proxies", "")?
-        .set_default("proxy.rotate_after", 100)?
-        .set_default("validation.enable", true)?
-        .set_default("validation.timeout", 30)?;
+    let config_builder = with_defaults(ConfigCrate::builder())?;
 
     // Load config file if it exists
     let config_builder = if Path::new(&config_path).exists() {
@@ -49,8 +40,63 @@ pub fn load_config() -> Result<Arc<Config>> {
     );
 
     // Build the configuration
-    let config = config_builder.build()?;
+    parse_config(config_builder.build()?)
+}
+
+/// Load configuration from TOML on top of the defaults, without files or environment variables
+#[cfg(test)]
+pub fn config_from_toml(toml: &str) -> Result<Arc<Config>> {
+    let config_builder = with_defaults(ConfigCrate::builder())?
+        .add_source(File::from_str(toml, config::FileFormat::Toml));
+
+    parse_config(config_builder.build()?)
+}
 
+/// Set the default of every setting
+fn with_defaults(config_builder: ConfigBuilder<DefaultState>) -> Result<ConfigBuilder<DefaultState>> {
+    let config_builder = config_builder
+        .set_default("discord.command_prefix", "!")?
+        .set_default("discord.status_message", "Scraping coupons")?
+        .set_default("discord.enable_commands", true)?
+        .set_default("discord.invalid_post_action", "edit")?
+        .set_default("discord.batch_size", 10)?
+        .set_default("discord.digest_threshold", 0)?
+        .set_default("scraping.default_interval", 60)?
+        .set_default("scraping.max_concurrent", 10)?
+        .set_default("scraping.scraper_timeout", 120)?
+        .set_default("scraping.user_agent", "RinKokonoe Coupon Bot/1.0")?
+        .set_default("scraping.definitions_dir", "scrapers")?
+        .set_default("scraping.duplicate_similarity", 0.8)?
+        .set_default("scraping.likely_dead_after", 3)?
+        .set_default("scraping.unhealthy_after", 3)?
+        .set_default("rss.items_per_feed", 30)?
+        .set_default("rss.refresh_interval", 60)?
+        .set_default("api.enable", true)?
+        .set_default("api.port", 8080)?
+        .set_default("api.rate_limit", 60)?
+        .set_default("proxy.enable", false)?
+        .set_default("proxy.proxies", "")?
+        .set_default("proxy.rotate_after", 100)?
+        .set_default("validation.enable", true)?
+        .set_default("validation.timeout", 30)?
+        .set_default("validation.revalidate_interval", 360)?
+        .set_default("validation.revalidate_after", 24)?
+        .set_default("validation.revalidate_batch", 50)?
+        .set_default("validation.notify_dead", true)?
+        .set_default("outbox.poll_interval", 5)?
+        .set_default("outbox.max_attempts", 10)?
+        .set_default("outbox.batch_delay", 30)?
+        .set_default("digest.time", "09:00")?
+        .set_default("digest.timezone", "UTC")?
+        .set_default("digest.weekday", "mon")?
+        .set_default("digest.expiring_within", 48)?
+        .set_default("shutdown.drain_timeout", 30)?;
+
+    Ok(config_builder)
+}
+
+/// Convert loaded settings into a validated `Config`
+fn parse_config(config: ConfigCrate) -> Result<Arc<Config>> {
     // Convert to our Config struct
     let discord_config = DiscordConfig {
         command_prefix: config.get_string("discord.command_prefix")?,
@@ -61,12 +107,30 @@ pub fn load_config() -> Result<Arc<Config>> {
         channel_id: config
             .get_string("discord.channel_id")
             .ok(),
+        enable_commands: config.get_bool("discord.enable_commands")?,
+        guild_id: config
+            .get_string("discord.guild_id")
+            .ok(),
+        invalid_post_action: config
+            .get("discord.invalid_post_action")
+            .context("discord.invalid_post_action must be one of edit, delete or keep")?,
+        targets: get_optional(&config, "discord.targets")?.unwrap_or_default(),
+        batch_size: config.get_int("discord.batch_size")? as u64,
+        digest_threshold: config.get_int("discord.digest_threshold")? as u64,
+        digest: get_optional(&config, "discord.digest")?,
     };
 
     let scraping_config = ScrapingConfig {
         default_interval: config.get_int("scraping.default_interval")? as u64,
         max_concurrent: config.get_int("scraping.max_concurrent")? as u64,
+        scraper_timeout: config.get_int("scraping.scraper_timeout")? as u64,
         user_agent: config.get_string("scraping.user_agent")?,
+        schedules: get_optional(&config, "scraping.schedules")?.unwrap_or_default(),
+        sources: get_optional(&config, "scraping.sources")?.unwrap_or_default(),
+        definitions_dir: config.get_string("scraping.definitions_dir")?,
+        duplicate_similarity: config.get_float("scraping.duplicate_similarity")?,
+        likely_dead_after: config.get_int("scraping.likely_dead_after")? as u64,
+        unhealthy_after: config.get_int("scraping.unhealthy_after")? as u64,
     };
 
     let rss_config = RssConfig {
@@ -89,6 +153,27 @@ pub fn load_config() -> Result<Arc<Config>> {
     let validation_config = ValidationConfig {
         enable: config.get_bool("validation.enable")?,
         timeout: config.get_int("validation.timeout")? as u64,
+        revalidate_interval: config.get_int("validation.revalidate_interval")? as u64,
+        revalidate_after: config.get_int("validation.revalidate_after")? as u64,
+        revalidate_batch: config.get_int("validation.revalidate_batch")? as u64,
+        notify_dead: config.get_bool("validation.notify_dead")?,
+    };
+
+    let outbox_config = OutboxConfig {
+        poll_interval: config.get_int("outbox.poll_interval")? as u64,
+        max_attempts: config.get_int("outbox.max_attempts")? as u32,
+        batch_delay: config.get_int("outbox.batch_delay")? as u64,
+    };
+
+    let digest_config = DigestConfig {
+        time: config.get_string("digest.time")?,
+        timezone: config.get_string("digest.timezone")?,
+        weekday: config.get_string("digest.weekday")?,
+        expiring_within: config.get_int("digest.expiring_within")? as u64,
+    };
+
+    let shutdown_config = ShutdownConfig {
+        drain_timeout: config.get_int("shutdown.drain_timeout")? as u64,
     };
 
     let app_config = Config {
@@ -98,6 +183,11 @@ pub fn load_config() -> Result<Arc<Config>> {
         api: api_config,
         proxy: proxy_config,
         validation: validation_config,
+        outbox: outbox_config,
+        digest: digest_config,
+        shutdown: shutdown_config,
+        notifiers: get_optional(&config, "notifiers")?.unwrap_or_default(),
+        webhooks: get_optional(&config, "webhooks")?.unwrap_or_default(),
     };
 
     // Validate configuration
@@ -107,17 +197,53 @@ pub fn load_config() -> Result<Arc<Config>> {
     Ok(Arc::new(app_config))
 }
 
+/// Deserialize an optional structured value (such as an array of tables) from the configuration
+fn get_optional<'de, T: serde::Deserialize<'de>>(config: &ConfigCrate, key: &str) -> Result<Option<T>> {
+    match config.get::<T>(key) {
+        Ok(value) => Ok(Some(value)),
+        Err(ConfigError::NotFound(_)) => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Invalid configuration value for {}", key)),
+    }
+}
+
 /// Validate the configuration to ensure required values are present and valid
 fn validate_config(config: &Config) -> Result<()> {
     // Validate Discord token from environment
-    if env::var("DISCORD_TOKEN").is_err() && 
-       env::var("RIN_DISCORD_TOKEN").is_err() && 
-       config.discord.webhook_url.is_none() {
+    let has_token = env::var("DISCORD_TOKEN").is_ok() || env::var("RIN_DISCORD_TOKEN").is_ok();
+    let has_webhook = config.discord.webhook_url.is_some()
+        || config.discord.targets.iter().any(|target| target.webhook_url.is_some());
+    if !has_token && !has_webhook && config.notifiers.is_empty() {
         return Err(anyhow::anyhow!(
-            "DISCORD_TOKEN environment variable or discord.webhook_url must be set"
+            "DISCORD_TOKEN environment variable, discord.webhook_url or a [[notifiers]] entry must be set"
         ));
     }
 
+    // Validate notification targets
+    let mut target_names = HashSet::new();
+    for target in &config.discord.targets {
+        validate_target(target, has_token)?;
+        if !target_names.insert(target.name.as_str()) {
+            return Err(anyhow::anyhow!(
+                "Duplicate notification target name: {}",
+                target.name
+            ));
+        }
+    }
+
+    // Validate other notification backends
+    let mut notifier_names = HashSet::new();
+    for notifier in &config.notifiers {
+        validate_notifier(notifier)?;
+        if !notifier_names.insert(notifier.name.as_str()) {
+            return Err(anyhow::anyhow!("Duplicate notifier name: {}", notifier.name));
+        }
+    }
+
+    // Validate event webhooks
+    for webhook in &config.webhooks {
+        validate_webhook(webhook)?;
+    }
+
     // Validate scraping interval
     if config.scraping.default_interval < 1 {
         return Err(anyhow::anyhow!(
@@ -125,14 +251,250 @@ fn validate_config(config: &Config) -> Result<()> {
         ));
     }
 
+    if config.scraping.max_concurrent < 1 {
+        return Err(anyhow::anyhow!("scraping.max_concurrent must be at least 1"));
+    }
+
+    if config.scraping.scraper_timeout < 1 {
+        return Err(anyhow::anyhow!(
+            "scraping.scraper_timeout must be at least 1 second"
+        ));
+    }
+
+    if !(1..=MAX_EMBEDS_PER_MESSAGE as u64).contains(&config.discord.batch_size) {
+        return Err(anyhow::anyhow!(
+            "discord.batch_size must be between 1 and {}",
+            MAX_EMBEDS_PER_MESSAGE
+        ));
+    }
+
+    if config.outbox.poll_interval < 1 {
+        return Err(anyhow::anyhow!("outbox.poll_interval must be at least 1 second"));
+    }
+
+    if config.outbox.max_attempts < 1 {
+        return Err(anyhow::anyhow!("outbox.max_attempts must be at least 1"));
+    }
+
+    if config.scraping.unhealthy_after < 1 {
+        return Err(anyhow::anyhow!("scraping.unhealthy_after must be at least 1"));
+    }
+
+    // 0 would merge every coupon of a vendor that shares a code
+    let similarity = config.scraping.duplicate_similarity;
+    if !(similarity > 0.0 && similarity <= 1.0) {
+        return Err(anyhow::anyhow!(
+            "scraping.duplicate_similarity must be greater than 0 and at most 1"
+        ));
+    }
+
+    // Validate the digest schedule
+    DigestSchedule::from_config(&config.digest)?;
+
+    // Validate per-scraper schedules
+    for schedule in &config.scraping.schedules {
+        ScrapeSchedule::from_config(schedule)?;
+    }
+
+    // Validate generic scraper sources
+    for source in &config.scraping.sources {
+        validate_source(source)?;
+    }
+
+    // Validate re-validation settings
+    if config.validation.revalidate_interval > 0 && config.validation.revalidate_batch < 1 {
+        return Err(anyhow::anyhow!(
+            "validation.revalidate_batch must be at least 1"
+        ));
+    }
+
+    // Validate feed settings
+    if config.rss.refresh_interval < 1 {
+        return Err(anyhow::anyhow!("rss.refresh_interval must be at least 1 minute"));
+    }
+
+    if config.rss.items_per_feed < 1 {
+        return Err(anyhow::anyhow!("rss.items_per_feed must be at least 1"));
+    }
+
+    // Validate proxy settings if proxies are enabled
+    if config.proxy.enable {
+        let proxies = ProxyPool::parse_proxy_list(&config.proxy.proxies)?;
+        if proxies.is_empty() {
+            return Err(anyhow::anyhow!(
+                "proxy.proxies must list at least one proxy when proxy.enable is true"
+            ));
+        }
+
+        if config.proxy.rotate_after < 1 {
+            return Err(anyhow::anyhow!("proxy.rotate_after must be at least 1"));
+        }
+    }
+
     // Validate API port if API is enabled
-    if config.api.enable && (config.api.port < 1024 || config.api.port > 65535) {
+    if config.api.enable && config.api.port < 1024 {
         return Err(anyhow::anyhow!("api.port must be between 1024 and 65535"));
     }
 
     Ok(())
 }
 
+/// Validate a notification target's destination and filter
+fn validate_target(target: &NotificationTarget, has_token: bool) -> Result<()> {
+    if target.name.trim().is_empty() {
+        return Err(anyhow::anyhow!("discord.targets entries must have a name"));
+    }
+
+    if target.name == DEFAULT_TARGET {
+        return Err(anyhow::anyhow!(
+            "Notification target name '{}' is reserved for discord.webhook_url and discord.channel_id",
+            DEFAULT_TARGET
+        ));
+    }
+
+    match (&target.webhook_url, &target.channel_id) {
+        (Some(webhook_url), None) => {
+            reqwest::Url::parse(webhook_url).with_context(|| {
+                format!("Invalid webhook URL for notification target {}", target.name)
+            })?;
+        }
+        (None, Some(channel_id)) => {
+            channel_id.parse::<u64>().with_context(|| {
+                format!(
+                    "channel_id of notification target {} must be a numeric channel id",
+                    target.name
+                )
+            })?;
+            if !has_token {
+                return Err(anyhow::anyhow!(
+                    "Notification target {} posts to a channel, which requires DISCORD_TOKEN",
+                    target.name
+                ));
+            }
+        }
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Notification target {} must set exactly one of webhook_url or channel_id",
+                target.name
+            ));
+        }
+    }
+
+    if target.filter.min_discount.is_some_and(|discount| !(0.0..=100.0).contains(&discount)) {
+        return Err(anyhow::anyhow!(
+            "min_discount of notification target {} must be between 0 and 100",
+            target.name
+        ));
+    }
+
+    Ok(())
+}
+
+/// Validate the URLs and required settings of a notification backend
+fn validate_notifier(notifier: &NotifierConfig) -> Result<()> {
+    if notifier.name.trim().is_empty() {
+        return Err(anyhow::anyhow!("notifiers entries must have a name"));
+    }
+
+    let urls = match &notifier.backend {
+        NotifierBackend::Slack { webhook_url } => vec![webhook_url],
+        NotifierBackend::Telegram { api_url, .. } => vec![api_url],
+        NotifierBackend::Matrix { homeserver, .. } => vec![homeserver],
+        NotifierBackend::Webhook { url, secret, .. } => {
+            if secret.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "Webhook notifier {} must have a secret to sign events with",
+                    notifier.name
+                ));
+            }
+            vec![url]
+        }
+        NotifierBackend::Email { to, .. } => {
+            if to.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "Email notifier {} must list at least one address in to",
+                    notifier.name
+                ));
+            }
+            Vec::new()
+        }
+    };
+    for url in urls {
+        reqwest::Url::parse(url)
+            .with_context(|| format!("Invalid URL for notifier {}: {}", notifier.name, url))?;
+    }
+
+    Ok(())
+}
+
+/// Validate the URL, secret and retry limit of an event webhook
+fn validate_webhook(webhook: &WebhookConfig) -> Result<()> {
+    reqwest::Url::parse(&webhook.url)
+        .with_context(|| format!("Invalid URL for webhook: {}", webhook.url))?;
+
+    if webhook.secret.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Webhook {} must have a secret to sign events with",
+            webhook.url
+        ));
+    }
+
+    if webhook.max_attempts < 1 {
+        return Err(anyhow::anyhow!(
+            "max_attempts of webhook {} must be at least 1",
+            webhook.url
+        ));
+    }
+
+    Ok(())
+}
+
+/// Validate a generic scraper source's URL and CSS selectors
+fn validate_source(source: &SourceConfig) -> Result<()> {
+    if source.name.trim().is_empty() {
+        return Err(anyhow::anyhow!(
+            "scraping.sources entry for {} must have a name",
+            source.url
+        ));
+    }
+
+    reqwest::Url::parse(&source.url)
+        .with_context(|| format!("Invalid URL for source {}: {}", source.name, source.url))?;
+
+    // Without a code selector the page text is searched for codes and the other selectors are unused
+    let selectors = &source.selectors;
+    if selectors.code.is_none()
+        && [&selectors.item, &selectors.discount, &selectors.title, &selectors.expiry]
+            .iter()
+            .any(|selector| selector.is_some())
+    {
+        return Err(anyhow::anyhow!(
+            "Selectors for source {} must include a code selector",
+            source.name
+        ));
+    }
+
+    let selectors = [
+        &source.selectors.item,
+        &source.selectors.code,
+        &source.selectors.discount,
+        &source.selectors.title,
+        &source.selectors.expiry,
+    ];
+    for selector in selectors.into_iter().flatten() {
+        scraper::Selector::parse(selector).map_err(|e| {
+            anyhow::anyhow!(
+                "Invalid CSS selector '{}' for source {}: {}",
+                selector,
+                source.name,
+                e
+            )
+        })?;
+    }
+
+    Ok(())
+}
+
 /// Get the Discord token from environment variables
 pub fn get_discord_token() -> Result<String> {
     // Try RIN_DISCORD_TOKEN first, then fall back to DISCORD_TOKEN
@@ -156,3 +518,21 @@ pub fn get_base_url() -> String {
     env::var("BASE_URL").unwrap_or_else(|_| "http://localhost:8080".to_string())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "[discord]\nwebhook_url = \"https://discord.com/api/webhooks/1/token\"\n\
+        [[scraping.sources]]\nname = \"Deals\"\nurl = \"https://example.com/deals\"\n";
+
+    #[test]
+    fn source_selectors_need_a_code_selector() {
+        let without_code = format!("{}[scraping.sources.selectors]\ntitle = \"h2\"\n", SOURCE);
+        let error = config_from_toml(&without_code).unwrap_err();
+        assert!(format!("{:#}", error).contains("must include a code selector"));
+
+        let with_code = format!("{}[scraping.sources.selectors]\ncode = \".code\"\ntitle = \"h2\"\n", SOURCE);
+        assert!(config_from_toml(&with_code).is_ok());
+        assert!(config_from_toml(SOURCE).is_ok());
+    }
+}
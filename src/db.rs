@@ -1,17 +1,25 @@
 use anyhow::{Context as AnyhowContext, Result};
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use sqlx::{
-    migrate::MigrateDatabase, pool::PoolOptions, sqlite::SqlitePoolOptions, Pool, Sqlite, SqlitePool,
+    migrate::MigrateDatabase, sqlite::SqlitePoolOptions, types::Json, Pool,
+    QueryBuilder, Sqlite, SqlitePool, Transaction,
 };
+use std::collections::HashMap;
 use std::path::Path;
 use std::time::Duration;
-use tracing::{debug, error, info, warn};
+use tracing::{debug, info, warn};
 
 use crate::config;
-use crate::models::{Config, Coupon};
+use crate::discord::SentMessage;
+use crate::metrics;
+use crate::models::{
+    Config, Coupon, CouponFilter, CouponListing, CouponSighting, CouponStats, DigestFrequency,
+    DigestRun, DiscordMessage, OutboxEntry, OutboxStatus, QueueSizes, ScrapeRun, ScrapeStatus, ScraperHealth,
+    ScraperScheduleState, SourceStats, ValidationRun, WebhookDeadLetter,
+};
 
 /// Initialize the database, creating it if it doesn't exist
-pub async fn initialize_database(config: &Config) -> Result<Pool<Sqlite>> {
+pub async fn initialize_database(_config: &Config) -> Result<Pool<Sqlite>> {
     let database_url = config::get_database_url();
     info!("Initializing database with URL: {}", database_url);
 
@@ -47,24 +55,39 @@ pub async fn initialize_database(config: &Config) -> Result<Pool<Sqlite>> {
         .await
         .context("Failed to connect to SQLite database")?;
 
+    prepare_schema(&pool).await?;
+
+    // Bring coupon hashes from older releases up to date
+    rehash_coupons(&pool).await?;
+
+    Ok(pool)
+}
+
+/// Apply migrations, then create the tables they don't cover
+pub async fn prepare_schema(pool: &SqlitePool) -> Result<()> {
     // Run migrations if they exist
-    apply_migrations(&pool).await?;
+    apply_migrations(pool).await?;
 
     // Create tables if they don't exist
-    create_tables(&pool).await?;
+    create_tables(pool).await
+}
 
-    Ok(pool)
+/// Directory the migrations are read from
+fn migrations_dir() -> String {
+    std::env::var("MIGRATIONS_DIR").unwrap_or_else(|_| "migrations".to_string())
 }
 
 /// Apply database migrations if available
 async fn apply_migrations(pool: &SqlitePool) -> Result<()> {
-    let migrations_path = std::env::var("MIGRATIONS_DIR").unwrap_or_else(|_| "migrations".to_string());
+    let migrations_path = migrations_dir();
     
     if Path::new(&migrations_path).exists() {
         info!("Applying database migrations from {}", migrations_path);
-        sqlx::migrate::Migrator::new(Path::new(&migrations_path))
+        let migrator = sqlx::migrate::Migrator::new(Path::new(&migrations_path))
             .await
-            .context("Failed to create migrator")?
+            .context("Failed to create migrator")?;
+        repair_coupons_migration(pool, &migrator).await?;
+        migrator
             .run(pool)
             .await
             .context("Failed to run migrations")?;
@@ -76,6 +99,46 @@ async fn apply_migrations(pool: &SqlitePool) -> Result<()> {
     Ok(())
 }
 
+/// Version of the migration creating the coupons table
+const COUPONS_MIGRATION: i64 = 20250529000001;
+
+/// SHA-384 checksum of the coupons migration as released in 1.0.0, with its down section
+const OLD_COUPONS_MIGRATION_CHECKSUM: &str = "0e3fe8530004603ce7e352852e42f082b3720388db7672f9e960d36d33e701b3846d2de5951abd88cc594b6f5a11cae2";
+
+/// The migration creating the coupons table used to contain its down section, so applying
+/// it dropped the table again and `create_tables` recreated it. Databases that applied that
+/// version get the checksum of the fixed migration, which the migrator checks. Any other
+/// checksum is left alone, so the migrator still reports a migration that was edited.
+async fn repair_coupons_migration(pool: &SqlitePool, migrator: &sqlx::migrate::Migrator) -> Result<()> {
+    let Some(migration) = migrator.iter().find(|migration| {
+        migration.version == COUPONS_MIGRATION && !migration.migration_type.is_down_migration()
+    }) else {
+        return Ok(());
+    };
+    
+    // The bookkeeping table is created by the migrator, so it's absent on new databases
+    let tracked: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'",
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to look up applied migrations")?;
+    if tracked == 0 {
+        return Ok(());
+    }
+    
+    let old_checksum = hex::decode(OLD_COUPONS_MIGRATION_CHECKSUM)?;
+    sqlx::query("UPDATE _sqlx_migrations SET checksum = ? WHERE version = ? AND checksum = ?")
+        .bind(migration.checksum.as_ref())
+        .bind(migration.version)
+        .bind(old_checksum)
+        .execute(pool)
+        .await
+        .context("Failed to update checksum of the coupons migration")?;
+    
+    Ok(())
+}
+
 /// Create database tables if they don't exist
 async fn create_tables(pool: &SqlitePool) -> Result<()> {
     info!("Creating database tables if they don't exist");
@@ -94,9 +157,13 @@ async fn create_tables(pool: &SqlitePool) -> Result<()> {
             expiry TEXT,
             created_at TEXT NOT NULL DEFAULT (datetime('now')),
             validated_at TEXT,
+            last_checked_at TEXT,
             is_valid INTEGER NOT NULL DEFAULT 0,
             is_posted INTEGER NOT NULL DEFAULT 0,
-            hash TEXT NOT NULL UNIQUE
+            hash TEXT NOT NULL UNIQUE,
+            first_seen TEXT,
+            last_seen TEXT,
+            likely_dead INTEGER NOT NULL DEFAULT 0
         )
         "#,
     )
@@ -104,19 +171,511 @@ async fn create_tables(pool: &SqlitePool) -> Result<()> {
     .await
     .context("Failed to create coupons table")?;
     
+    // Create scraper schedules table
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS scraper_schedules (
+            scraper TEXT PRIMARY KEY NOT NULL,
+            last_run TEXT,
+            next_run TEXT NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create scraper_schedules table")?;
+    
+    // Create Discord messages table
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS discord_messages (
+            coupon_id INTEGER NOT NULL REFERENCES coupons(id) ON DELETE CASCADE,
+            message_id TEXT NOT NULL,
+            channel_id TEXT NOT NULL,
+            via_webhook INTEGER NOT NULL DEFAULT 0,
+            posted_at TEXT NOT NULL,
+            target TEXT NOT NULL DEFAULT 'default',
+            embed_index INTEGER NOT NULL DEFAULT 0,
+            embed_count INTEGER NOT NULL DEFAULT 1,
+            field_index INTEGER,
+            PRIMARY KEY (coupon_id, message_id)
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create discord_messages table")?;
+    
+    // Create validation history table
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS validation_runs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            coupon_id INTEGER NOT NULL REFERENCES coupons(id) ON DELETE CASCADE,
+            validator TEXT NOT NULL,
+            is_valid INTEGER,
+            message TEXT,
+            http_status INTEGER,
+            duration_ms INTEGER NOT NULL,
+            validated_at TEXT NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create validation_runs table")?;
+    
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_validation_runs_coupon_id ON validation_runs(coupon_id, validated_at)",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create validation_runs index")?;
+    
+    // Create table of near-duplicate listings
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS coupon_listings (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            coupon_id INTEGER NOT NULL REFERENCES coupons(id) ON DELETE CASCADE,
+            source TEXT NOT NULL,
+            name TEXT NOT NULL,
+            url TEXT NOT NULL,
+            hash TEXT NOT NULL UNIQUE,
+            first_seen TEXT NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create coupon_listings table")?;
+    
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_coupon_listings_coupon_id ON coupon_listings(coupon_id)",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create coupon_listings index")?;
+    
+    // Create table of scrapes that found each coupon
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS coupon_sightings (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            coupon_id INTEGER NOT NULL REFERENCES coupons(id) ON DELETE CASCADE,
+            scraper TEXT NOT NULL,
+            source TEXT NOT NULL,
+            seen_at TEXT NOT NULL,
+            UNIQUE (coupon_id, scraper, seen_at)
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create coupon_sightings table")?;
+    
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_coupon_sightings_scraper ON coupon_sightings(scraper, seen_at)",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create coupon_sightings index")?;
+    
+    // Create table of scraper runs
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS scrape_runs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            scraper TEXT NOT NULL,
+            started_at TEXT NOT NULL,
+            finished_at TEXT NOT NULL,
+            status TEXT NOT NULL,
+            coupons_found INTEGER NOT NULL,
+            http_statuses TEXT NOT NULL,
+            bytes_fetched INTEGER NOT NULL,
+            error TEXT
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create scrape_runs table")?;
+    
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_scrape_runs_scraper ON scrape_runs(scraper, started_at)",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create scrape_runs index")?;
+    
+    // Create table of coupon announcements waiting to be delivered
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS notification_outbox (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            coupon_id INTEGER NOT NULL REFERENCES coupons(id) ON DELETE CASCADE,
+            idempotency_key TEXT NOT NULL UNIQUE,
+            status TEXT NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            last_error TEXT,
+            created_at TEXT NOT NULL,
+            next_attempt_at TEXT NOT NULL,
+            last_attempt_at TEXT,
+            delivered_at TEXT
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create notification_outbox table")?;
+    
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_notification_outbox_due ON notification_outbox(status, next_attempt_at)",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create notification_outbox index")?;
+    
+    // Create table of targets that already received an announcement
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS outbox_deliveries (
+            outbox_id INTEGER NOT NULL REFERENCES notification_outbox(id) ON DELETE CASCADE,
+            target TEXT NOT NULL,
+            delivered_at TEXT NOT NULL,
+            PRIMARY KEY (outbox_id, target)
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create outbox_deliveries table")?;
+    
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_outbox_deliveries_target ON outbox_deliveries(target, delivered_at)",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create outbox_deliveries index")?;
+    
+    // Create table of posted digests
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS digest_runs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            frequency TEXT NOT NULL,
+            target TEXT NOT NULL DEFAULT '',
+            coupon_count INTEGER NOT NULL,
+            targets INTEGER NOT NULL,
+            window_start TEXT NOT NULL,
+            window_end TEXT NOT NULL,
+            posted_at TEXT NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create digest_runs table")?;
+    
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_digest_runs_frequency ON digest_runs(frequency, window_end)",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create digest_runs index")?;
+    
+    // Create table of undeliverable webhook events
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS webhook_dead_letters (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            url TEXT NOT NULL,
+            event_id TEXT NOT NULL,
+            event_type TEXT NOT NULL,
+            payload TEXT NOT NULL,
+            attempts INTEGER NOT NULL,
+            last_error TEXT NOT NULL,
+            http_status INTEGER,
+            failed_at TEXT NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create webhook_dead_letters table")?;
+    
+    // Create table of data migrations run at startup
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS data_migrations (
+            name TEXT PRIMARY KEY,
+            applied_at TEXT NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create data_migrations table")?;
+    
     info!("Database tables created successfully");
     Ok(())
 }
 
+/// Name of the coupon rehash in the data_migrations table
+const REHASH_COUPONS_MIGRATION: &str = "rehash_coupons";
+
+/// Recompute coupon hashes with `Coupon::identity_hash` and merge coupons that turn out
+/// to be duplicates. SQLite can't compute SHA-256, so this data migration runs in Rust.
+/// It runs once and is recorded in the data_migrations table, since new coupons are
+/// stored with current hashes.
+///
+/// The oldest coupon of a group is kept. It takes over the validation history, Discord
+/// messages, listings and sightings of its duplicates, stays posted if any of them was
+/// posted and keeps the most recent validation result.
+async fn rehash_coupons(pool: &SqlitePool) -> Result<()> {
+    let applied = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count: i64" FROM data_migrations WHERE name = ?"#,
+        REHASH_COUPONS_MIGRATION
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to look up the coupon rehash")?;
+    if applied > 0 {
+        return Ok(());
+    }
+
+    let mut groups: HashMap<String, Vec<Coupon>> = HashMap::new();
+    for coupon in get_all_coupons(pool).await? {
+        let hash = Coupon::identity_hash(&coupon.source, &coupon.code, &coupon.url);
+        groups.entry(hash).or_default().push(coupon);
+    }
+
+    let stale: Vec<(String, Vec<Coupon>)> = groups
+        .into_iter()
+        .filter(|(hash, group)| group.len() > 1 || group[0].hash != *hash)
+        .collect();
+
+    let mut tx = pool.begin().await.context("Failed to start coupon rehash")?;
+    let mut merged = 0;
+
+    for (hash, mut group) in stale {
+        group.sort_by_key(|coupon| coupon.id);
+        let keeper_id = group[0].id.unwrap_or_default();
+        let is_posted = group.iter().any(|coupon| coupon.is_posted);
+        let latest = group
+            .iter()
+            .max_by_key(|coupon| coupon.validated_at)
+            .unwrap_or(&group[0]);
+        let is_valid = latest.is_valid;
+        let validated_at = latest.validated_at.map(|t| t.to_rfc3339());
+        let first_seen = group
+            .iter()
+            .filter_map(|coupon| coupon.first_seen)
+            .min()
+            .map(|t| t.to_rfc3339());
+        let last_seen = group
+            .iter()
+            .filter_map(|coupon| coupon.last_seen)
+            .max()
+            .map(|t| t.to_rfc3339());
+        let likely_dead = group.iter().all(|coupon| coupon.likely_dead);
+
+        for duplicate in &group[1..] {
+            let duplicate_id = duplicate.id.unwrap_or_default();
+            debug!(
+                "Merging duplicate coupon {} into {}: {}",
+                duplicate_id, keeper_id, duplicate.name
+            );
+
+            sqlx::query!(
+                "UPDATE validation_runs SET coupon_id = ? WHERE coupon_id = ?",
+                keeper_id,
+                duplicate_id
+            )
+            .execute(&mut *tx)
+            .await
+            .context("Failed to move validation runs of duplicate coupon")?;
+
+            // Batched messages announcing both coupons are already linked to the keeper
+            sqlx::query!(
+                "UPDATE OR IGNORE discord_messages SET coupon_id = ? WHERE coupon_id = ?",
+                keeper_id,
+                duplicate_id
+            )
+            .execute(&mut *tx)
+            .await
+            .context("Failed to move Discord messages of duplicate coupon")?;
+
+            sqlx::query!("DELETE FROM discord_messages WHERE coupon_id = ?", duplicate_id)
+                .execute(&mut *tx)
+                .await
+                .context("Failed to delete Discord messages of duplicate coupon")?;
+
+            sqlx::query!(
+                "UPDATE coupon_listings SET coupon_id = ? WHERE coupon_id = ?",
+                keeper_id,
+                duplicate_id
+            )
+            .execute(&mut *tx)
+            .await
+            .context("Failed to move listings of duplicate coupon")?;
+
+            sqlx::query!(
+                "UPDATE notification_outbox SET coupon_id = ? WHERE coupon_id = ?",
+                keeper_id,
+                duplicate_id
+            )
+            .execute(&mut *tx)
+            .await
+            .context("Failed to move announcements of duplicate coupon")?;
+
+            // Sightings the keeper already has for the same run are dropped with the duplicate
+            sqlx::query!(
+                "UPDATE OR IGNORE coupon_sightings SET coupon_id = ? WHERE coupon_id = ?",
+                keeper_id,
+                duplicate_id
+            )
+            .execute(&mut *tx)
+            .await
+            .context("Failed to move sightings of duplicate coupon")?;
+
+            sqlx::query!("DELETE FROM coupons WHERE id = ?", duplicate_id)
+                .execute(&mut *tx)
+                .await
+                .context("Failed to delete duplicate coupon")?;
+
+            merged += 1;
+        }
+
+        if group.len() > 1 {
+            merge_announcements(&mut tx, keeper_id, is_valid && !is_posted).await?;
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE coupons
+            SET hash = ?,
+                is_posted = ?,
+                is_valid = ?,
+                validated_at = ?,
+                first_seen = ?,
+                last_seen = ?,
+                likely_dead = ?
+            WHERE id = ?
+            "#,
+            hash,
+            is_posted,
+            is_valid,
+            validated_at,
+            first_seen,
+            last_seen,
+            likely_dead,
+            keeper_id
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to update coupon hash")?;
+    }
+
+    let now = Utc::now().to_rfc3339();
+    sqlx::query!(
+        "INSERT INTO data_migrations (name, applied_at) VALUES (?, ?)",
+        REHASH_COUPONS_MIGRATION,
+        now
+    )
+    .execute(&mut *tx)
+    .await
+    .context("Failed to record the coupon rehash")?;
+
+    tx.commit().await.context("Failed to commit coupon rehash")?;
+    info!("Recomputed coupon hashes, merged {} duplicate coupons", merged);
+
+    Ok(())
+}
+
+/// Leave a single announcement in flight for a coupon that duplicates were merged into.
+/// The oldest pending announcement is kept and takes over the targets the others already
+/// reached. If none is pending and the coupon still has to be announced, its latest
+/// skipped announcement is queued again.
+async fn merge_announcements(
+    tx: &mut Transaction<'_, Sqlite>,
+    coupon_id: i64,
+    unannounced: bool,
+) -> Result<()> {
+    let kept = sqlx::query_scalar!(
+        r#"SELECT MIN(id) as "id: i64" FROM notification_outbox WHERE coupon_id = ? AND status = 'pending'"#,
+        coupon_id
+    )
+    .fetch_one(&mut **tx)
+    .await
+    .context("Failed to find pending announcement")?;
+
+    let Some(kept) = kept else {
+        if unannounced {
+            let now = Utc::now().to_rfc3339();
+            sqlx::query!(
+                r#"
+                UPDATE notification_outbox
+                SET status = 'pending',
+                    attempts = 0,
+                    last_error = NULL,
+                    next_attempt_at = ?
+                WHERE id = (SELECT MAX(id) FROM notification_outbox WHERE coupon_id = ? AND status = 'skipped')
+                "#,
+                now,
+                coupon_id
+            )
+            .execute(&mut **tx)
+            .await
+            .context("Failed to queue announcement of merged coupon")?;
+        }
+        return Ok(());
+    };
+
+    sqlx::query!(
+        r#"
+        UPDATE OR IGNORE outbox_deliveries
+        SET outbox_id = ?
+        WHERE outbox_id IN (SELECT id FROM notification_outbox WHERE coupon_id = ? AND status = 'pending')
+        "#,
+        kept,
+        coupon_id
+    )
+    .execute(&mut **tx)
+    .await
+    .context("Failed to move delivered targets of merged announcements")?;
+
+    sqlx::query!(
+        r#"
+        UPDATE notification_outbox
+        SET status = 'skipped', last_error = 'Merged into another announcement'
+        WHERE coupon_id = ? AND status = 'pending' AND id != ?
+        "#,
+        coupon_id,
+        kept
+    )
+    .execute(&mut **tx)
+    .await
+    .context("Failed to skip merged announcements")?;
+
+    Ok(())
+}
+
 /// Insert a new coupon into the database
 pub async fn insert_coupon(pool: &SqlitePool, coupon: &Coupon) -> Result<i64> {
+    let _timer = metrics::time_query("insert_coupon");
     debug!("Inserting coupon: {:?}", coupon);
+    let expiry = coupon.expiry.map(|dt| dt.to_rfc3339());
+    let created_at = coupon.created_at.unwrap_or_else(Utc::now).to_rfc3339();
+    let first_seen = coupon.first_seen.map(|dt| dt.to_rfc3339());
+    let last_seen = coupon.last_seen.map(|dt| dt.to_rfc3339());
     
     let result = sqlx::query!(
         r#"
         INSERT INTO coupons
-        (name, description, discount_percentage, code, url, source, expiry, created_at, is_valid, hash)
-        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        (name, description, discount_percentage, code, url, source, expiry, created_at, is_valid, hash,
+         first_seen, last_seen)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
         "#,
         coupon.name,
         coupon.description,
@@ -124,10 +683,12 @@ pub async fn insert_coupon(pool: &SqlitePool, coupon: &Coupon) -> Result<i64> {
         coupon.code,
         coupon.url,
         coupon.source,
-        coupon.expiry.map(|dt| dt.to_rfc3339()),
-        coupon.created_at.unwrap_or_else(Utc::now).to_rfc3339(),
+        expiry,
+        created_at,
         coupon.is_valid,
-        coupon.hash
+        coupon.hash,
+        first_seen,
+        last_seen
     )
     .execute(pool)
     .await
@@ -136,14 +697,61 @@ pub async fn insert_coupon(pool: &SqlitePool, coupon: &Coupon) -> Result<i64> {
     Ok(result.last_insert_rowid())
 }
 
-/// Check if a coupon already exists in the database by its hash
-pub async fn coupon_exists(pool: &SqlitePool, hash: &str) -> Result<bool> {
-    let result = sqlx::query!("SELECT COUNT(*) as count FROM coupons WHERE hash = ?", hash)
-        .fetch_one(pool)
+/// Find the coupon a hash belongs to, either as the coupon itself or as one of its listings
+pub async fn find_coupon_id_by_hash(pool: &SqlitePool, hash: &str) -> Result<Option<i64>> {
+    let _timer = metrics::time_query("find_coupon_id_by_hash");
+    let coupon_id = sqlx::query_scalar!(r#"SELECT id as "id!" FROM coupons WHERE hash = ?"#, hash)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to look up coupon by hash")?;
+    if coupon_id.is_some() {
+        return Ok(coupon_id);
+    }
+
+    let coupon_id = sqlx::query_scalar!("SELECT coupon_id FROM coupon_listings WHERE hash = ?", hash)
+        .fetch_optional(pool)
         .await
-        .context("Failed to check if coupon exists")?;
+        .context("Failed to look up coupon listing by hash")?;
+
+    Ok(coupon_id)
+}
+
+/// Get coupons with the given code, compared case-insensitively, oldest first
+pub async fn get_coupons_by_code(pool: &SqlitePool, code: &str) -> Result<Vec<Coupon>> {
+    let _timer = metrics::time_query("get_coupons_by_code");
+    let code = code.trim().to_uppercase();
+    
+    let coupons = sqlx::query_as!(
+        Coupon,
+        r#"
+        SELECT 
+            id,
+            name,
+            description,
+            discount_percentage,
+            code,
+            url,
+            source,
+            expiry as "expiry: DateTime<Utc>",
+            created_at as "created_at: DateTime<Utc>",
+            validated_at as "validated_at: DateTime<Utc>",
+            first_seen as "first_seen: DateTime<Utc>",
+            last_seen as "last_seen: DateTime<Utc>",
+            likely_dead as "likely_dead: bool",
+            is_valid as "is_valid: bool",
+            is_posted as "is_posted: bool",
+            hash
+        FROM coupons
+        WHERE UPPER(TRIM(code)) = ?
+        ORDER BY id
+        "#,
+        code
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to get coupons by code")?;
     
-    Ok(result.count > 0)
+    Ok(coupons)
 }
 
 /// Update a coupon's validation status
@@ -152,17 +760,20 @@ pub async fn update_validation_status(
     coupon_id: i64,
     is_valid: bool,
 ) -> Result<()> {
+    let _timer = metrics::time_query("update_validation_status");
     let now = Utc::now().to_rfc3339();
     
     sqlx::query!(
         r#"
         UPDATE coupons
         SET is_valid = ?,
-            validated_at = ?
+            validated_at = ?,
+            last_checked_at = ?
         WHERE id = ?
         "#,
         is_valid,
         now,
+        now,
         coupon_id
     )
     .execute(pool)
@@ -172,18 +783,27 @@ pub async fn update_validation_status(
     Ok(())
 }
 
-/// Mark a coupon as posted
-pub async fn mark_as_posted(pool: &SqlitePool, coupon_id: i64) -> Result<()> {
-    sqlx::query!("UPDATE coupons SET is_posted = 1 WHERE id = ?", coupon_id)
-        .execute(pool)
-        .await
-        .context("Failed to mark coupon as posted")?;
+/// Mark a coupon as checked without changing its validation status or time, e.g. after
+/// its check failed, so it moves to the back of the revalidation queue
+pub async fn mark_checked(pool: &SqlitePool, coupon_id: i64) -> Result<()> {
+    let _timer = metrics::time_query("mark_checked");
+    let now = Utc::now().to_rfc3339();
     
-    Ok(())
+    sqlx::query!(
+        "UPDATE coupons SET last_checked_at = ? WHERE id = ?",
+        now,
+        coupon_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to update coupon check time")?;
+    
+    Ok(())
 }
 
 /// Get all coupons from the database
 pub async fn get_all_coupons(pool: &SqlitePool) -> Result<Vec<Coupon>> {
+    let _timer = metrics::time_query("get_all_coupons");
     let coupons = sqlx::query_as!(
         Coupon,
         r#"
@@ -195,11 +815,14 @@ pub async fn get_all_coupons(pool: &SqlitePool) -> Result<Vec<Coupon>> {
             code,
             url,
             source,
-            expiry as "expiry: Option<DateTime<Utc>>",
-            created_at as "created_at: Option<DateTime<Utc>>",
-            validated_at as "validated_at: Option<DateTime<Utc>>",
-            is_valid,
-            is_posted,
+            expiry as "expiry: DateTime<Utc>",
+            created_at as "created_at: DateTime<Utc>",
+            validated_at as "validated_at: DateTime<Utc>",
+            first_seen as "first_seen: DateTime<Utc>",
+            last_seen as "last_seen: DateTime<Utc>",
+            likely_dead as "likely_dead: bool",
+            is_valid as "is_valid: bool",
+            is_posted as "is_posted: bool",
             hash
         FROM coupons
         ORDER BY created_at DESC
@@ -214,6 +837,7 @@ pub async fn get_all_coupons(pool: &SqlitePool) -> Result<Vec<Coupon>> {
 
 /// Get valid coupons that haven't been posted yet
 pub async fn get_valid_unposted_coupons(pool: &SqlitePool) -> Result<Vec<Coupon>> {
+    let _timer = metrics::time_query("get_valid_unposted_coupons");
     let coupons = sqlx::query_as!(
         Coupon,
         r#"
@@ -225,11 +849,14 @@ pub async fn get_valid_unposted_coupons(pool: &SqlitePool) -> Result<Vec<Coupon>
             code,
             url,
             source,
-            expiry as "expiry: Option<DateTime<Utc>>",
-            created_at as "created_at: Option<DateTime<Utc>>",
-            validated_at as "validated_at: Option<DateTime<Utc>>",
-            is_valid,
-            is_posted,
+            expiry as "expiry: DateTime<Utc>",
+            created_at as "created_at: DateTime<Utc>",
+            validated_at as "validated_at: DateTime<Utc>",
+            first_seen as "first_seen: DateTime<Utc>",
+            last_seen as "last_seen: DateTime<Utc>",
+            likely_dead as "likely_dead: bool",
+            is_valid as "is_valid: bool",
+            is_posted as "is_posted: bool",
             hash
         FROM coupons
         WHERE is_valid = 1 AND is_posted = 0
@@ -245,6 +872,7 @@ pub async fn get_valid_unposted_coupons(pool: &SqlitePool) -> Result<Vec<Coupon>
 
 /// Get coupon by id
 pub async fn get_coupon_by_id(pool: &SqlitePool, id: i64) -> Result<Option<Coupon>> {
+    let _timer = metrics::time_query("get_coupon_by_id");
     let coupon = sqlx::query_as!(
         Coupon,
         r#"
@@ -256,11 +884,14 @@ pub async fn get_coupon_by_id(pool: &SqlitePool, id: i64) -> Result<Option<Coupo
             code,
             url,
             source,
-            expiry as "expiry: Option<DateTime<Utc>>",
-            created_at as "created_at: Option<DateTime<Utc>>",
-            validated_at as "validated_at: Option<DateTime<Utc>>",
-            is_valid,
-            is_posted,
+            expiry as "expiry: DateTime<Utc>",
+            created_at as "created_at: DateTime<Utc>",
+            validated_at as "validated_at: DateTime<Utc>",
+            first_seen as "first_seen: DateTime<Utc>",
+            last_seen as "last_seen: DateTime<Utc>",
+            likely_dead as "likely_dead: bool",
+            is_valid as "is_valid: bool",
+            is_posted as "is_posted: bool",
             hash
         FROM coupons
         WHERE id = ?
@@ -274,8 +905,57 @@ pub async fn get_coupon_by_id(pool: &SqlitePool, id: i64) -> Result<Option<Coupo
     Ok(coupon)
 }
 
+/// Get unexpired coupons last checked before `checked_before`, stalest first. Coupons
+/// that were never checked come first.
+pub async fn get_stale_coupons(
+    pool: &SqlitePool,
+    checked_before: DateTime<Utc>,
+    limit: i64,
+) -> Result<Vec<Coupon>> {
+    let _timer = metrics::time_query("get_stale_coupons");
+    let checked_before = checked_before.to_rfc3339();
+    let now = Utc::now().to_rfc3339();
+    
+    let coupons = sqlx::query_as!(
+        Coupon,
+        r#"
+        SELECT 
+            id,
+            name,
+            description,
+            discount_percentage,
+            code,
+            url,
+            source,
+            expiry as "expiry: DateTime<Utc>",
+            created_at as "created_at: DateTime<Utc>",
+            validated_at as "validated_at: DateTime<Utc>",
+            first_seen as "first_seen: DateTime<Utc>",
+            last_seen as "last_seen: DateTime<Utc>",
+            likely_dead as "likely_dead: bool",
+            is_valid as "is_valid: bool",
+            is_posted as "is_posted: bool",
+            hash
+        FROM coupons
+        WHERE (last_checked_at IS NULL OR last_checked_at < ?)
+          AND (expiry IS NULL OR expiry > ?)
+        ORDER BY last_checked_at IS NOT NULL, last_checked_at ASC
+        LIMIT ?
+        "#,
+        checked_before,
+        now,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to get stale coupons")?;
+    
+    Ok(coupons)
+}
+
 /// Delete expired coupons
 pub async fn delete_expired_coupons(pool: &SqlitePool) -> Result<u64> {
+    let _timer = metrics::time_query("delete_expired_coupons");
     let now = Utc::now().to_rfc3339();
     
     let result = sqlx::query!(
@@ -292,8 +972,47 @@ pub async fn delete_expired_coupons(pool: &SqlitePool) -> Result<u64> {
     Ok(result.rows_affected())
 }
 
+/// Get expired coupons that are still stored
+pub async fn get_expired_coupons(pool: &SqlitePool) -> Result<Vec<Coupon>> {
+    let _timer = metrics::time_query("get_expired_coupons");
+    let now = Utc::now().to_rfc3339();
+    
+    let coupons = sqlx::query_as!(
+        Coupon,
+        r#"
+        SELECT 
+            id,
+            name,
+            description,
+            discount_percentage,
+            code,
+            url,
+            source,
+            expiry as "expiry: DateTime<Utc>",
+            created_at as "created_at: DateTime<Utc>",
+            validated_at as "validated_at: DateTime<Utc>",
+            first_seen as "first_seen: DateTime<Utc>",
+            last_seen as "last_seen: DateTime<Utc>",
+            likely_dead as "likely_dead: bool",
+            is_valid as "is_valid: bool",
+            is_posted as "is_posted: bool",
+            hash
+        FROM coupons
+        WHERE expiry IS NOT NULL AND expiry < ?
+        "#,
+        now
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to get expired coupons")?;
+    
+    Ok(coupons)
+}
+
 /// Get coupons by source
+#[allow(dead_code)]
 pub async fn get_coupons_by_source(pool: &SqlitePool, source: &str) -> Result<Vec<Coupon>> {
+    let _timer = metrics::time_query("get_coupons_by_source");
     let coupons = sqlx::query_as!(
         Coupon,
         r#"
@@ -305,11 +1024,14 @@ pub async fn get_coupons_by_source(pool: &SqlitePool, source: &str) -> Result<Ve
             code,
             url,
             source,
-            expiry as "expiry: Option<DateTime<Utc>>",
-            created_at as "created_at: Option<DateTime<Utc>>",
-            validated_at as "validated_at: Option<DateTime<Utc>>",
-            is_valid,
-            is_posted,
+            expiry as "expiry: DateTime<Utc>",
+            created_at as "created_at: DateTime<Utc>",
+            validated_at as "validated_at: DateTime<Utc>",
+            first_seen as "first_seen: DateTime<Utc>",
+            last_seen as "last_seen: DateTime<Utc>",
+            likely_dead as "likely_dead: bool",
+            is_valid as "is_valid: bool",
+            is_posted as "is_posted: bool",
             hash
         FROM coupons
         WHERE source = ?
@@ -324,3 +1046,1526 @@ pub async fn get_coupons_by_source(pool: &SqlitePool, source: &str) -> Result<Ve
     Ok(coupons)
 }
 
+
+/// Get a page of coupons matching a filter, along with the total number of matches
+pub async fn get_coupons_filtered(
+    pool: &SqlitePool,
+    filter: &CouponFilter,
+) -> Result<(Vec<Coupon>, i64)> {
+    let _timer = metrics::time_query("get_coupons_filtered");
+    let mut count_query = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) FROM coupons");
+    push_coupon_filter(&mut count_query, filter);
+    
+    let total: i64 = count_query
+        .build_query_scalar()
+        .fetch_one(pool)
+        .await
+        .context("Failed to count filtered coupons")?;
+    
+    let mut select_query = QueryBuilder::<Sqlite>::new(
+        r#"
+        SELECT
+            id,
+            name,
+            description,
+            discount_percentage,
+            code,
+            url,
+            source,
+            expiry,
+            created_at,
+            validated_at,
+            first_seen,
+            last_seen,
+            is_valid,
+            is_posted,
+            likely_dead,
+            hash
+        FROM coupons
+        "#,
+    );
+    push_coupon_filter(&mut select_query, filter);
+    select_query
+        .push(" ORDER BY created_at DESC LIMIT ")
+        .push_bind(filter.limit)
+        .push(" OFFSET ")
+        .push_bind(filter.offset);
+    
+    let coupons = select_query
+        .build_query_as::<Coupon>()
+        .fetch_all(pool)
+        .await
+        .context("Failed to get filtered coupons")?;
+    
+    Ok((coupons, total))
+}
+
+/// Append the WHERE clause for a coupon filter to a query
+fn push_coupon_filter(query: &mut QueryBuilder<'_, Sqlite>, filter: &CouponFilter) {
+    query.push(" WHERE 1 = 1");
+    
+    if let Some(source) = &filter.source {
+        query.push(" AND source = ").push_bind(source.clone());
+    }
+    
+    if let Some(is_valid) = filter.is_valid {
+        query.push(" AND is_valid = ").push_bind(is_valid);
+    }
+    
+    if let Some(expired) = filter.expired {
+        let now = Utc::now().to_rfc3339();
+        if expired {
+            query.push(" AND expiry IS NOT NULL AND expiry < ").push_bind(now);
+        } else {
+            query.push(" AND (expiry IS NULL OR expiry >= ").push_bind(now).push(")");
+        }
+    }
+    
+    if let Some(expires_before) = filter.expires_before {
+        query
+            .push(" AND expiry IS NOT NULL AND expiry < ")
+            .push_bind(expires_before.to_rfc3339());
+    }
+}
+
+/// Get the persisted run times of all scraper schedules
+pub async fn get_scraper_schedules(pool: &SqlitePool) -> Result<Vec<ScraperScheduleState>> {
+    let _timer = metrics::time_query("get_scraper_schedules");
+    let schedules = sqlx::query_as!(
+        ScraperScheduleState,
+        r#"
+        SELECT
+            scraper,
+            last_run as "last_run: DateTime<Utc>",
+            next_run as "next_run: DateTime<Utc>"
+        FROM scraper_schedules
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to get scraper schedules")?;
+    
+    Ok(schedules)
+}
+
+/// Record a scraper run and when it should run next
+pub async fn save_scraper_schedule(
+    pool: &SqlitePool,
+    scraper: &str,
+    last_run: DateTime<Utc>,
+    next_run: DateTime<Utc>,
+) -> Result<()> {
+    let _timer = metrics::time_query("save_scraper_schedule");
+    let last_run = last_run.to_rfc3339();
+    let next_run = next_run.to_rfc3339();
+    
+    sqlx::query!(
+        r#"
+        INSERT INTO scraper_schedules (scraper, last_run, next_run)
+        VALUES (?, ?, ?)
+        ON CONFLICT(scraper) DO UPDATE SET
+            last_run = excluded.last_run,
+            next_run = excluded.next_run
+        "#,
+        scraper,
+        last_run,
+        next_run
+    )
+    .execute(pool)
+    .await
+    .context("Failed to save scraper schedule")?;
+    
+    Ok(())
+}
+
+/// Get aggregate coupon counts
+pub async fn get_coupon_stats(pool: &SqlitePool) -> Result<CouponStats> {
+    let _timer = metrics::time_query("get_coupon_stats");
+    let stats = sqlx::query_as!(
+        CouponStats,
+        r#"
+        SELECT
+            COUNT(*) as "total!: i64",
+            COALESCE(SUM(is_valid), 0) as "valid!: i64",
+            COALESCE(SUM(is_posted), 0) as "posted!: i64"
+        FROM coupons
+        "#
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to get coupon stats")?;
+    
+    Ok(stats)
+}
+
+/// Count the items waiting in the bot's queues
+pub async fn get_queue_sizes(pool: &SqlitePool) -> Result<QueueSizes> {
+    let _timer = metrics::time_query("get_queue_sizes");
+    let sizes = sqlx::query_as!(
+        QueueSizes,
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM coupons WHERE is_valid = 1 AND is_posted = 0)
+                as "unposted_coupons!: i64",
+            (SELECT COUNT(*) FROM notification_outbox WHERE status = 'pending')
+                as "outbox_pending!: i64",
+            (SELECT COUNT(*) FROM notification_outbox WHERE status = 'failed')
+                as "outbox_failed!: i64",
+            (SELECT COUNT(*) FROM webhook_dead_letters) as "webhook_dead_letters!: i64"
+        "#
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to get queue sizes")?;
+    
+    Ok(sizes)
+}
+
+/// Check that the database answers queries
+pub async fn ping(pool: &SqlitePool) -> Result<()> {
+    sqlx::query("SELECT 1")
+        .execute(pool)
+        .await
+        .context("Database is unreachable")?;
+    
+    Ok(())
+}
+
+/// Count the migrations in the migrations directory that haven't been applied
+pub async fn get_pending_migrations(pool: &SqlitePool) -> Result<usize> {
+    let migrations_path = migrations_dir();
+    if !Path::new(&migrations_path).exists() {
+        return Ok(0);
+    }
+    
+    let migrator = sqlx::migrate::Migrator::new(Path::new(&migrations_path))
+        .await
+        .context("Failed to read migrations")?;
+    
+    // The bookkeeping table is created by the migrator, so it's absent until migrations ran
+    let applied: Vec<i64> =
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success = 1")
+            .fetch_all(pool)
+            .await
+            .unwrap_or_default();
+    
+    let pending = migrator
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .filter(|migration| !applied.contains(&migration.version))
+        .count();
+    
+    Ok(pending)
+}
+
+/// Get coupon counts per source
+pub async fn get_source_stats(pool: &SqlitePool) -> Result<Vec<SourceStats>> {
+    let _timer = metrics::time_query("get_source_stats");
+    let stats = sqlx::query_as!(
+        SourceStats,
+        r#"
+        SELECT
+            source,
+            COUNT(*) as "total!: i64",
+            COALESCE(SUM(is_valid), 0) as "valid!: i64"
+        FROM coupons
+        GROUP BY source
+        ORDER BY source
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to get source stats")?;
+    
+    Ok(stats)
+}
+
+/// Record a validation attempt
+pub async fn insert_validation_run(pool: &SqlitePool, run: &ValidationRun) -> Result<i64> {
+    let _timer = metrics::time_query("insert_validation_run");
+    let validated_at = run.validated_at.to_rfc3339();
+    
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO validation_runs
+        (coupon_id, validator, is_valid, message, http_status, duration_ms, validated_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
+        "#,
+        run.coupon_id,
+        run.validator,
+        run.is_valid,
+        run.message,
+        run.http_status,
+        run.duration_ms,
+        validated_at
+    )
+    .execute(pool)
+    .await
+    .context("Failed to insert validation run")?;
+    
+    Ok(result.last_insert_rowid())
+}
+
+/// Get a coupon's validation history, newest first
+pub async fn get_validation_runs(
+    pool: &SqlitePool,
+    coupon_id: i64,
+    limit: i64,
+) -> Result<Vec<ValidationRun>> {
+    let _timer = metrics::time_query("get_validation_runs");
+    let runs = sqlx::query_as!(
+        ValidationRun,
+        r#"
+        SELECT
+            id,
+            coupon_id,
+            validator,
+            is_valid as "is_valid: bool",
+            message,
+            http_status,
+            duration_ms,
+            validated_at as "validated_at: DateTime<Utc>"
+        FROM validation_runs
+        WHERE coupon_id = ?
+        ORDER BY validated_at DESC, id DESC
+        LIMIT ?
+        "#,
+        coupon_id,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to get validation runs")?;
+    
+    Ok(runs)
+}
+
+/// Get the most recent validation attempt for a coupon
+pub async fn get_latest_validation_run(
+    pool: &SqlitePool,
+    coupon_id: i64,
+) -> Result<Option<ValidationRun>> {
+    Ok(get_validation_runs(pool, coupon_id, 1).await?.into_iter().next())
+}
+
+/// Get the Discord messages announcing a coupon
+pub async fn get_discord_messages(pool: &SqlitePool, coupon_id: i64) -> Result<Vec<DiscordMessage>> {
+    let _timer = metrics::time_query("get_discord_messages");
+    let messages = sqlx::query_as!(
+        DiscordMessage,
+        r#"
+        SELECT
+            coupon_id,
+            message_id,
+            target,
+            channel_id,
+            via_webhook as "via_webhook: bool",
+            posted_at as "posted_at: DateTime<Utc>",
+            embed_index,
+            embed_count,
+            field_index
+        FROM discord_messages
+        WHERE coupon_id = ?
+        "#,
+        coupon_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to get Discord messages")?;
+    
+    Ok(messages)
+}
+
+/// Forget a Discord message, e.g. after it was deleted
+pub async fn delete_discord_message(pool: &SqlitePool, coupon_id: i64, message_id: &str) -> Result<()> {
+    let _timer = metrics::time_query("delete_discord_message");
+    sqlx::query!(
+        "DELETE FROM discord_messages WHERE coupon_id = ? AND message_id = ?",
+        coupon_id,
+        message_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to delete Discord message")?;
+    
+    Ok(())
+}
+
+/// Record a near-duplicate listing of a coupon
+pub async fn insert_coupon_listing(pool: &SqlitePool, listing: &CouponListing) -> Result<i64> {
+    let _timer = metrics::time_query("insert_coupon_listing");
+    let first_seen = listing.first_seen.to_rfc3339();
+    
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO coupon_listings
+        (coupon_id, source, name, url, hash, first_seen)
+        VALUES (?, ?, ?, ?, ?, ?)
+        "#,
+        listing.coupon_id,
+        listing.source,
+        listing.name,
+        listing.url,
+        listing.hash,
+        first_seen
+    )
+    .execute(pool)
+    .await
+    .context("Failed to insert coupon listing")?;
+    
+    Ok(result.last_insert_rowid())
+}
+
+/// Get the listings merged into a coupon, oldest first
+pub async fn get_coupon_listings(pool: &SqlitePool, coupon_id: i64) -> Result<Vec<CouponListing>> {
+    let _timer = metrics::time_query("get_coupon_listings");
+    let listings = sqlx::query_as!(
+        CouponListing,
+        r#"
+        SELECT
+            id,
+            coupon_id,
+            source,
+            name,
+            url,
+            hash,
+            first_seen as "first_seen: DateTime<Utc>"
+        FROM coupon_listings
+        WHERE coupon_id = ?
+        ORDER BY first_seen, id
+        "#,
+        coupon_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to get coupon listings")?;
+    
+    Ok(listings)
+}
+
+/// Record that a scrape run found a coupon, moving its `last_seen` forward and clearing
+/// its likely-dead flag. Returns whether the coupon had been marked likely dead.
+pub async fn record_sighting(
+    pool: &SqlitePool,
+    coupon_id: i64,
+    scraper: &str,
+    source: &str,
+    seen_at: DateTime<Utc>,
+) -> Result<bool> {
+    let _timer = metrics::time_query("record_sighting");
+    let seen_at = seen_at.to_rfc3339();
+    
+    sqlx::query!(
+        r#"
+        INSERT OR IGNORE INTO coupon_sightings
+        (coupon_id, scraper, source, seen_at)
+        VALUES (?, ?, ?, ?)
+        "#,
+        coupon_id,
+        scraper,
+        source,
+        seen_at
+    )
+    .execute(pool)
+    .await
+    .context("Failed to insert coupon sighting")?;
+    
+    let was_dead = sqlx::query_scalar!(
+        r#"SELECT likely_dead as "likely_dead: bool" FROM coupons WHERE id = ?"#,
+        coupon_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to get likely-dead flag")?
+    .unwrap_or(false);
+    
+    sqlx::query!(
+        r#"
+        UPDATE coupons
+        SET last_seen = MAX(COALESCE(last_seen, ''), ?),
+            likely_dead = 0
+        WHERE id = ?
+        "#,
+        seen_at,
+        coupon_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to update coupon last seen")?;
+    
+    Ok(was_dead)
+}
+
+/// Mark coupons as likely dead once every scraper that found them has completed
+/// `missed_runs` runs without finding them again, and return the newly marked coupons.
+/// Runs are the distinct start times recorded in `coupon_sightings`, so a scraper run
+/// that found nothing at all (usually a broken page) doesn't count.
+pub async fn mark_likely_dead_coupons(pool: &SqlitePool, missed_runs: i64) -> Result<Vec<Coupon>> {
+    let _timer = metrics::time_query("mark_likely_dead_coupons");
+    let coupon_ids = sqlx::query_scalar!(
+        r#"
+        SELECT coupon_id as "coupon_id!"
+        FROM (
+            SELECT
+                s.coupon_id,
+                (
+                    SELECT COUNT(DISTINCT later.seen_at)
+                    FROM coupon_sightings later
+                    WHERE later.scraper = s.scraper AND later.seen_at > MAX(s.seen_at)
+                ) AS missed_runs
+            FROM coupon_sightings s
+            GROUP BY s.coupon_id, s.scraper
+        )
+        WHERE coupon_id IN (SELECT id FROM coupons WHERE likely_dead = 0)
+        GROUP BY coupon_id
+        HAVING MIN(missed_runs) >= ?
+        "#,
+        missed_runs
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to find coupons missing from their sources")?;
+    
+    let mut coupons = Vec::new();
+    for coupon_id in coupon_ids {
+        sqlx::query!("UPDATE coupons SET likely_dead = 1 WHERE id = ?", coupon_id)
+            .execute(pool)
+            .await
+            .context("Failed to mark coupon as likely dead")?;
+        
+        if let Some(coupon) = get_coupon_by_id(pool, coupon_id).await? {
+            coupons.push(coupon);
+        }
+    }
+    
+    Ok(coupons)
+}
+
+/// Get the scrape runs that found a coupon, newest first
+pub async fn get_coupon_sightings(
+    pool: &SqlitePool,
+    coupon_id: i64,
+    limit: i64,
+) -> Result<Vec<CouponSighting>> {
+    let _timer = metrics::time_query("get_coupon_sightings");
+    let sightings = sqlx::query_as!(
+        CouponSighting,
+        r#"
+        SELECT
+            scraper,
+            source,
+            seen_at as "seen_at: DateTime<Utc>"
+        FROM coupon_sightings
+        WHERE coupon_id = ?
+        ORDER BY seen_at DESC, id DESC
+        LIMIT ?
+        "#,
+        coupon_id,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to get coupon sightings")?;
+    
+    Ok(sightings)
+}
+
+/// Record a finished scraper run
+pub async fn insert_scrape_run(pool: &SqlitePool, run: &ScrapeRun) -> Result<i64> {
+    let _timer = metrics::time_query("insert_scrape_run");
+    let started_at = run.started_at.to_rfc3339();
+    let finished_at = run.finished_at.to_rfc3339();
+    
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO scrape_runs
+        (scraper, started_at, finished_at, status, coupons_found, http_statuses, bytes_fetched, error)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+        run.scraper,
+        started_at,
+        finished_at,
+        run.status,
+        run.coupons_found,
+        run.http_statuses,
+        run.bytes_fetched,
+        run.error
+    )
+    .execute(pool)
+    .await
+    .context("Failed to insert scrape run")?;
+    
+    Ok(result.last_insert_rowid())
+}
+
+/// Get a scraper's runs, newest first
+pub async fn get_scrape_runs(pool: &SqlitePool, scraper: &str, limit: i64) -> Result<Vec<ScrapeRun>> {
+    let _timer = metrics::time_query("get_scrape_runs");
+    let runs = sqlx::query_as!(
+        ScrapeRun,
+        r#"
+        SELECT
+            id,
+            scraper,
+            started_at as "started_at: DateTime<Utc>",
+            finished_at as "finished_at: DateTime<Utc>",
+            status as "status: ScrapeStatus",
+            coupons_found,
+            http_statuses as "http_statuses: Json<Vec<u16>>",
+            bytes_fetched,
+            error
+        FROM scrape_runs
+        WHERE scraper = ?
+        ORDER BY started_at DESC, id DESC
+        LIMIT ?
+        "#,
+        scraper,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to get scrape runs")?;
+    
+    Ok(runs)
+}
+
+/// Summarize the health of every scraper that has run, from its latest `window` runs
+pub async fn get_scraper_health(
+    pool: &SqlitePool,
+    window: i64,
+    unhealthy_after: u64,
+) -> Result<Vec<ScraperHealth>> {
+    let _timer = metrics::time_query("get_scraper_health");
+    let scrapers = sqlx::query_scalar!("SELECT DISTINCT scraper FROM scrape_runs ORDER BY scraper")
+        .fetch_all(pool)
+        .await
+        .context("Failed to get scrapers with runs")?;
+    
+    let mut health = Vec::new();
+    for scraper in scrapers {
+        let runs = get_scrape_runs(pool, &scraper, window).await?;
+        health.push(ScraperHealth::from_runs(&scraper, &runs, unhealthy_after));
+    }
+    
+    Ok(health)
+}
+
+/// Get the start time of the latest scraper run
+pub async fn get_last_scrape_time(pool: &SqlitePool) -> Result<Option<DateTime<Utc>>> {
+    let _timer = metrics::time_query("get_last_scrape_time");
+    let started_at = sqlx::query_scalar!(
+        r#"SELECT MAX(started_at) as "started_at: DateTime<Utc>" FROM scrape_runs"#
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to get last scrape time")?;
+    
+    Ok(started_at)
+}
+
+/// Delete scraper runs that started before the given time
+pub async fn delete_scrape_runs_before(pool: &SqlitePool, before: DateTime<Utc>) -> Result<u64> {
+    let _timer = metrics::time_query("delete_scrape_runs_before");
+    let before = before.to_rfc3339();
+    
+    let result = sqlx::query!("DELETE FROM scrape_runs WHERE started_at < ?", before)
+        .execute(pool)
+        .await
+        .context("Failed to delete old scrape runs")?;
+    
+    Ok(result.rows_affected())
+}
+
+/// Keep a webhook event that could not be delivered
+pub async fn insert_webhook_dead_letter(pool: &SqlitePool, letter: &WebhookDeadLetter) -> Result<i64> {
+    let _timer = metrics::time_query("insert_webhook_dead_letter");
+    let failed_at = letter.failed_at.to_rfc3339();
+    
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO webhook_dead_letters
+        (url, event_id, event_type, payload, attempts, last_error, http_status, failed_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+        letter.url,
+        letter.event_id,
+        letter.event_type,
+        letter.payload,
+        letter.attempts,
+        letter.last_error,
+        letter.http_status,
+        failed_at
+    )
+    .execute(pool)
+    .await
+    .context("Failed to insert webhook dead letter")?;
+    
+    Ok(result.last_insert_rowid())
+}
+
+/// Mark a coupon valid and queue its announcement, due at `due_at`, in one transaction,
+/// so a crash can't leave a valid coupon behind that is never posted. An announcement
+/// that was skipped or gave up before is queued again.
+pub async fn mark_valid_and_enqueue_post(
+    pool: &SqlitePool,
+    coupon_id: i64,
+    idempotency_key: &str,
+    due_at: DateTime<Utc>,
+) -> Result<()> {
+    let _timer = metrics::time_query("mark_valid_and_enqueue_post");
+    let now = Utc::now().to_rfc3339();
+    let due_at = due_at.to_rfc3339();
+    
+    let mut tx = pool.begin().await.context("Failed to start transaction")?;
+    
+    sqlx::query!(
+        "UPDATE coupons SET is_valid = 1, validated_at = ?, last_checked_at = ? WHERE id = ?",
+        now,
+        now,
+        coupon_id
+    )
+    .execute(&mut *tx)
+    .await
+    .context("Failed to update coupon validation status")?;
+    
+    sqlx::query!(
+        r#"
+        INSERT INTO notification_outbox (coupon_id, idempotency_key, status, created_at, next_attempt_at)
+        VALUES (?, ?, 'pending', ?, ?)
+        ON CONFLICT(idempotency_key) DO UPDATE
+        SET status = 'pending',
+            attempts = 0,
+            last_error = NULL,
+            next_attempt_at = excluded.next_attempt_at
+        WHERE notification_outbox.status IN ('skipped', 'failed')
+        "#,
+        coupon_id,
+        idempotency_key,
+        now,
+        due_at
+    )
+    .execute(&mut *tx)
+    .await
+    .context("Failed to queue coupon announcement")?;
+    
+    tx.commit().await.context("Failed to commit coupon announcement")?;
+    
+    Ok(())
+}
+
+/// Queue the announcement of a coupon unless it has been queued before or another
+/// announcement of it is pending, e.g. one queued under its hash before a rehash.
+/// Returns `true` if it was queued.
+pub async fn enqueue_post(pool: &SqlitePool, coupon_id: i64, idempotency_key: &str) -> Result<bool> {
+    let _timer = metrics::time_query("enqueue_post");
+    let now = Utc::now().to_rfc3339();
+    
+    let result = sqlx::query!(
+        r#"
+        INSERT OR IGNORE INTO notification_outbox (coupon_id, idempotency_key, status, created_at, next_attempt_at)
+        SELECT ?, ?, 'pending', ?, ?
+        WHERE NOT EXISTS (
+            SELECT 1 FROM notification_outbox WHERE coupon_id = ? AND status = 'pending'
+        )
+        "#,
+        coupon_id,
+        idempotency_key,
+        now,
+        now,
+        coupon_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to queue coupon announcement")?;
+    
+    Ok(result.rows_affected() > 0)
+}
+
+/// Get pending announcements whose next attempt is due, oldest first
+pub async fn get_due_outbox_entries(
+    pool: &SqlitePool,
+    now: DateTime<Utc>,
+    limit: i64,
+) -> Result<Vec<OutboxEntry>> {
+    let _timer = metrics::time_query("get_due_outbox_entries");
+    let now = now.to_rfc3339();
+    
+    let entries = sqlx::query_as!(
+        OutboxEntry,
+        r#"
+        SELECT
+            id as "id!",
+            coupon_id,
+            idempotency_key,
+            status as "status: OutboxStatus",
+            attempts,
+            last_error,
+            created_at as "created_at: DateTime<Utc>",
+            next_attempt_at as "next_attempt_at: DateTime<Utc>",
+            last_attempt_at as "last_attempt_at: DateTime<Utc>",
+            delivered_at as "delivered_at: DateTime<Utc>"
+        FROM notification_outbox
+        WHERE status = 'pending' AND next_attempt_at <= ?
+        ORDER BY next_attempt_at, id
+        LIMIT ?
+        "#,
+        now,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to get due outbox entries")?;
+    
+    Ok(entries)
+}
+
+/// Get the targets that already received an announcement in earlier attempts
+pub async fn get_outbox_deliveries(pool: &SqlitePool, outbox_id: i64) -> Result<Vec<String>> {
+    let _timer = metrics::time_query("get_outbox_deliveries");
+    let targets = sqlx::query_scalar!(
+        "SELECT target FROM outbox_deliveries WHERE outbox_id = ? ORDER BY target",
+        outbox_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to get delivered targets")?;
+    
+    Ok(targets)
+}
+
+/// Record the targets an announcement reached in an attempt and the Discord messages
+/// it was sent as
+async fn save_deliveries(
+    tx: &mut Transaction<'_, Sqlite>,
+    entry: &OutboxEntry,
+    targets: &[String],
+    messages: &[SentMessage],
+    now: &str,
+) -> Result<()> {
+    for target in targets {
+        sqlx::query!(
+            "INSERT OR IGNORE INTO outbox_deliveries (outbox_id, target, delivered_at) VALUES (?, ?, ?)",
+            entry.id,
+            target,
+            now
+        )
+        .execute(&mut **tx)
+        .await
+        .context("Failed to save delivered target")?;
+    }
+    
+    for message in messages {
+        save_discord_message(tx, entry.coupon_id, message, now).await?;
+    }
+    
+    Ok(())
+}
+
+/// Remember a Discord message announcing a coupon, so it can be edited or deleted later
+async fn save_discord_message(
+    tx: &mut Transaction<'_, Sqlite>,
+    coupon_id: i64,
+    message: &SentMessage,
+    now: &str,
+) -> Result<()> {
+    let message_id = message.message_id.to_string();
+    let channel_id = message.channel_id.to_string();
+    let embed_index = message.embed_index as i64;
+    let embed_count = message.embed_count as i64;
+    let field_index = message.field_index.map(|index| index as i64);
+    sqlx::query!(
+        r#"
+        INSERT OR REPLACE INTO discord_messages
+        (coupon_id, message_id, target, channel_id, via_webhook, posted_at, embed_index, embed_count, field_index)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+        coupon_id,
+        message_id,
+        message.target,
+        channel_id,
+        message.via_webhook,
+        now,
+        embed_index,
+        embed_count,
+        field_index
+    )
+    .execute(&mut **tx)
+    .await
+    .context("Failed to save Discord message")?;
+    
+    Ok(())
+}
+
+/// Remember the Discord messages of a posted digest, each with the coupon it lists
+pub async fn save_digest_messages(pool: &SqlitePool, messages: &[(i64, SentMessage)]) -> Result<()> {
+    let _timer = metrics::time_query("save_digest_messages");
+    let now = Utc::now().to_rfc3339();
+    
+    let mut tx = pool.begin().await.context("Failed to start transaction")?;
+    for (coupon_id, message) in messages {
+        save_discord_message(&mut tx, *coupon_id, message, &now).await?;
+    }
+    tx.commit().await.context("Failed to commit digest messages")?;
+    
+    Ok(())
+}
+
+/// Mark an announcement delivered, together with its coupon, the targets the last attempt
+/// reached and the Discord messages it was sent as
+pub async fn complete_outbox_entry(
+    pool: &SqlitePool,
+    entry: &OutboxEntry,
+    targets: &[String],
+    messages: &[SentMessage],
+) -> Result<()> {
+    let _timer = metrics::time_query("complete_outbox_entry");
+    let now = Utc::now().to_rfc3339();
+    
+    let mut tx = pool.begin().await.context("Failed to start transaction")?;
+    
+    sqlx::query!("UPDATE coupons SET is_posted = 1 WHERE id = ?", entry.coupon_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to mark coupon as posted")?;
+    
+    save_deliveries(&mut tx, entry, targets, messages, &now).await?;
+    
+    sqlx::query!(
+        r#"
+        UPDATE notification_outbox
+        SET status = 'delivered',
+            attempts = attempts + 1,
+            last_error = NULL,
+            last_attempt_at = ?,
+            delivered_at = ?
+        WHERE id = ?
+        "#,
+        now,
+        now,
+        entry.id
+    )
+    .execute(&mut *tx)
+    .await
+    .context("Failed to mark announcement delivered")?;
+    
+    tx.commit().await.context("Failed to commit announcement delivery")?;
+    
+    Ok(())
+}
+
+/// Drop an announcement without sending it
+pub async fn skip_outbox_entry(pool: &SqlitePool, id: i64, reason: &str) -> Result<()> {
+    let _timer = metrics::time_query("skip_outbox_entry");
+    sqlx::query!(
+        "UPDATE notification_outbox SET status = 'skipped', last_error = ? WHERE id = ?",
+        reason,
+        id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to skip announcement")?;
+    
+    Ok(())
+}
+
+/// Record an attempt that failed for some targets, together with the targets it did
+/// reach and the Discord messages it was sent as. The announcement is retried at
+/// `retry_at`, or given up if it is `None`; a coupon that reached any target by then
+/// counts as posted.
+pub async fn fail_outbox_attempt(
+    pool: &SqlitePool,
+    entry: &OutboxEntry,
+    targets: &[String],
+    messages: &[SentMessage],
+    error: &str,
+    retry_at: Option<DateTime<Utc>>,
+) -> Result<()> {
+    let _timer = metrics::time_query("fail_outbox_attempt");
+    let now = Utc::now();
+    let status = match retry_at {
+        Some(_) => OutboxStatus::Pending,
+        None => OutboxStatus::Failed,
+    };
+    let next_attempt_at = retry_at.unwrap_or(now).to_rfc3339();
+    let now = now.to_rfc3339();
+    
+    let mut tx = pool.begin().await.context("Failed to start transaction")?;
+    
+    save_deliveries(&mut tx, entry, targets, messages, &now).await?;
+    
+    sqlx::query!(
+        r#"
+        UPDATE notification_outbox
+        SET status = ?,
+            attempts = attempts + 1,
+            last_error = ?,
+            last_attempt_at = ?,
+            next_attempt_at = ?
+        WHERE id = ?
+        "#,
+        status,
+        error,
+        now,
+        next_attempt_at,
+        entry.id
+    )
+    .execute(&mut *tx)
+    .await
+    .context("Failed to record announcement attempt")?;
+    
+    if retry_at.is_none() {
+        sqlx::query!(
+            r#"
+            UPDATE coupons SET is_posted = 1
+            WHERE id = ? AND EXISTS (SELECT 1 FROM outbox_deliveries WHERE outbox_id = ?)
+            "#,
+            entry.coupon_id,
+            entry.id
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to mark coupon as posted")?;
+    }
+    
+    tx.commit().await.context("Failed to commit announcement attempt")?;
+    
+    Ok(())
+}
+
+/// Get the coupons a digest target accepted from the outbox in `[start, end)` that are
+/// still valid and haven't expired by `now`. A coupon is accepted once it is valid, so
+/// coupons validated long after they were first seen land in the digest of that time.
+pub async fn get_digest_coupons(
+    pool: &SqlitePool,
+    target: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Result<Vec<Coupon>> {
+    let _timer = metrics::time_query("get_digest_coupons");
+    let start = start.to_rfc3339();
+    let end = end.to_rfc3339();
+    let now = now.to_rfc3339();
+    
+    let coupons = sqlx::query_as!(
+        Coupon,
+        r#"
+        SELECT 
+            id,
+            name,
+            description,
+            discount_percentage,
+            code,
+            url,
+            source,
+            expiry as "expiry: DateTime<Utc>",
+            created_at as "created_at: DateTime<Utc>",
+            validated_at as "validated_at: DateTime<Utc>",
+            first_seen as "first_seen: DateTime<Utc>",
+            last_seen as "last_seen: DateTime<Utc>",
+            likely_dead as "likely_dead: bool",
+            is_valid as "is_valid: bool",
+            is_posted as "is_posted: bool",
+            hash
+        FROM coupons
+        WHERE is_valid = 1
+            AND id IN (
+                SELECT notification_outbox.coupon_id
+                FROM outbox_deliveries
+                JOIN notification_outbox ON notification_outbox.id = outbox_deliveries.outbox_id
+                WHERE outbox_deliveries.target = ?
+                    AND outbox_deliveries.delivered_at >= ? AND outbox_deliveries.delivered_at < ?
+            )
+            AND (expiry IS NULL OR expiry > ?)
+        ORDER BY first_seen ASC
+        "#,
+        target,
+        start,
+        end,
+        now
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to get coupons of digest window")?;
+    
+    Ok(coupons)
+}
+
+/// Get the latest digest run of a frequency posted to a Discord target. Runs from before
+/// digests were tracked per target count for every target.
+pub async fn get_last_digest_run(
+    pool: &SqlitePool,
+    frequency: DigestFrequency,
+    target: &str,
+) -> Result<Option<DigestRun>> {
+    let _timer = metrics::time_query("get_last_digest_run");
+    let run = sqlx::query_as!(
+        DigestRun,
+        r#"
+        SELECT
+            id,
+            frequency as "frequency: DigestFrequency",
+            target,
+            window_start as "window_start: DateTime<Utc>",
+            window_end as "window_end: DateTime<Utc>",
+            coupon_count,
+            targets,
+            posted_at as "posted_at: DateTime<Utc>"
+        FROM digest_runs
+        WHERE frequency = ? AND (target = ? OR target = '')
+        ORDER BY window_end DESC, id DESC
+        LIMIT 1
+        "#,
+        frequency,
+        target
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to get last digest run")?;
+    
+    Ok(run)
+}
+
+/// Record a posted or skipped digest
+pub async fn insert_digest_run(pool: &SqlitePool, run: &DigestRun) -> Result<i64> {
+    let _timer = metrics::time_query("insert_digest_run");
+    let window_start = run.window_start.to_rfc3339();
+    let window_end = run.window_end.to_rfc3339();
+    let posted_at = run.posted_at.to_rfc3339();
+    
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO digest_runs (frequency, target, window_start, window_end, coupon_count, targets, posted_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
+        "#,
+        run.frequency,
+        run.target,
+        window_start,
+        window_end,
+        run.coupon_count,
+        run.targets,
+        posted_at
+    )
+    .execute(pool)
+    .await
+    .context("Failed to insert digest run")?;
+    
+    Ok(result.last_insert_rowid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::memory_database;
+    use serenity::model::id::{ChannelId, MessageId};
+
+    /// Insert a coupon with a hash from before `Coupon::identity_hash`
+    async fn insert_legacy_coupon(pool: &SqlitePool, name: &str, url: &str, hash: &str) -> i64 {
+        let mut coupon = Coupon::new(
+            name.to_string(),
+            String::new(),
+            None,
+            "STUDENT".to_string(),
+            url.to_string(),
+            "GitHub".to_string(),
+            None,
+        );
+        coupon.hash = hash.to_string();
+        insert_coupon(pool, &coupon).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn rehash_merges_duplicates_into_oldest_coupon() {
+        let pool = memory_database().await;
+        let keeper =
+            insert_legacy_coupon(&pool, "GitHub Student Pack", "https://github.com", "a1").await;
+        let duplicate = insert_legacy_coupon(
+            &pool,
+            "100% off GitHub Student Pack",
+            "https://github.com/?utm_source=feed",
+            "b2",
+        )
+        .await;
+        let other = insert_legacy_coupon(&pool, "GitLab", "https://gitlab.com", "c3").await;
+
+        mark_valid_and_enqueue_post(&pool, duplicate, "post-b2", Utc::now()).await.unwrap();
+        let entry = get_due_outbox_entries(&pool, Utc::now(), 1).await.unwrap().remove(0);
+        let message = SentMessage {
+            target: "default".to_string(),
+            message_id: MessageId(1),
+            channel_id: ChannelId(2),
+            via_webhook: true,
+            embed_index: 0,
+            embed_count: 1,
+            field_index: None,
+        };
+        complete_outbox_entry(&pool, &entry, &["discord:default".to_string()], &[message]).await.unwrap();
+
+        rehash_coupons(&pool).await.unwrap();
+        // Running it again finds nothing left to do
+        rehash_coupons(&pool).await.unwrap();
+
+        let coupons = get_all_coupons(&pool).await.unwrap();
+        assert_eq!(coupons.len(), 2);
+        assert!(get_coupon_by_id(&pool, duplicate).await.unwrap().is_none());
+
+        let merged = get_coupon_by_id(&pool, keeper).await.unwrap().unwrap();
+        assert_eq!(
+            merged.hash,
+            Coupon::identity_hash("GitHub", "STUDENT", "https://github.com")
+        );
+        assert!(merged.is_valid);
+        assert!(merged.is_posted);
+        assert_eq!(get_discord_messages(&pool, keeper).await.unwrap().len(), 1);
+
+        let other = get_coupon_by_id(&pool, other).await.unwrap().unwrap();
+        assert_eq!(
+            other.hash,
+            Coupon::identity_hash("GitHub", "STUDENT", "https://gitlab.com")
+        );
+    }
+
+    #[tokio::test]
+    async fn rehash_merges_duplicates_announced_in_one_message() {
+        let pool = memory_database().await;
+        let keeper =
+            insert_legacy_coupon(&pool, "GitHub Student Pack", "https://github.com", "a1").await;
+        let duplicate = insert_legacy_coupon(
+            &pool,
+            "100% off GitHub Student Pack",
+            "https://github.com/?utm_source=feed",
+            "b2",
+        )
+        .await;
+
+        // Both coupons were announced in the same batched message
+        mark_valid_and_enqueue_post(&pool, keeper, "post-a1", Utc::now()).await.unwrap();
+        mark_valid_and_enqueue_post(&pool, duplicate, "post-b2", Utc::now()).await.unwrap();
+        let entries = get_due_outbox_entries(&pool, Utc::now(), 10).await.unwrap();
+        for (embed_index, entry) in entries.iter().enumerate() {
+            let message = SentMessage {
+                target: "default".to_string(),
+                message_id: MessageId(1),
+                channel_id: ChannelId(2),
+                via_webhook: true,
+                embed_index,
+                embed_count: 2,
+                field_index: None,
+            };
+            complete_outbox_entry(&pool, entry, &["discord:default".to_string()], &[message]).await.unwrap();
+        }
+
+        rehash_coupons(&pool).await.unwrap();
+
+        assert!(get_coupon_by_id(&pool, duplicate).await.unwrap().is_none());
+        assert_eq!(get_discord_messages(&pool, keeper).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn rehash_runs_once() {
+        let pool = memory_database().await;
+        rehash_coupons(&pool).await.unwrap();
+
+        let coupon_id =
+            insert_legacy_coupon(&pool, "GitHub Student Pack", "https://github.com", "a1").await;
+        rehash_coupons(&pool).await.unwrap();
+        let coupon = get_coupon_by_id(&pool, coupon_id).await.unwrap().unwrap();
+        assert_eq!(coupon.hash, "a1");
+    }
+
+    #[tokio::test]
+    async fn rehash_keeps_one_announcement_of_merged_coupons() {
+        let pool = memory_database().await;
+        let keeper =
+            insert_legacy_coupon(&pool, "GitHub Student Pack", "https://github.com", "a1").await;
+        let duplicate = insert_legacy_coupon(
+            &pool,
+            "100% off GitHub Student Pack",
+            "https://github.com/?utm_source=feed",
+            "b2",
+        )
+        .await;
+
+        // Both announcements are pending, the duplicate's already reached Slack
+        mark_valid_and_enqueue_post(&pool, keeper, "post-a1", Utc::now()).await.unwrap();
+        mark_valid_and_enqueue_post(&pool, duplicate, "post-b2", Utc::now()).await.unwrap();
+        let entries = get_due_outbox_entries(&pool, Utc::now(), 10).await.unwrap();
+        let later = Utc::now() + chrono::Duration::minutes(1);
+        fail_outbox_attempt(&pool, &entries[1], &["slack".to_string()], &[], "email: timeout", Some(later))
+            .await
+            .unwrap();
+
+        rehash_coupons(&pool).await.unwrap();
+
+        // The duplicate's announcement isn't deleted with it, and only one stays pending
+        let pending = get_due_outbox_entries(&pool, later, 10).await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].coupon_id, keeper);
+        assert_eq!(get_outbox_deliveries(&pool, pending[0].id).await.unwrap(), vec!["slack"]);
+
+        // Queueing the coupon under its new hash doesn't announce it twice
+        let hash = Coupon::identity_hash("GitHub", "STUDENT", "https://github.com");
+        assert!(!enqueue_post(&pool, keeper, &format!("post-{}", hash)).await.unwrap());
+        assert_eq!(get_queue_sizes(&pool).await.unwrap().outbox_pending, 1);
+    }
+
+    #[tokio::test]
+    async fn rehash_queues_merged_coupons_that_were_never_announced() {
+        let pool = memory_database().await;
+        let keeper =
+            insert_legacy_coupon(&pool, "GitHub Student Pack", "https://github.com", "a1").await;
+        let duplicate = insert_legacy_coupon(
+            &pool,
+            "100% off GitHub Student Pack",
+            "https://github.com/?utm_source=feed",
+            "b2",
+        )
+        .await;
+
+        // The duplicate's announcement was skipped while it was invalid, the keeper has none
+        mark_valid_and_enqueue_post(&pool, duplicate, "post-b2", Utc::now()).await.unwrap();
+        let entry = get_due_outbox_entries(&pool, Utc::now(), 1).await.unwrap().remove(0);
+        skip_outbox_entry(&pool, entry.id, "Coupon is no longer valid").await.unwrap();
+        update_validation_status(&pool, keeper, true).await.unwrap();
+
+        rehash_coupons(&pool).await.unwrap();
+
+        let pending = get_due_outbox_entries(&pool, Utc::now(), 10).await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].id, entry.id);
+        assert_eq!(pending[0].coupon_id, keeper);
+    }
+
+    #[tokio::test]
+    async fn failed_checks_move_coupons_to_the_back_of_the_revalidation_queue() {
+        let pool = memory_database().await;
+        let failing = insert_legacy_coupon(&pool, "GitHub Student Pack", "https://github.com", "a1").await;
+        let other = insert_legacy_coupon(&pool, "GitLab", "https://gitlab.com", "b2").await;
+        update_validation_status(&pool, failing, true).await.unwrap();
+        update_validation_status(&pool, other, true).await.unwrap();
+
+        let later = Utc::now() + chrono::Duration::hours(1);
+        let stale = get_stale_coupons(&pool, later, 1).await.unwrap();
+        assert_eq!(stale[0].id, Some(failing));
+
+        let validated_at = get_coupon_by_id(&pool, failing).await.unwrap().unwrap().validated_at;
+        mark_checked(&pool, failing).await.unwrap();
+
+        let stale = get_stale_coupons(&pool, later, 1).await.unwrap();
+        assert_eq!(stale[0].id, Some(other));
+        let coupon = get_coupon_by_id(&pool, failing).await.unwrap().unwrap();
+        assert!(coupon.is_valid);
+        assert_eq!(coupon.validated_at, validated_at);
+    }
+
+    #[tokio::test]
+    async fn expired_coupons_are_not_revalidated() {
+        let pool = memory_database().await;
+        let mut expired = Coupon::new(
+            "Expired".to_string(),
+            String::new(),
+            None,
+            "OLD".to_string(),
+            "https://github.com".to_string(),
+            "GitHub".to_string(),
+            Some(Utc::now() - chrono::Duration::days(1)),
+        );
+        expired.hash = "a1".to_string();
+        insert_coupon(&pool, &expired).await.unwrap();
+        let current = insert_legacy_coupon(&pool, "GitLab", "https://gitlab.com", "b2").await;
+
+        let stale = get_stale_coupons(&pool, Utc::now(), 10).await.unwrap();
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].id, Some(current));
+    }
+
+    #[tokio::test]
+    async fn listings_resolve_to_their_coupon() {
+        let pool = memory_database().await;
+        let coupon_id =
+            insert_legacy_coupon(&pool, "GitHub Student Pack", "https://github.com", "a1").await;
+
+        let listing = CouponListing {
+            id: None,
+            coupon_id,
+            source: "Generic".to_string(),
+            name: "AI Dev Tools: GitHub students".to_string(),
+            url: "https://aidevtools.com/deals".to_string(),
+            hash: "d4".to_string(),
+            first_seen: Utc::now(),
+        };
+        insert_coupon_listing(&pool, &listing).await.unwrap();
+
+        assert_eq!(find_coupon_id_by_hash(&pool, "a1").await.unwrap(), Some(coupon_id));
+        assert_eq!(find_coupon_id_by_hash(&pool, "d4").await.unwrap(), Some(coupon_id));
+        assert_eq!(find_coupon_id_by_hash(&pool, "e5").await.unwrap(), None);
+
+        let by_code = get_coupons_by_code(&pool, " student ").await.unwrap();
+        assert_eq!(by_code.len(), 1);
+        assert_eq!(get_coupon_listings(&pool, coupon_id).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn coupons_missed_by_their_scrapers_are_likely_dead() {
+        let pool = memory_database().await;
+        let gone =
+            insert_legacy_coupon(&pool, "GitHub Student Pack", "https://github.com", "a1").await;
+        let listed = insert_legacy_coupon(&pool, "GitLab", "https://gitlab.com", "b2").await;
+
+        let start = Utc::now();
+        let run = |n: i64| start + chrono::Duration::minutes(n);
+        record_sighting(&pool, gone, "GitHub", "GitHub", run(0)).await.unwrap();
+        for n in 0..3 {
+            record_sighting(&pool, listed, "GitHub", "GitHub", run(n)).await.unwrap();
+            assert!(mark_likely_dead_coupons(&pool, 3).await.unwrap().is_empty());
+        }
+
+        record_sighting(&pool, listed, "GitHub", "GitHub", run(3)).await.unwrap();
+        let dead = mark_likely_dead_coupons(&pool, 3).await.unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].id, Some(gone));
+        assert!(dead[0].likely_dead);
+        assert!(mark_likely_dead_coupons(&pool, 3).await.unwrap().is_empty());
+
+        // Finding the coupon again clears the flag
+        assert!(record_sighting(&pool, gone, "GitHub", "GitHub", run(4)).await.unwrap());
+        let coupon = get_coupon_by_id(&pool, gone).await.unwrap().unwrap();
+        assert!(!coupon.likely_dead);
+        assert_eq!(coupon.last_seen.map(|t| t.timestamp()), Some(run(4).timestamp()));
+        assert_eq!(get_coupon_sightings(&pool, gone, 10).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn scraper_health_counts_failures_since_last_success() {
+        let pool = memory_database().await;
+        let start = Utc::now();
+        let statuses = [
+            ScrapeStatus::Success,
+            ScrapeStatus::Empty,
+            ScrapeStatus::Failed,
+            ScrapeStatus::TimedOut,
+        ];
+        for (n, status) in statuses.into_iter().enumerate() {
+            let started_at = start + chrono::Duration::minutes(n as i64);
+            let run = ScrapeRun {
+                id: None,
+                scraper: "GitHub".to_string(),
+                started_at,
+                finished_at: started_at,
+                status,
+                coupons_found: if status == ScrapeStatus::Success { 2 } else { 0 },
+                http_statuses: Json(vec![200]),
+                bytes_fetched: 1024,
+                error: (status == ScrapeStatus::Failed).then(|| "HTTP 503".to_string()),
+            };
+            insert_scrape_run(&pool, &run).await.unwrap();
+        }
+
+        let health = get_scraper_health(&pool, 20, 3).await.unwrap();
+        assert_eq!(health.len(), 1);
+        assert!(!health[0].healthy);
+        assert_eq!(health[0].consecutive_failures, 3);
+        assert_eq!(health[0].last_status, Some(ScrapeStatus::TimedOut));
+        assert_eq!(health[0].success_rate, 0.25);
+        assert!(get_scraper_health(&pool, 20, 4).await.unwrap()[0].healthy);
+
+        let runs = get_scrape_runs(&pool, "GitHub", 2).await.unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[1].error.as_deref(), Some("HTTP 503"));
+        let last_scrape = get_last_scrape_time(&pool).await.unwrap();
+        assert_eq!(
+            last_scrape.map(|t| t.timestamp()),
+            Some((start + chrono::Duration::minutes(3)).timestamp())
+        );
+    }
+
+    #[tokio::test]
+    async fn migrations_keep_the_coupons_table() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        apply_migrations(&pool).await.unwrap();
+        
+        let coupons: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'coupons'",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(coupons, 1);
+        
+        // Databases that applied the coupons migration with its down section still start
+        sqlx::query("UPDATE _sqlx_migrations SET checksum = ? WHERE version = ?")
+            .bind(hex::decode(OLD_COUPONS_MIGRATION_CHECKSUM).unwrap())
+            .bind(COUPONS_MIGRATION)
+            .execute(&pool)
+            .await
+            .unwrap();
+        prepare_schema(&pool).await.unwrap();
+        
+        // Other changes to the applied migration are still reported
+        sqlx::query("UPDATE _sqlx_migrations SET checksum = x'00' WHERE version = ?")
+            .bind(COUPONS_MIGRATION)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(prepare_schema(&pool).await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn readiness_checks_pass_on_prepared_schema() {
+        let pool = memory_database().await;
+        ping(&pool).await.unwrap();
+        assert_eq!(get_pending_migrations(&pool).await.unwrap(), 0);
+
+        let coupon_id =
+            insert_legacy_coupon(&pool, "GitHub Student Pack", "https://github.com", "a1").await;
+        mark_valid_and_enqueue_post(&pool, coupon_id, "post-a1", Utc::now()).await.unwrap();
+        let sizes = get_queue_sizes(&pool).await.unwrap();
+        assert_eq!(sizes.unposted_coupons, 1);
+        assert_eq!(sizes.outbox_pending, 1);
+        assert_eq!(sizes.webhook_dead_letters, 0);
+
+        let entry = get_due_outbox_entries(&pool, Utc::now(), 1).await.unwrap().remove(0);
+        complete_outbox_entry(&pool, &entry, &[], &[]).await.unwrap();
+        let sizes = get_queue_sizes(&pool).await.unwrap();
+        assert_eq!(sizes.unposted_coupons, 0);
+        assert_eq!(sizes.outbox_pending, 0);
+    }
+    
+    #[tokio::test]
+    async fn filtered_coupons_keep_their_flags() {
+        let pool = memory_database().await;
+        let posted =
+            insert_legacy_coupon(&pool, "GitHub Student Pack", "https://github.com", "a1").await;
+        let unchecked = insert_legacy_coupon(&pool, "Replit Core", "https://replit.com", "b2").await;
+        mark_valid_and_enqueue_post(&pool, posted, "post-a1", Utc::now()).await.unwrap();
+        let entry = get_due_outbox_entries(&pool, Utc::now(), 1).await.unwrap().remove(0);
+        complete_outbox_entry(&pool, &entry, &[], &[]).await.unwrap();
+
+        let filter = CouponFilter {
+            source: None,
+            is_valid: None,
+            expired: None,
+            expires_before: None,
+            limit: 10,
+            offset: 0,
+        };
+        let (coupons, total) = get_coupons_filtered(&pool, &filter).await.unwrap();
+        assert_eq!(total, 2);
+        let posted = coupons.iter().find(|c| c.id == Some(posted)).unwrap();
+        assert!(posted.is_valid);
+        assert!(posted.is_posted);
+        assert!(posted.validated_at.is_some());
+        let unchecked = coupons.iter().find(|c| c.id == Some(unchecked)).unwrap();
+        assert!(!unchecked.is_valid);
+        assert!(!unchecked.is_posted);
+
+        let filter = CouponFilter { is_valid: Some(true), ..filter };
+        let (coupons, total) = get_coupons_filtered(&pool, &filter).await.unwrap();
+        assert_eq!(total, 1);
+        assert!(coupons[0].is_valid);
+    }
+}
@@ -0,0 +1,513 @@
+use anyhow::{Context as AnyhowContext, Result};
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use config::{Config as ConfigCrate, File};
+use regex::Regex;
+use reqwest::Url;
+use scraper::{ElementRef, Html, Selector};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::models::{Coupon, CouponSource};
+use crate::proxy::HttpClient;
+use crate::scraper::{element_text, parse_expiry, Scraper};
+
+/// File extensions recognised as scraper definitions
+const DEFINITION_EXTENSIONS: &[&str] = &["toml", "yaml", "yml"];
+
+/// A scraper described by a TOML or YAML file instead of code
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScraperDefinition {
+    /// Scraper name, used in logs and to match `[[scraping.schedules]]` entries
+    pub name: String,
+    /// Source the coupons are attributed to, e.g. `CursorAI`
+    pub source: CouponSource,
+    /// Base that relative page URLs are resolved against
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub pages: Vec<PageDefinition>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// A page to fetch and how to turn it into coupons
+#[derive(Debug, Clone, Deserialize)]
+pub struct PageDefinition {
+    /// Page URL, absolute or relative to the definition's `base_url`
+    pub url: String,
+    /// Elements that each hold one coupon; without it the whole page yields a single coupon
+    #[serde(default)]
+    pub item: Option<String>,
+    /// Maximum number of `item` matches to turn into coupons
+    #[serde(default)]
+    pub max_items: Option<usize>,
+    pub name: FieldDefinition,
+    #[serde(default)]
+    pub description: FieldDefinition,
+    pub code: FieldDefinition,
+    #[serde(default)]
+    pub discount: FieldDefinition,
+    #[serde(default)]
+    pub expiry: FieldDefinition,
+    /// Days until expiry when no expiry date is scraped
+    #[serde(default)]
+    pub expires_in_days: Option<i64>,
+}
+
+/// How to extract one coupon field from an item.
+///
+/// A field without `selector`, `attribute` or `regex` always takes its `default`.
+/// `name` and `description` may reference the extracted `{code}` and `{discount}`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FieldDefinition {
+    /// Element inside the item to read; the item itself when omitted
+    #[serde(default)]
+    pub selector: Option<String>,
+    /// Attribute to read instead of the element's text
+    #[serde(default)]
+    pub attribute: Option<String>,
+    /// Pattern applied to the value; the first capture group, or the whole match, is kept
+    #[serde(default)]
+    pub regex: Option<String>,
+    /// Value used when nothing could be extracted
+    #[serde(default)]
+    pub default: Option<String>,
+}
+
+/// A field definition with its selector and regex compiled
+struct CompiledField {
+    selector: Option<Selector>,
+    attribute: Option<String>,
+    regex: Option<Regex>,
+    default: Option<String>,
+}
+
+impl CompiledField {
+    fn compile(field: &FieldDefinition) -> Result<Self> {
+        let selector = field
+            .selector
+            .as_deref()
+            .map(compile_selector)
+            .transpose()?;
+        let regex = field
+            .regex
+            .as_deref()
+            .map(|pattern| {
+                Regex::new(pattern).with_context(|| format!("Invalid regex '{}'", pattern))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            selector,
+            attribute: field.attribute.clone(),
+            regex,
+            default: field.default.clone(),
+        })
+    }
+
+    /// Extract the field's value from an item, falling back to the default
+    fn extract(&self, item: ElementRef) -> Option<String> {
+        if self.selector.is_none() && self.attribute.is_none() && self.regex.is_none() {
+            return self.default.clone();
+        }
+
+        let element = match &self.selector {
+            Some(selector) => item.select(selector).next(),
+            None => Some(item),
+        };
+
+        let value = element.and_then(|element| match &self.attribute {
+            Some(attribute) => element.value().attr(attribute).map(str::to_string),
+            None => Some(element_text(element)),
+        });
+
+        let value = value.and_then(|value| match &self.regex {
+            Some(regex) => regex
+                .captures(&value)
+                .and_then(|cap| cap.get(1).or_else(|| cap.get(0)))
+                .map(|m| m.as_str().to_string()),
+            None => Some(value),
+        });
+
+        value
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+            .or_else(|| self.default.clone())
+    }
+}
+
+/// A page definition with its selectors compiled
+struct CompiledPage {
+    url: String,
+    item: Option<Selector>,
+    max_items: usize,
+    name: CompiledField,
+    description: CompiledField,
+    code: CompiledField,
+    discount: CompiledField,
+    expiry: CompiledField,
+    expires_in_days: Option<i64>,
+}
+
+impl CompiledPage {
+    fn compile(page: &PageDefinition, base_url: Option<&Url>) -> Result<Self> {
+        let url = match base_url {
+            Some(base_url) => base_url.join(&page.url),
+            None => Url::parse(&page.url),
+        }
+        .with_context(|| format!("Invalid URL: {}", page.url))?;
+
+        let field = |field: &FieldDefinition, label: &str| {
+            CompiledField::compile(field).with_context(|| format!("Invalid {} field", label))
+        };
+
+        Ok(Self {
+            url: url.to_string(),
+            item: page.item.as_deref().map(compile_selector).transpose()?,
+            max_items: page.max_items.unwrap_or(usize::MAX),
+            name: field(&page.name, "name")?,
+            description: field(&page.description, "description")?,
+            code: field(&page.code, "code")?,
+            discount: field(&page.discount, "discount")?,
+            expiry: field(&page.expiry, "expiry")?,
+            expires_in_days: page.expires_in_days,
+        })
+    }
+
+    /// Turn a fetched page into coupons
+    fn extract(&self, document: &Html, source: &CouponSource) -> Vec<Coupon> {
+        let items: Vec<ElementRef> = match &self.item {
+            Some(selector) => document.select(selector).take(self.max_items).collect(),
+            None => vec![document.root_element()],
+        };
+
+        items
+            .into_iter()
+            .filter_map(|item| self.extract_coupon(item, source))
+            .collect()
+    }
+
+    /// Build a coupon from a single item; items without a code are skipped
+    fn extract_coupon(&self, item: ElementRef, source: &CouponSource) -> Option<Coupon> {
+        let code = self.code.extract(item)?;
+        let discount = self.discount.extract(item).and_then(|value| {
+            value.trim_end_matches('%').trim().parse::<f64>().ok()
+        });
+
+        let fill = |template: String| {
+            template
+                .replace("{code}", &code)
+                .replace("{discount}", &discount.map(|d| d.to_string()).unwrap_or_default())
+        };
+        let name = fill(self.name.extract(item)?);
+        let description = self.description.extract(item).map(fill).unwrap_or_default();
+
+        let expiry = self
+            .expiry
+            .extract(item)
+            .and_then(|value| parse_expiry(&value))
+            .or_else(|| {
+                self.expires_in_days
+                    .map(|days| Utc::now() + Duration::days(days))
+            });
+
+        Some(Coupon::new(
+            name,
+            description,
+            discount,
+            code,
+            self.url.clone(),
+            source.to_string(),
+            expiry,
+        ))
+    }
+}
+
+fn compile_selector(selector: &str) -> Result<Selector> {
+    Selector::parse(selector)
+        .map_err(|e| anyhow::anyhow!("Invalid CSS selector '{}': {}", selector, e))
+}
+
+/// Scraper driven by a `ScraperDefinition`
+pub struct DeclarativeScraper {
+    name: String,
+    source: CouponSource,
+    pages: Vec<CompiledPage>,
+}
+
+impl DeclarativeScraper {
+    /// Compile a definition, failing on invalid URLs, selectors or regexes
+    pub fn new(definition: &ScraperDefinition) -> Result<Self> {
+        let pages = definition
+            .base_url
+            .as_deref()
+            .map(|base_url| {
+                Url::parse(base_url).with_context(|| format!("Invalid base URL: {}", base_url))
+            })
+            .transpose()
+            .and_then(|base_url| {
+                definition
+                    .pages
+                    .iter()
+                    .map(|page| CompiledPage::compile(page, base_url.as_ref()))
+                    .collect::<Result<Vec<_>>>()
+            })
+            .with_context(|| format!("Invalid scraper definition for {}", definition.name))?;
+
+        Ok(Self {
+            name: definition.name.clone(),
+            source: definition.source.clone(),
+            pages,
+        })
+    }
+}
+
+#[async_trait]
+impl Scraper for DeclarativeScraper {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn source(&self) -> String {
+        self.source.to_string()
+    }
+
+    async fn scrape(&self, client: &HttpClient) -> Result<Vec<Coupon>> {
+        info!("Scraping coupons from {}", self.name);
+        let mut coupons = Vec::new();
+
+        for page in &self.pages {
+            let response = client
+                .get(&page.url)
+                .await
+                .with_context(|| format!("Failed to fetch {} page {}", self.name, page.url))?;
+
+            if !response.status().is_success() {
+                warn!("Failed to fetch {} page {}: HTTP {}", self.name, page.url, response.status());
+                continue;
+            }
+
+            let html = client.text(response).await.context("Failed to get response text")?;
+            let document = Html::parse_document(&html);
+            coupons.extend(page.extract(&document, &self.source));
+        }
+
+        info!("Found {} coupons from {}", coupons.len(), self.name);
+        Ok(coupons)
+    }
+}
+
+/// Load every scraper definition in a directory, sorted by file name.
+/// A missing directory yields no definitions.
+pub fn load_definitions(dir: &str) -> Result<Vec<ScraperDefinition>> {
+    let path = Path::new(dir);
+    if !path.is_dir() {
+        info!("No scraper definitions directory at {}", dir);
+        return Ok(Vec::new());
+    }
+
+    let mut files: Vec<PathBuf> = std::fs::read_dir(path)
+        .with_context(|| format!("Failed to read scraper definitions from {}", dir))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|file| {
+            file.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| DEFINITION_EXTENSIONS.contains(&ext))
+        })
+        .collect();
+    files.sort();
+
+    files.iter().map(|file| load_definition(file)).collect()
+}
+
+/// Parse a single TOML or YAML scraper definition
+fn load_definition(file: &Path) -> Result<ScraperDefinition> {
+    ConfigCrate::builder()
+        .add_source(File::from(file))
+        .build()
+        .and_then(|definition| definition.try_deserialize())
+        .with_context(|| format!("Invalid scraper definition {}", file.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{serve_fixtures, test_client};
+    use config::FileFormat;
+
+    /// Load a definition shipped in `scrapers/` and point it at a local server
+    fn shipped_scraper(name: &str, base_url: &str) -> DeclarativeScraper {
+        let mut definition = load_definitions(concat!(env!("CARGO_MANIFEST_DIR"), "/scrapers"))
+            .unwrap()
+            .into_iter()
+            .find(|definition| definition.name == name)
+            .unwrap_or_else(|| panic!("No shipped definition named {}", name));
+
+        definition.base_url = Some(base_url.to_string());
+        DeclarativeScraper::new(&definition).unwrap()
+    }
+
+    fn parse_definition(toml: &str) -> ScraperDefinition {
+        ConfigCrate::builder()
+            .add_source(File::from_str(toml, FileFormat::Toml))
+            .build()
+            .and_then(|definition| definition.try_deserialize())
+            .unwrap()
+    }
+
+    #[test]
+    fn shipped_definitions_compile() {
+        let definitions =
+            load_definitions(concat!(env!("CARGO_MANIFEST_DIR"), "/scrapers")).unwrap();
+
+        let names: Vec<&str> = definitions.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, ["Cursor AI", "Replit", "Tabnine", "Warp"]);
+
+        for definition in &definitions {
+            DeclarativeScraper::new(definition).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn cursor_definition_extracts_student_and_promo_coupons() {
+        let base_url = serve_fixtures(&[
+            ("/student", "cursor_student.html"),
+            ("/pricing", "cursor_pricing.html"),
+        ])
+        .await;
+
+        let coupons = shipped_scraper("Cursor AI", &base_url)
+            .scrape(&test_client())
+            .await
+            .unwrap();
+
+        let summary: Vec<(&str, &str, Option<f64>)> = coupons
+            .iter()
+            .map(|c| (c.name.as_str(), c.code.as_str(), c.discount_percentage))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("Cursor AI Student Plan", "STUDENT", Some(100.0)),
+                ("Cursor AI Promotion: 25% Off", "SPRING25", Some(25.0)),
+                ("Cursor AI Promotion: 10% Off", "PROMO", Some(10.0)),
+            ]
+        );
+        assert_eq!(coupons[0].url, format!("{}/student", base_url));
+        assert!(coupons.iter().all(|c| c.source == "Cursor AI"));
+        assert!(coupons.iter().all(|c| c.expiry.is_some()));
+    }
+
+    #[tokio::test]
+    async fn cursor_definition_skips_missing_blocks_and_pages() {
+        let base_url = serve_fixtures(&[("/student", "student_program.html")]).await;
+
+        let coupons = shipped_scraper("Cursor AI", &base_url)
+            .scrape(&test_client())
+            .await
+            .unwrap();
+
+        assert!(coupons.is_empty());
+    }
+
+    #[tokio::test]
+    async fn replit_definition_extracts_education_coupon() {
+        let base_url =
+            serve_fixtures(&[("/site/teams-for-education", "replit_education.html")]).await;
+
+        let coupons = shipped_scraper("Replit", &base_url)
+            .scrape(&test_client())
+            .await
+            .unwrap();
+
+        assert_eq!(coupons.len(), 1);
+        assert_eq!(coupons[0].name, "Replit Teams for Education");
+        assert_eq!(coupons[0].code, "EDUCATION");
+        assert_eq!(coupons[0].discount_percentage, Some(50.0));
+        assert!(coupons[0].expiry.is_none());
+    }
+
+    #[tokio::test]
+    async fn student_program_definitions_yield_one_coupon_per_page() {
+        let base_url = serve_fixtures(&[("/students", "student_program.html")]).await;
+
+        for (name, code) in [("Warp", "AUTO-APPLIED"), ("Tabnine", "STUDENT")] {
+            let coupons = shipped_scraper(name, &base_url)
+                .scrape(&test_client())
+                .await
+                .unwrap();
+
+            assert_eq!(coupons.len(), 1, "{}", name);
+            assert_eq!(coupons[0].code, code);
+            assert_eq!(coupons[0].source, name);
+            assert_eq!(coupons[0].discount_percentage, Some(100.0));
+        }
+    }
+
+    #[tokio::test]
+    async fn fields_use_selectors_attributes_and_regexes() {
+        let base_url = serve_fixtures(&[("/deals", "deal_site.html")]).await;
+        let mut definition = parse_definition(
+            r#"
+            name = "Deal Site"
+            source = "Generic"
+
+            [[pages]]
+            url = "/deals"
+            item = "li.deal"
+            max_items = 2
+            name = { selector = "h3" }
+            description = { default = "{discount}% off with {code}" }
+            code = { selector = "[data-code]", attribute = "data-code", default = "NONE" }
+            discount = { selector = ".discount", regex = '(\d+(?:\.\d+)?)\s*%' }
+            expiry = { selector = ".expires", regex = '\d{4}-\d{2}-\d{2}' }
+            expires_in_days = 7
+            "#,
+        );
+        definition.base_url = Some(base_url);
+
+        let coupons = DeclarativeScraper::new(&definition)
+            .unwrap()
+            .scrape(&test_client())
+            .await
+            .unwrap();
+
+        assert_eq!(coupons.len(), 2);
+        assert_eq!(coupons[0].name, "Vector Database Pro");
+        assert_eq!(coupons[0].code, "NONE");
+        assert_eq!(coupons[0].description, "25% off with NONE");
+        assert_eq!(
+            coupons[0].expiry.map(|e| e.date_naive()),
+            chrono::NaiveDate::from_ymd_opt(2027, 1, 31)
+        );
+
+        assert_eq!(coupons[1].code, "PROMPT15");
+        assert_eq!(coupons[1].discount_percentage, Some(15.5));
+        // "March 1, 2027" doesn't match the regex, so the default lifetime applies
+        assert!(coupons[1].expiry.unwrap() < Utc::now() + Duration::days(8));
+    }
+
+    #[test]
+    fn invalid_selector_is_rejected() {
+        let definition = parse_definition(
+            r#"
+            name = "Broken"
+            source = "Generic"
+
+            [[pages]]
+            url = "https://example.com"
+            item = "div[["
+            name = { default = "Broken" }
+            code = { default = "CODE" }
+            "#,
+        );
+
+        let error = DeclarativeScraper::new(&definition).err().unwrap();
+        assert!(format!("{:#}", error).contains("div[["));
+    }
+}
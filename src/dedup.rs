@@ -0,0 +1,242 @@
+use reqwest::Url;
+use std::collections::HashSet;
+
+use crate::models::{canonical_url, Coupon};
+
+/// Words that appear in all kinds of deal titles and say nothing about the offer
+const FILLER_WORDS: &[&str] = &[
+    "a", "an", "and", "the", "for", "of", "on", "off", "with", "get", "code", "coupon", "promo",
+    "deal", "deals", "discount",
+];
+
+/// Distinctive words of a title: lowercased, without filler words and bare numbers
+fn title_words(title: &str) -> HashSet<String> {
+    title
+        .split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+        .filter(|word| !word.is_empty())
+        .filter(|word| !FILLER_WORDS.contains(&word.as_str()))
+        .filter(|word| !word.chars().all(|c| c.is_ascii_digit()))
+        .collect()
+}
+
+/// Sørensen–Dice similarity of the distinctive words of two titles,
+/// from 0 (no words in common) to 1 (the same words)
+pub fn title_similarity(a: &str, b: &str) -> f64 {
+    let a = title_words(a);
+    let b = title_words(b);
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+
+    2.0 * a.intersection(&b).count() as f64 / (a.len() + b.len()) as f64
+}
+
+/// Codes that scrapers fall back to when a page shows no real code. Offers of
+/// different vendors share them, so they say nothing about which offer a coupon is.
+const PLACEHOLDER_CODES: &[&str] = &["STUDENT", "PROMO", "EDUCATION", "AUTO-APPLIED"];
+
+/// Site a coupon links to: its registrable domain, e.g. `cursor.com` for
+/// `https://www.cursor.com/pricing`, and the vendor name in it, e.g. `cursor`.
+/// Hosts without a registrable domain, like IP addresses, are their own site.
+fn site(coupon: &Coupon) -> Option<(String, String)> {
+    let url = Url::parse(&canonical_url(&coupon.url)).ok()?;
+    let host = url.host_str()?.to_lowercase();
+    if url.domain().is_none() {
+        return Some((host.clone(), host));
+    }
+
+    let (Some(domain), Some(suffix)) = (psl::domain_str(&host), psl::suffix_str(&host)) else {
+        return Some((host.clone(), host));
+    };
+    let name = domain.strip_suffix(suffix).unwrap_or(domain).trim_end_matches('.');
+
+    Some((domain.to_string(), name.to_string()))
+}
+
+/// Checks if two coupons are for the same vendor: they link to the same site, or one
+/// links to the vendor's site and the other's title names it, as aggregator listings do
+fn same_vendor(a: &Coupon, b: &Coupon) -> bool {
+    let (Some((domain_a, name_a)), Some((domain_b, name_b))) = (site(a), site(b)) else {
+        return false;
+    };
+
+    domain_a == domain_b
+        || title_words(&b.name).contains(&name_a)
+        || title_words(&a.name).contains(&name_b)
+}
+
+/// Checks if `candidate` is the same offer as `coupon`, listed under another title or URL:
+/// the codes must match and not be a placeholder, the coupons must be for the same vendor,
+/// and their titles must be at least `threshold` similar
+pub fn is_near_duplicate(coupon: &Coupon, candidate: &Coupon, threshold: f64) -> bool {
+    let code = coupon.code.trim().to_uppercase();
+    if code != candidate.code.trim().to_uppercase() || PLACEHOLDER_CODES.contains(&code.as_str()) {
+        return false;
+    }
+
+    same_vendor(coupon, candidate) && title_similarity(&coupon.name, &candidate.name) >= threshold
+}
+
+/// Find the stored coupon a newly scraped one duplicates: the oldest near-duplicate
+/// among `candidates`, which should share its code
+pub fn find_canonical<'a>(
+    coupon: &Coupon,
+    candidates: &'a [Coupon],
+    threshold: f64,
+) -> Option<&'a Coupon> {
+    candidates
+        .iter()
+        .filter(|candidate| is_near_duplicate(coupon, candidate, threshold))
+        .min_by_key(|candidate| candidate.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::declarative::load_definitions;
+
+    /// Default of `scraping.duplicate_similarity`
+    const DEFAULT_THRESHOLD: f64 = 0.8;
+
+    fn coupon(name: &str, code: &str, url: &str, source: &str) -> Coupon {
+        Coupon::new(
+            name.to_string(),
+            String::new(),
+            None,
+            code.to_string(),
+            url.to_string(),
+            source.to_string(),
+            None,
+        )
+    }
+
+    #[test]
+    fn similarity_ignores_case_numbers_and_filler() {
+        assert_eq!(
+            title_similarity("Cursor Pro: 20% off", "cursor pro discount code"),
+            1.0
+        );
+        assert_eq!(title_similarity("Cursor Pro", "Tabnine Pro"), 0.5);
+        assert_eq!(title_similarity("50% off", "Cursor Pro"), 0.0);
+    }
+
+    #[test]
+    fn near_duplicates_share_code_vendor_and_title() {
+        let original = coupon(
+            "Cursor Pro for students",
+            "SAVE20",
+            "https://cursor.com/pricing",
+            "Cursor AI",
+        );
+
+        // Same site and title, different source and tracking parameters
+        let same_site = coupon(
+            "Cursor Pro students",
+            "save20",
+            "https://www.cursor.com/pricing?utm_source=x",
+            "Generic",
+        );
+        assert!(is_near_duplicate(&same_site, &original, 0.8));
+
+        // Same site, unrelated title
+        let other_offer = coupon("Spring sale", "SAVE20", "https://cursor.com/pricing", "Generic");
+        assert!(!is_near_duplicate(&other_offer, &original, 0.8));
+
+        // Aggregator page naming the vendor, similar title
+        let aggregator = coupon(
+            "LLM Deals: Cursor Pro students",
+            "SAVE20",
+            "https://llmdeals.net",
+            "Generic",
+        );
+        assert!(is_near_duplicate(&aggregator, &original, 0.8));
+
+        // Two offers on one aggregator sharing a code
+        let other_product = coupon(
+            "LLM Deals: Tabnine",
+            "SAVE20",
+            "https://llmdeals.net",
+            "Generic",
+        );
+        assert!(!is_near_duplicate(&other_product, &aggregator, 0.8));
+
+        // Similar title of another vendor
+        let other_vendor = coupon("Tabnine Pro for students", "SAVE20", "https://tabnine.com", "Tabnine");
+        assert!(!is_near_duplicate(&other_vendor, &original, 0.5));
+
+        // Same title, different code
+        let other_code = coupon(
+            "Cursor Pro for students",
+            "SAVE30",
+            "https://cursor.com/pricing",
+            "Cursor AI",
+        );
+        assert!(!is_near_duplicate(&other_code, &original, 0.8));
+    }
+
+    #[test]
+    fn sites_under_a_shared_suffix_are_different_vendors() {
+        let original = coupon("Spring sale", "SPRING", "https://foo.co.uk/sale", "Generic");
+        let other_site = coupon("Spring sale", "SPRING", "https://bar.co.uk/sale", "Generic");
+        assert!(!is_near_duplicate(&other_site, &original, 0.8));
+
+        let same_site = coupon("Spring sale", "SPRING", "https://shop.foo.co.uk", "Generic");
+        assert!(is_near_duplicate(&same_site, &original, 0.8));
+    }
+
+    #[test]
+    fn placeholder_codes_never_match() {
+        let original = coupon("Cursor Pro", "STUDENT", "https://cursor.com/student", "Cursor AI");
+        let same = coupon("Cursor Pro", "student", "https://cursor.com/student", "Generic");
+        assert!(!is_near_duplicate(&same, &original, 0.8));
+    }
+
+    #[test]
+    fn shipped_definitions_are_not_duplicates_of_each_other() {
+        let definitions = load_definitions(concat!(env!("CARGO_MANIFEST_DIR"), "/scrapers")).unwrap();
+        let mut coupons = Vec::new();
+        for definition in &definitions {
+            for page in &definition.pages {
+                let discount = page.discount.default.clone().unwrap_or_default();
+                let name = page.name.default.clone().unwrap().replace("{discount}", &discount);
+                let url = match &definition.base_url {
+                    Some(base_url) => format!("{}{}", base_url, page.url),
+                    None => page.url.clone(),
+                };
+                let code = page.code.default.clone().unwrap();
+                coupons.push((definition.name.as_str(), coupon(&name, &code, &url, &definition.name)));
+            }
+        }
+
+        for (name, scraped) in &coupons {
+            for (other_name, stored) in &coupons {
+                if name != other_name {
+                    assert!(
+                        !is_near_duplicate(scraped, stored, DEFAULT_THRESHOLD),
+                        "{} ({}) duplicates {} ({})",
+                        scraped.name,
+                        name,
+                        stored.name,
+                        other_name
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn canonical_is_oldest_match() {
+        let mut newer = coupon("Cursor Pro", "SAVE20", "https://cursor.com", "Cursor AI");
+        newer.id = Some(7);
+        let mut older = coupon("Cursor Pro", "SAVE20", "https://cursor.com/pricing", "Generic");
+        older.id = Some(3);
+        let mut unrelated = coupon("Replit Core", "SAVE20", "https://replit.com", "Replit");
+        unrelated.id = Some(1);
+        let candidates = vec![newer, older, unrelated];
+
+        let scraped = coupon("Cursor Pro 20% off", "SAVE20", "https://llmdeals.net", "Generic");
+        let canonical = find_canonical(&scraped, &candidates, 0.8).unwrap();
+        assert_eq!(canonical.id, Some(3));
+    }
+}
@@ -0,0 +1,494 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+use crate::db;
+use crate::discord::{delivery_key, DEFAULT_TARGET};
+use crate::models::{AppState, Config, CouponDigest, DigestConfig, DigestFrequency, DigestRun};
+use crate::notifier::{DigestDelivery, Notifier};
+
+/// Delay before retrying a digest that couldn't be posted
+const RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// When digests are posted: a local time of day, on a given weekday for weekly digests
+#[derive(Debug, Clone)]
+pub struct DigestSchedule {
+    time: NaiveTime,
+    timezone: Tz,
+    weekday: Weekday,
+}
+
+impl DigestSchedule {
+    /// Parse the `[digest]` settings
+    pub fn from_config(config: &DigestConfig) -> Result<Self> {
+        let time = NaiveTime::parse_from_str(&config.time, "%H:%M")
+            .with_context(|| format!("digest.time must be HH:MM, got '{}'", config.time))?;
+        let timezone = config.timezone.parse::<Tz>().map_err(|_| {
+            anyhow::anyhow!("digest.timezone must be an IANA timezone such as Europe/Berlin, got '{}'", config.timezone)
+        })?;
+        let weekday = config.weekday.parse::<Weekday>().map_err(|_| {
+            anyhow::anyhow!("digest.weekday must be a day such as mon or monday, got '{}'", config.weekday)
+        })?;
+
+        Ok(Self { time, timezone, weekday })
+    }
+
+    /// First time after `after` a digest of the given frequency is due
+    fn next_after(&self, frequency: DigestFrequency, after: DateTime<Utc>) -> DateTime<Utc> {
+        let today = after.with_timezone(&self.timezone).date_naive();
+
+        (0..=7)
+            .filter_map(|days| today.checked_add_days(chrono::Days::new(days)))
+            .filter(|date| frequency == DigestFrequency::Daily || date.weekday() == self.weekday)
+            // Skips the day if DST makes the time not exist
+            .filter_map(|date| self.timezone.from_local_datetime(&date.and_time(self.time)).earliest())
+            .map(|at| at.with_timezone(&Utc))
+            .find(|at| *at > after)
+            .unwrap_or(after + frequency.period())
+    }
+}
+
+/// The next digest of a frequency to a Discord target and the window it covers
+#[derive(Debug, Clone)]
+struct PendingDigest {
+    frequency: DigestFrequency,
+    target: String,
+    window_start: DateTime<Utc>,
+    window_end: DateTime<Utc>,
+    /// When to post it; later than `window_end` after a failed attempt
+    post_at: DateTime<Utc>,
+}
+
+impl PendingDigest {
+    /// Plan the first digest of a frequency to a target, continuing where the last one
+    /// recorded for it ended. Windows missed while the bot was down, or while the target
+    /// failed, are merged into a single digest.
+    async fn resume(
+        db_pool: &SqlitePool,
+        schedule: &DigestSchedule,
+        frequency: DigestFrequency,
+        target: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Self> {
+        let last_end = db::get_last_digest_run(db_pool, frequency, target)
+            .await?
+            .map(|run| run.window_end);
+
+        let (window_start, window_end) = match last_end {
+            Some(window_start) => {
+                let mut window_end = schedule.next_after(frequency, window_start);
+                while schedule.next_after(frequency, window_end) <= now {
+                    window_end = schedule.next_after(frequency, window_end);
+                }
+                (window_start, window_end)
+            }
+            None => {
+                let window_end = schedule.next_after(frequency, now);
+                (window_end - frequency.period(), window_end)
+            }
+        };
+
+        Ok(Self {
+            frequency,
+            target: target.to_string(),
+            window_start,
+            window_end,
+            post_at: window_end,
+        })
+    }
+
+    /// Move on to the window following this one
+    fn advance(&mut self, schedule: &DigestSchedule) {
+        self.window_start = self.window_end;
+        self.window_end = schedule.next_after(self.frequency, self.window_end);
+        self.post_at = self.window_end;
+    }
+}
+
+/// Compile the coupons the target accepted during a digest's window and post them,
+/// recording the run
+async fn post_digest(
+    db_pool: &SqlitePool,
+    notifier: &dyn Notifier,
+    pending: &PendingDigest,
+    expiring_within: chrono::Duration,
+    now: DateTime<Utc>,
+) -> Result<DigestRun> {
+    let coupons = db::get_digest_coupons(
+        db_pool,
+        &delivery_key(&pending.target),
+        pending.window_start,
+        pending.window_end,
+        now,
+    )
+    .await?;
+
+    let digest = CouponDigest {
+        frequency: pending.frequency,
+        target: pending.target.clone(),
+        window_start: pending.window_start,
+        window_end: pending.window_end,
+        coupons,
+        expiring_before: now + expiring_within,
+    };
+
+    // An empty window is recorded without posting, so the next one starts after it
+    let delivery = if digest.coupons.is_empty() {
+        DigestDelivery::default()
+    } else {
+        notifier.send_digest(&digest).await?
+    };
+    // Remember the posts so coupons that stop working are struck through in them
+    db::save_digest_messages(db_pool, &delivery.messages).await?;
+
+    let mut run = DigestRun {
+        id: None,
+        frequency: digest.frequency,
+        target: digest.target,
+        window_start: digest.window_start,
+        window_end: digest.window_end,
+        coupon_count: digest.coupons.len() as i64,
+        targets: delivery.targets as i64,
+        posted_at: now,
+    };
+    run.id = Some(db::insert_digest_run(db_pool, &run).await?);
+
+    Ok(run)
+}
+
+/// Discord targets that asked for digests, with their frequency: the default target
+/// and any `[[discord.targets]]` entry
+fn configured_digests(config: &Config) -> Vec<(String, DigestFrequency)> {
+    let default_target = config.discord.webhook_url.is_some() || config.discord.channel_id.is_some();
+    let default_digest = config
+        .discord
+        .digest
+        .filter(|_| default_target)
+        .map(|frequency| (DEFAULT_TARGET.to_string(), frequency));
+
+    default_digest
+        .into_iter()
+        .chain(
+            config
+                .discord
+                .targets
+                .iter()
+                .filter_map(|target| Some((target.name.clone(), target.digest?))),
+        )
+        .collect()
+}
+
+/// Start posting daily and weekly digests, if any notification target asked for them
+pub async fn start_digest_scheduler(
+    state: Arc<Mutex<AppState>>,
+    notifier: Arc<dyn Notifier>,
+    config: &Config,
+    shutdown: CancellationToken,
+) -> Result<Option<JoinHandle<()>>> {
+    let digests = configured_digests(config);
+    if digests.is_empty() {
+        return Ok(None);
+    }
+
+    let db_pool = state.lock().await.db_pool.clone();
+    let schedule = DigestSchedule::from_config(&config.digest)?;
+    let expiring_within = chrono::Duration::hours(config.digest.expiring_within as i64);
+
+    let mut pending = Vec::new();
+    for (target, frequency) in digests {
+        let digest = PendingDigest::resume(&db_pool, &schedule, frequency, &target, Utc::now()).await?;
+        info!(
+            "Next {} digest to {} at {}",
+            frequency.as_str(),
+            target,
+            digest.post_at.with_timezone(&schedule.timezone)
+        );
+        pending.push(digest);
+    }
+
+    let handle = tokio::spawn(async move {
+        while !shutdown.is_cancelled() {
+            let Some(next) = pending.iter_mut().min_by_key(|digest| digest.post_at) else {
+                break;
+            };
+
+            let wait = (next.post_at - Utc::now()).to_std().unwrap_or_default();
+            tokio::select! {
+                _ = sleep(wait) => {}
+                _ = shutdown.cancelled() => break,
+            }
+
+            let now = Utc::now();
+            match post_digest(&db_pool, notifier.as_ref(), next, expiring_within, now).await {
+                Ok(run) => {
+                    info!(
+                        "Posted {} digest of {} coupons to {}",
+                        run.frequency.as_str(),
+                        run.coupon_count,
+                        run.target
+                    );
+                    next.advance(&schedule);
+                }
+                Err(e) => {
+                    error!("Failed to post {} digest to {}: {:#}", next.frequency.as_str(), next.target, e);
+                    next.post_at = now + chrono::Duration::seconds(RETRY_DELAY.as_secs() as i64);
+                }
+            }
+        }
+    });
+
+    Ok(Some(handle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discord::SentMessage;
+    use crate::models::{Coupon, OutboxEntry};
+    use crate::notifier::{Delivery, DigestDelivery};
+    use crate::testing::memory_database;
+    use async_trait::async_trait;
+    use serenity::model::id::{ChannelId, MessageId};
+    use std::sync::Mutex as StdMutex;
+
+    /// Notifier that keeps the digests it is asked to post
+    #[derive(Default)]
+    struct DigestRecorder {
+        digests: StdMutex<Vec<CouponDigest>>,
+    }
+
+    #[async_trait]
+    impl Notifier for DigestRecorder {
+        fn name(&self) -> &str {
+            "recorder"
+        }
+
+        async fn send_coupon(&self, _coupon: &Coupon, _idempotency_key: &str) -> Result<Delivery> {
+            Ok(Delivery::default())
+        }
+
+        async fn send_dead_coupon(&self, _coupon: &Coupon, _reason: Option<&str>) -> Result<()> {
+            Ok(())
+        }
+
+        async fn send_digest(&self, digest: &CouponDigest) -> Result<DigestDelivery> {
+            if digest.target == "broken" {
+                anyhow::bail!("Target is down");
+            }
+            self.digests.lock().unwrap().push(digest.clone());
+            let messages = digest.coupons.iter()
+                .enumerate()
+                .filter_map(|(field_index, coupon)| {
+                    Some((coupon.id?, SentMessage {
+                        target: digest.target.clone(),
+                        message_id: MessageId(10),
+                        channel_id: ChannelId(5),
+                        via_webhook: true,
+                        embed_index: 1,
+                        embed_count: 2,
+                        field_index: Some(field_index),
+                    }))
+                })
+                .collect();
+            Ok(DigestDelivery {
+                targets: 1,
+                messages,
+            })
+        }
+    }
+
+    fn schedule(time: &str, timezone: &str, weekday: &str) -> DigestSchedule {
+        DigestSchedule::from_config(&DigestConfig {
+            time: time.to_string(),
+            timezone: timezone.to_string(),
+            weekday: weekday.to_string(),
+            expiring_within: 48,
+        })
+        .unwrap()
+    }
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn next_digest_is_at_the_local_time() {
+        // 09:00 in Berlin is 07:00 UTC in summer and 08:00 UTC in winter
+        let berlin = schedule("09:00", "Europe/Berlin", "mon");
+        assert_eq!(
+            berlin.next_after(DigestFrequency::Daily, utc("2026-07-01T06:00:00Z")),
+            utc("2026-07-01T07:00:00Z")
+        );
+        assert_eq!(
+            berlin.next_after(DigestFrequency::Daily, utc("2026-07-01T07:00:00Z")),
+            utc("2026-07-02T07:00:00Z")
+        );
+        assert_eq!(
+            berlin.next_after(DigestFrequency::Daily, utc("2026-12-01T10:00:00Z")),
+            utc("2026-12-02T08:00:00Z")
+        );
+
+        // 2026-10-16 is a Friday
+        assert_eq!(
+            berlin.next_after(DigestFrequency::Weekly, utc("2026-10-16T12:00:00Z")),
+            utc("2026-10-19T07:00:00Z")
+        );
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        let config = |time: &str, timezone: &str, weekday: &str| DigestConfig {
+            time: time.to_string(),
+            timezone: timezone.to_string(),
+            weekday: weekday.to_string(),
+            expiring_within: 48,
+        };
+
+        assert!(DigestSchedule::from_config(&config("25:00", "UTC", "mon")).is_err());
+        assert!(DigestSchedule::from_config(&config("09:00", "Mars/Olympus", "mon")).is_err());
+        assert!(DigestSchedule::from_config(&config("09:00", "UTC", "someday")).is_err());
+        assert!(DigestSchedule::from_config(&config("18:30", "America/New_York", "friday")).is_ok());
+    }
+
+    /// Insert a coupon seen long ago, validate it now and let `targets` accept it
+    async fn accept_coupon(pool: &SqlitePool, targets: &[&str]) {
+        let mut coupon = Coupon::new(
+            "Cursor Pro".to_string(),
+            String::new(),
+            Some(20.0),
+            "SAVE20".to_string(),
+            "https://cursor.com/pricing".to_string(),
+            "Cursor AI".to_string(),
+            None,
+        );
+        coupon.first_seen = Some(Utc::now() - chrono::Duration::days(30));
+        let coupon_id = db::insert_coupon(pool, &coupon).await.unwrap();
+        db::mark_valid_and_enqueue_post(pool, coupon_id, &OutboxEntry::post_key(&coupon), Utc::now())
+            .await
+            .unwrap();
+
+        let entry = db::get_due_outbox_entries(pool, Utc::now(), 10).await.unwrap().remove(0);
+        let targets: Vec<String> = targets.iter().map(|target| delivery_key(target)).collect();
+        db::complete_outbox_entry(pool, &entry, &targets, &[]).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn digests_cover_consecutive_windows() {
+        let pool = memory_database().await;
+        let notifier = DigestRecorder::default();
+        let schedule = schedule("09:00", "UTC", "mon");
+        let expiring_within = chrono::Duration::hours(48);
+
+        let now = utc("2026-10-16T12:00:00Z");
+        let mut pending = PendingDigest::resume(&pool, &schedule, DigestFrequency::Daily, "default", now)
+            .await
+            .unwrap();
+        assert_eq!(pending.window_start, utc("2026-10-16T09:00:00Z"));
+        assert_eq!(pending.window_end, utc("2026-10-17T09:00:00Z"));
+
+        let run = post_digest(&pool, &notifier, &pending, expiring_within, pending.window_end)
+            .await
+            .unwrap();
+        assert_eq!(run.coupon_count, 0);
+        assert_eq!(run.target, "default");
+
+        // The next window starts where this one ended
+        pending.advance(&schedule);
+        assert_eq!(pending.window_start, utc("2026-10-17T09:00:00Z"));
+        post_digest(&pool, &notifier, &pending, expiring_within, pending.window_end)
+            .await
+            .unwrap();
+        assert!(notifier.digests.lock().unwrap().is_empty());
+
+        // After three days offline, the missed windows are merged into one
+        let later = utc("2026-10-21T12:00:00Z");
+        let resumed = PendingDigest::resume(&pool, &schedule, DigestFrequency::Daily, "default", later)
+            .await
+            .unwrap();
+        assert_eq!(resumed.window_start, utc("2026-10-18T09:00:00Z"));
+        assert_eq!(resumed.window_end, utc("2026-10-21T09:00:00Z"));
+        assert!(resumed.post_at < later);
+    }
+
+    #[tokio::test]
+    async fn digests_list_the_coupons_their_target_accepted() {
+        let pool = memory_database().await;
+        let notifier = DigestRecorder::default();
+        let schedule = schedule("09:00", "UTC", "mon");
+        let expiring_within = chrono::Duration::hours(48);
+
+        // First seen a month ago, but only valid since now
+        accept_coupon(&pool, &["default"]).await;
+
+        let now = Utc::now();
+        let pending = PendingDigest::resume(&pool, &schedule, DigestFrequency::Daily, "default", now)
+            .await
+            .unwrap();
+        let run = post_digest(&pool, &notifier, &pending, expiring_within, pending.window_end)
+            .await
+            .unwrap();
+        assert_eq!(run.coupon_count, 1);
+        assert_eq!(run.targets, 1);
+        let coupon = notifier.digests.lock().unwrap()[0].coupons[0].clone();
+        assert_eq!(coupon.code, "SAVE20");
+
+        // The digest is remembered, so the coupon is struck through if it stops working
+        let posts = db::get_discord_messages(&pool, coupon.id.unwrap()).await.unwrap();
+        assert_eq!(posts.len(), 1);
+        assert_eq!((posts[0].embed_index, posts[0].field_index), (1, Some(0)));
+
+        // Another target's digest doesn't list it
+        let other = PendingDigest::resume(&pool, &schedule, DigestFrequency::Daily, "deals", now)
+            .await
+            .unwrap();
+        let run = post_digest(&pool, &notifier, &other, expiring_within, other.window_end)
+            .await
+            .unwrap();
+        assert_eq!(run.coupon_count, 0);
+    }
+
+    #[tokio::test]
+    async fn failed_targets_keep_their_window() {
+        let pool = memory_database().await;
+        let notifier = DigestRecorder::default();
+        let schedule = schedule("09:00", "UTC", "mon");
+        let expiring_within = chrono::Duration::hours(48);
+        accept_coupon(&pool, &["default", "broken"]).await;
+
+        // Both targets posted yesterday's empty window, then today's fails for one of them
+        let now = Utc::now();
+        let yesterday = now - chrono::Duration::days(1);
+        let mut runs = Vec::new();
+        for target in ["default", "broken"] {
+            let mut pending = PendingDigest::resume(&pool, &schedule, DigestFrequency::Daily, target, yesterday)
+                .await
+                .unwrap();
+            post_digest(&pool, &notifier, &pending, expiring_within, pending.window_end)
+                .await
+                .unwrap();
+            pending.advance(&schedule);
+            runs.push(post_digest(&pool, &notifier, &pending, expiring_within, pending.window_end).await);
+        }
+        assert!(runs[0].is_ok());
+        assert!(runs[1].is_err());
+
+        // The target that failed posts the same window again, the other one moves on
+        let later = now + chrono::Duration::days(1);
+        let retried = PendingDigest::resume(&pool, &schedule, DigestFrequency::Daily, "broken", later)
+            .await
+            .unwrap();
+        let resumed = PendingDigest::resume(&pool, &schedule, DigestFrequency::Daily, "default", later)
+            .await
+            .unwrap();
+        assert!(retried.window_start <= now);
+        assert!(resumed.window_start > now);
+    }
+}
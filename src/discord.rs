@@ -2,61 +2,182 @@ use anyhow::{Context as AnyhowContext, Result};
 use chrono::Utc;
 use serenity::{
     async_trait,
-    builder::{CreateEmbed, CreateMessage},
-    http::Http,
+    builder::CreateEmbed,
+    http::{ratelimiting::RatelimitInfo, Http},
+    json::{self, Value},
     model::{
-        channel::Message,
+        application::{
+            command::Command,
+            interaction::{Interaction, InteractionResponseType},
+        },
         gateway::Ready,
-        id::ChannelId,
+        channel::Embed,
+        id::{ChannelId, GuildId, MessageId},
         webhook::Webhook,
     },
     prelude::*,
 };
-use std::env;
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 
+use crate::commands;
 use crate::config;
-use crate::models::{Config, Coupon};
+use crate::metrics;
+use crate::models::{
+    AppState, Config, Coupon, CouponDigest, DigestFrequency, DiscordAuth, DiscordMessage, InvalidPostAction,
+    NotificationFilter,
+};
+use crate::notifier::{Announcement, Delivery, DigestDelivery, Notifier};
+
+/// Name of the target built from `discord.webhook_url` and `discord.channel_id`
+pub const DEFAULT_TARGET: &str = "default";
+
+/// Most embeds Discord accepts in a single message
+pub const MAX_EMBEDS_PER_MESSAGE: usize = 10;
+
+/// Most fields Discord accepts in a single embed
+const MAX_EMBED_FIELDS: usize = 25;
+
+/// Most characters Discord accepts across all embeds of a message
+const MAX_MESSAGE_EMBED_CHARS: usize = 6000;
+
+/// Most coupons highlighted as expiring soon in a scheduled digest
+const MAX_EXPIRING_LISTED: usize = 10;
+
+/// Key of a Discord target in the deliveries of an announcement
+pub fn delivery_key(target: &str) -> String {
+    format!("discord:{}", target)
+}
+
+/// A webhook or channel that coupons are routed to
+#[derive(Debug, Clone)]
+struct DiscordTarget {
+    name: String,
+    webhook_url: Option<String>,
+    channel_id: Option<ChannelId>,
+    /// Frequency of the digests the target gets instead of a post per coupon
+    digest: Option<DigestFrequency>,
+    filter: NotificationFilter,
+}
 
 /// Discord client wrapper that supports both bot token and webhook
 pub struct DiscordClient {
     token_client: Option<Arc<Http>>,
-    webhook_url: Option<String>,
-    channel_id: Option<ChannelId>,
+    /// Client for webhook requests, shared so its rate limit buckets carry over between messages
+    webhook_http: Arc<Http>,
+    /// Webhooks resolved from their URL, by URL
+    webhooks: Mutex<HashMap<String, Webhook>>,
+    targets: Vec<DiscordTarget>,
     config: Arc<Config>,
 }
 
 impl DiscordClient {
-    /// Create a new Discord client
-    pub fn new(token_client: Option<Arc<Http>>, webhook_url: Option<String>, channel_id: Option<String>, config: Arc<Config>) -> Self {
-        let channel_id = channel_id.and_then(|id| id.parse::<u64>().ok()).map(ChannelId);
+    /// Create a new Discord client posting to the default target, if configured,
+    /// and to every target in `discord.targets`
+    pub fn new(token_client: Option<Arc<Http>>, config: Arc<Config>) -> Self {
+        Self::with_webhook_http(token_client, create_http(""), config)
+    }
+    
+    /// Create a new Discord client sending webhook requests through the given client
+    fn with_webhook_http(token_client: Option<Arc<Http>>, webhook_http: Http, config: Arc<Config>) -> Self {
+        let mut targets = Vec::new();
+        
+        let default_channel = config.discord.channel_id.as_ref()
+            .and_then(|id| id.parse::<u64>().ok())
+            .map(ChannelId);
+        if config.discord.webhook_url.is_some() || default_channel.is_some() {
+            targets.push(DiscordTarget {
+                name: DEFAULT_TARGET.to_string(),
+                webhook_url: config.discord.webhook_url.clone(),
+                channel_id: default_channel,
+                digest: config.discord.digest,
+                filter: NotificationFilter::default(),
+            });
+        }
+        
+        for target in &config.discord.targets {
+            targets.push(DiscordTarget {
+                name: target.name.clone(),
+                webhook_url: target.webhook_url.clone(),
+                channel_id: target.channel_id.as_ref()
+                    .and_then(|id| id.parse::<u64>().ok())
+                    .map(ChannelId),
+                digest: target.digest,
+                filter: target.filter.clone(),
+            });
+        }
         
         Self {
             token_client,
-            webhook_url,
-            channel_id,
+            webhook_http: Arc::new(webhook_http),
+            webhooks: Mutex::new(HashMap::new()),
+            targets,
             config,
         }
     }
     
-    /// Send a coupon notification to Discord
-    pub async fn send_coupon_notification(&self, coupon: &Coupon) -> Result<()> {
-        info!("Sending coupon notification to Discord: {}", coupon.name);
+    /// Resolve a webhook from its URL, looking it up only the first time it is used
+    async fn webhook(&self, webhook_url: &str) -> Result<Webhook> {
+        let mut webhooks = self.webhooks.lock().await;
+        if let Some(webhook) = webhooks.get(webhook_url) {
+            return Ok(webhook.clone());
+        }
+        
+        let webhook = Webhook::from_url(&self.webhook_http, webhook_url).await?;
+        webhooks.insert(webhook_url.to_string(), webhook.clone());
+        Ok(webhook)
+    }
+    
+    /// Targets that get a post per coupon rather than digests
+    fn immediate_targets(&self) -> impl Iterator<Item = &DiscordTarget> {
+        self.targets.iter().filter(|target| target.digest.is_none())
+    }
+    
+    /// Send an embed to every target whose filter matches the coupon.
+    /// Fails only if every matching target failed.
+    async fn send_to_matching_targets(&self, coupon: &Coupon, content: &str, embed: CreateEmbed) -> Result<Vec<SentMessage>> {
+        let mut sent = Vec::new();
+        let mut last_error = None;
         
-        let embed = self.create_coupon_embed(coupon);
+        for target in self.immediate_targets().filter(|target| target.filter.matches(coupon)) {
+            match self.send_embeds(target, content, vec![embed.clone()]).await {
+                Ok(message) => sent.push(message),
+                Err(e) => {
+                    error!("Failed to send to notification target {}: {}", target.name, e);
+                    metrics::discord_send_failed(&target.name);
+                    last_error = Some(e);
+                }
+            }
+        }
         
+        match last_error {
+            Some(e) if sent.is_empty() => Err(e),
+            _ => {
+                if sent.is_empty() {
+                    debug!("No notification target matches coupon: {}", coupon.name);
+                }
+                Ok(sent)
+            }
+        }
+    }
+    
+    /// Send a message with up to ten embeds to a target via its webhook, falling back to the bot token
+    async fn send_embeds(&self, target: &DiscordTarget, content: &str, embeds: Vec<CreateEmbed>) -> Result<SentMessage> {
         // Try webhook first if available
-        if let Some(webhook_url) = &self.webhook_url {
-            debug!("Using webhook to send notification");
-            return self.send_webhook_message(webhook_url, &coupon.name, embed).await;
+        if let Some(webhook_url) = &target.webhook_url {
+            debug!("Using webhook to send notification to {}", target.name);
+            return self.send_webhook_message(target, webhook_url, content, embeds).await;
         }
         
         // Fall back to bot token if available
         if let Some(client) = &self.token_client {
-            if let Some(channel_id) = self.channel_id {
+            if let Some(channel_id) = target.channel_id {
                 debug!("Using bot token to send notification to channel {}", channel_id);
-                return self.send_channel_message(client, channel_id, &coupon.name, embed).await;
+                return self.send_channel_message(target, client, channel_id, content, embeds).await;
             } else {
                 return Err(anyhow::anyhow!("Channel ID not set for bot token client"));
             }
@@ -66,122 +187,522 @@ impl DiscordClient {
         Err(anyhow::anyhow!("No Discord notification method available (neither webhook nor bot token)"))
     }
     
-    /// Create a rich embed for a coupon
-    fn create_coupon_embed(&self, coupon: &Coupon) -> CreateEmbed {
-        let mut embed = CreateEmbed::default();
-        
-        // Set the title and URL
-        embed.title(format!("✅ {} AI Coupon", coupon.name));
-        embed.url(&coupon.url);
+    /// Send a message via webhook
+    async fn send_webhook_message(&self, target: &DiscordTarget, webhook_url: &str, content: &str, embeds: Vec<CreateEmbed>) -> Result<SentMessage> {
+        let webhook = self.webhook(webhook_url).await?;
+        let embed_count = embeds.len();
         
-        // Set the description
-        embed.description(&coupon.description);
+        // Wait for the message so its id can be stored
+        let message = webhook.execute(&self.webhook_http, true, |w| {
+            w.content(content)
+                .username("RinKokonoe Coupon Bot")
+                .embeds(embeds.into_iter().map(embed_to_value).collect())
+        }).await?
+            .context("Webhook did not return the sent message")?;
         
-        // Add fields for discount, code, etc.
-        if let Some(discount) = coupon.discount_percentage {
-            embed.field("Discount", format!("{}%", discount), true);
-        }
+        Ok(SentMessage {
+            target: target.name.clone(),
+            message_id: message.id,
+            channel_id: message.channel_id,
+            via_webhook: true,
+            embed_index: 0,
+            embed_count,
+            field_index: None,
+        })
+    }
+    
+    /// Send a message to a channel using bot token
+    async fn send_channel_message(&self, target: &DiscordTarget, http: &Http, channel_id: ChannelId, content: &str, embeds: Vec<CreateEmbed>) -> Result<SentMessage> {
+        let embed_count = embeds.len();
+        let message = channel_id.send_message(http, |m| {
+            m.content(content)
+                .set_embeds(embeds)
+        }).await?;
         
-        embed.field("Code", &coupon.code, true);
-        embed.field("Source", &coupon.source, true);
+        Ok(SentMessage {
+            target: target.name.clone(),
+            message_id: message.id,
+            channel_id: message.channel_id,
+            via_webhook: false,
+            embed_index: 0,
+            embed_count,
+            field_index: None,
+        })
+    }
+    
+    /// Post the coupons matching a target, grouped into as few messages as allowed:
+    /// digests if there are more than `discord.digest_threshold`, otherwise messages of
+    /// up to `discord.batch_size` embeds. Returns the outcome of each message with the
+    /// positions in `coupons` of the coupons it carries and the message of each of them.
+    async fn send_batched(&self, target: &DiscordTarget, coupons: &[&Coupon]) -> Vec<(Vec<usize>, Result<Vec<SentMessage>>)> {
+        let mut results = Vec::new();
         
-        // Add expiry if available
-        if let Some(expiry) = coupon.expiry {
-            let now = Utc::now();
-            let days_left = (expiry - now).num_days();
-            
-            if days_left > 0 {
-                embed.field("Expires", format!("In {} days", days_left), true);
-            } else {
-                embed.field("Expires", "Today", true);
+        let digest_threshold = self.config.discord.digest_threshold as usize;
+        if digest_threshold > 0 && coupons.len() > digest_threshold {
+            info!("Sending a digest of {} coupons to {}", coupons.len(), target.name);
+            let content = format!("{} new coupons", coupons.len());
+            for (positions, embed) in create_digest_embeds(coupons) {
+                let result = self.send_embeds(target, &content, vec![embed]).await
+                    .map(|message| (0..positions.len())
+                        .map(|field_index| SentMessage {
+                            field_index: Some(field_index),
+                            ..message.clone()
+                        })
+                        .collect());
+                results.push((positions, result));
             }
+            
+            return results;
         }
         
-        // Set the color and timestamp
-        embed.color(0x00_c8_ff); // Light blue color
-        embed.timestamp(Utc::now());
+        let batch_size = (self.config.discord.batch_size as usize).clamp(1, MAX_EMBEDS_PER_MESSAGE);
+        let embeds: Vec<CreateEmbed> = coupons.iter().map(|coupon| create_coupon_embed(coupon)).collect();
+        for batch in batch_embeds(&embeds, batch_size) {
+            let content = match batch.as_slice() {
+                [position] => coupons[*position].name.clone(),
+                _ => format!("{} new coupons", batch.len()),
+            };
+            let batch_embeds = batch.iter().map(|&position| embeds[position].clone()).collect();
+            let result = self.send_embeds(target, &content, batch_embeds).await
+                .map(|message| (0..batch.len())
+                    .map(|embed_index| SentMessage {
+                        embed_index,
+                        ..message.clone()
+                    })
+                    .collect());
+            results.push((batch, result));
+        }
         
-        // Set footer
-        embed.footer(|f| {
-            f.text("RinKokonoe Coupon Bot")
-        });
+        results
+    }
+    
+    /// Look up a configured target by name
+    fn target(&self, name: &str) -> Result<&DiscordTarget> {
+        self.targets.iter()
+            .find(|target| target.name == name)
+            .with_context(|| format!("Notification target {} is no longer configured", name))
+    }
+    
+    /// Fetch the embeds of a stored post
+    async fn post_embeds(&self, post: &DiscordMessage) -> Result<Vec<Embed>> {
+        let (message_id, channel_id) = parse_post_ids(post)?;
         
-        embed
+        let message = if post.via_webhook {
+            let webhook_url = self.target(&post.target)?.webhook_url.as_deref()
+                .context("Webhook URL not set, can't fetch webhook message")?;
+            let webhook = self.webhook(webhook_url).await?;
+            webhook.get_message(&self.webhook_http, message_id).await?
+        } else {
+            let client = self.token_client.as_ref()
+                .context("Bot token not set, can't fetch bot message")?;
+            channel_id.message(client, message_id).await?
+        };
+        
+        Ok(message.embeds)
     }
     
-    /// Send a message via webhook
-    async fn send_webhook_message(&self, webhook_url: &str, content: &str, embed: CreateEmbed) -> Result<()> {
-        let http = Http::new("");
-        let webhook = Webhook::from_url(&http, webhook_url).await?;
+    /// Replace the embeds of a stored post, and its content if given
+    async fn update_post(&self, post: &DiscordMessage, content: Option<&str>, embeds: Vec<CreateEmbed>) -> Result<()> {
+        let (message_id, channel_id) = parse_post_ids(post)?;
         
-        webhook.execute(&http, false, |w| {
-            w.content(content)
-                .username("RinKokonoe Coupon Bot")
-                .embeds(vec![embed])
-        }).await?;
+        if post.via_webhook {
+            let webhook_url = self.target(&post.target)?.webhook_url.as_deref()
+                .context("Webhook URL not set, can't edit webhook message")?;
+            let webhook = self.webhook(webhook_url).await?;
+            
+            webhook.edit_message(&self.webhook_http, message_id, |m| {
+                if let Some(content) = content {
+                    m.content(content);
+                }
+                m.embeds(embeds.into_iter().map(embed_to_value).collect())
+            }).await?;
+        } else {
+            let client = self.token_client.as_ref()
+                .context("Bot token not set, can't edit bot message")?;
+            
+            channel_id.edit_message(client, message_id, |m| {
+                if let Some(content) = content {
+                    m.content(content);
+                }
+                m.set_embeds(embeds)
+            }).await?;
+        }
         
         Ok(())
     }
     
-    /// Send a message to a channel using bot token
-    async fn send_channel_message(&self, http: &Http, channel_id: ChannelId, content: &str, embed: CreateEmbed) -> Result<()> {
-        channel_id.send_message(http, |m| {
-            m.content(content)
-                .embed(|e| {
-                    e.0 = embed.0;
-                    e
+    /// Replace the content and embed of a stored post. In a message announcing several
+    /// coupons only the coupon's own embed is replaced, keeping the shared content.
+    async fn edit_post(&self, post: &DiscordMessage, content: &str, embed: CreateEmbed) -> Result<()> {
+        if post.embed_count > 1 {
+            let embeds = replace_embed(self.post_embeds(post).await?, post.embed_index, embed)?;
+            self.update_post(post, None, embeds).await
+        } else {
+            self.update_post(post, Some(content), vec![embed]).await
+        }
+    }
+    
+    /// Replace the field listing a coupon in a stored digest post
+    async fn edit_digest_field(&self, post: &DiscordMessage, field: (String, String)) -> Result<()> {
+        let field_index = post.field_index.context("Post is not a digest")?;
+        let embeds = replace_field(self.post_embeds(post).await?, post.embed_index, field_index, field)?;
+        self.update_post(post, None, embeds).await
+    }
+    
+    /// Delete a stored post
+    async fn delete_post(&self, post: &DiscordMessage) -> Result<()> {
+        let (message_id, channel_id) = parse_post_ids(post)?;
+        
+        if post.via_webhook {
+            let webhook_url = self.target(&post.target)?.webhook_url.as_deref()
+                .context("Webhook URL not set, can't delete webhook message")?;
+            let webhook = self.webhook(webhook_url).await?;
+            
+            webhook.delete_message(&self.webhook_http, message_id).await?;
+        } else {
+            let client = self.token_client.as_ref()
+                .context("Bot token not set, can't delete bot message")?;
+            
+            channel_id.delete_message(client, message_id).await?;
+        }
+        
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for DiscordClient {
+    fn name(&self) -> &str {
+        "Discord"
+    }
+    
+    /// Post a coupon to every matching target
+    async fn send_coupon(&self, coupon: &Coupon, idempotency_key: &str) -> Result<Delivery> {
+        let announcement = Announcement {
+            coupon,
+            idempotency_key,
+            delivered: &[],
+        };
+        self.send_coupons(&[announcement]).await.remove(0)
+    }
+    
+    /// Post coupons to every matching target that didn't receive them yet, batching the
+    /// coupons each target receives. Each coupon's delivery lists the targets it reached
+    /// and the ones that failed. Digest targets accept matching coupons without a post;
+    /// their next digest lists the coupons they accepted.
+    async fn send_coupons(&self, announcements: &[Announcement<'_>]) -> Vec<Result<Delivery>> {
+        info!("Sending {} coupon notifications to Discord", announcements.len());
+        
+        let mut deliveries: Vec<Delivery> = announcements.iter().map(|_| Delivery::default()).collect();
+        
+        for target in self.targets.iter().filter(|target| target.digest.is_some()) {
+            let key = delivery_key(&target.name);
+            for (announcement, delivery) in announcements.iter().zip(&mut deliveries) {
+                if target.filter.matches(announcement.coupon) && !announcement.was_delivered_to(&key) {
+                    delivery.delivered.push(key.clone());
+                }
+            }
+        }
+        
+        for target in self.immediate_targets() {
+            let key = delivery_key(&target.name);
+            let (indices, coupons): (Vec<usize>, Vec<&Coupon>) = announcements.iter()
+                .enumerate()
+                .filter(|(_, announcement)| {
+                    target.filter.matches(announcement.coupon) && !announcement.was_delivered_to(&key)
                 })
-        }).await?;
+                .map(|(i, announcement)| (i, announcement.coupon))
+                .unzip();
+            if coupons.is_empty() {
+                continue;
+            }
+            
+            for (positions, result) in self.send_batched(target, &coupons).await {
+                match result {
+                    Ok(messages) => {
+                        for (&position, message) in positions.iter().zip(messages) {
+                            let delivery = &mut deliveries[indices[position]];
+                            delivery.delivered.push(key.clone());
+                            delivery.messages.push(message);
+                        }
+                    }
+                    Err(e) => {
+                        error!("Failed to send to notification target {}: {}", target.name, e);
+                        metrics::discord_send_failed(&target.name);
+                        let error = format!("{:#}", e);
+                        for &position in &positions {
+                            deliveries[indices[position]].failed.push((key.clone(), error.clone()));
+                        }
+                    }
+                }
+            }
+        }
+        
+        deliveries.into_iter().map(Ok).collect()
+    }
+    
+    /// Post a digest to its target, if the target gets digests of its frequency. Returns
+    /// the message with the field listing each coupon.
+    async fn send_digest(&self, digest: &CouponDigest) -> Result<DigestDelivery> {
+        let Some(target) = self.targets.iter()
+            .find(|target| target.name == digest.target && target.digest == Some(digest.frequency))
+        else {
+            return Ok(DigestDelivery::default());
+        };
+        
+        let coupons: Vec<&Coupon> = digest.coupons.iter()
+            .filter(|coupon| target.filter.matches(coupon))
+            .collect();
+        if coupons.is_empty() {
+            debug!("No coupons for the {} digest of {}", digest.frequency.as_str(), target.name);
+            return Ok(DigestDelivery::default());
+        }
         
+        info!("Sending {} digest of {} coupons to {}", digest.frequency.as_str(), coupons.len(), target.name);
+        let content = format!("{} new coupons", coupons.len());
+        let (listings, embeds): (Vec<Vec<usize>>, Vec<CreateEmbed>) =
+            create_scheduled_digest_embeds(digest, &coupons).into_iter().unzip();
+        let message = match self.send_embeds(target, &content, embeds).await {
+            Ok(message) => message,
+            Err(e) => {
+                metrics::discord_send_failed(&target.name);
+                return Err(e.context(format!("Failed to send digest to notification target {}", target.name)));
+            }
+        };
+        
+        let messages = listings.iter()
+            .enumerate()
+            .flat_map(|(embed_index, positions)| positions.iter()
+                .enumerate()
+                .map(move |(field_index, &position)| (embed_index, field_index, position)))
+            .filter_map(|(embed_index, field_index, position)| {
+                let coupon_id = coupons[position].id?;
+                Some((coupon_id, SentMessage {
+                    embed_index,
+                    field_index: Some(field_index),
+                    ..message.clone()
+                }))
+            })
+            .collect();
+        
+        Ok(DigestDelivery {
+            targets: 1,
+            messages,
+        })
+    }
+    
+    /// Send a follow-up saying a previously posted coupon no longer works
+    async fn send_dead_coupon(&self, coupon: &Coupon, reason: Option<&str>) -> Result<()> {
+        info!("Sending dead coupon notification to Discord: {}", coupon.name);
+        
+        let embed = create_dead_coupon_embed(coupon, reason);
+        self.send_to_matching_targets(coupon, &format!("Coupon no longer valid: {}", coupon.name), embed).await?;
         Ok(())
     }
+    
+    /// Apply `discord.invalid_post_action` to a post of a coupon that expired or stopped working.
+    /// Returns `true` if the post was deleted.
+    async fn retract_post(
+        &self,
+        post: &DiscordMessage,
+        coupon: &Coupon,
+        status: &str,
+        reason: Option<&str>,
+    ) -> Result<bool> {
+        match self.config.discord.invalid_post_action {
+            InvalidPostAction::Keep => Ok(false),
+            _ if post.field_index.is_some() => {
+                // Deleting a digest would take the other coupons it lists along
+                info!("Striking through {} in a Discord digest", coupon.name);
+                self.edit_digest_field(post, retracted_digest_field(coupon, status, reason)).await?;
+                Ok(false)
+            }
+            InvalidPostAction::Edit => {
+                info!("Marking Discord post of {} as {}", coupon.name, status.to_lowercase());
+                let embed = create_retracted_embed(coupon, status, reason);
+                self.edit_post(post, &format!("~~{}~~", coupon.name), embed).await?;
+                Ok(false)
+            }
+            InvalidPostAction::Delete if post.embed_count > 1 => {
+                // Deleting the message would take the other coupons in it along
+                info!("Striking through {} in a Discord post shared with other coupons", coupon.name);
+                let embed = create_retracted_embed(coupon, status, reason);
+                self.edit_post(post, &format!("~~{}~~", coupon.name), embed).await?;
+                Ok(false)
+            }
+            InvalidPostAction::Delete => {
+                info!("Deleting Discord post of {}", coupon.name);
+                self.delete_post(post).await?;
+                Ok(true)
+            }
+        }
+    }
+    
+    /// Restore the original embed of a post for a coupon that works again. Posts that
+    /// are still around were edited unless `discord.invalid_post_action` is `keep`.
+    async fn restore_post(&self, post: &DiscordMessage, coupon: &Coupon) -> Result<()> {
+        if self.config.discord.invalid_post_action == InvalidPostAction::Keep {
+            return Ok(());
+        }
+        
+        info!("Restoring Discord post of {}", coupon.name);
+        if post.field_index.is_some() {
+            return self.edit_digest_field(post, digest_field(coupon)).await;
+        }
+        self.edit_post(post, &coupon.name, create_coupon_embed(coupon)).await
+    }
+}
+
+/// A message sent by the Discord client
+#[derive(Debug, Clone)]
+pub struct SentMessage {
+    /// Name of the target the message was sent to
+    pub target: String,
+    pub message_id: MessageId,
+    pub channel_id: ChannelId,
+    pub via_webhook: bool,
+    /// Position of the coupon's embed in the message
+    pub embed_index: usize,
+    /// Number of embeds in the message
+    pub embed_count: usize,
+    /// Position of the coupon's field if the message is a digest listing it
+    pub field_index: Option<usize>,
+}
+
+/// Parse the ids of a stored post
+fn parse_post_ids(post: &DiscordMessage) -> Result<(MessageId, ChannelId)> {
+    let message_id = post.message_id.parse::<u64>()
+        .with_context(|| format!("Invalid Discord message id: {}", post.message_id))?;
+    let channel_id = post.channel_id.parse::<u64>()
+        .with_context(|| format!("Invalid Discord channel id: {}", post.channel_id))?;
+    
+    Ok((MessageId(message_id), ChannelId(channel_id)))
+}
+
+/// Convert an embed builder into the JSON value expected by webhook builders
+fn embed_to_value(embed: CreateEmbed) -> Value {
+    Value::from(json::hashmap_to_json_map(embed.0))
+}
+
+/// Replace the embed at `index` among the embeds of a message
+fn replace_embed(embeds: Vec<Embed>, index: i64, embed: CreateEmbed) -> Result<Vec<CreateEmbed>> {
+    let mut embeds: Vec<CreateEmbed> = embeds.into_iter().map(CreateEmbed::from).collect();
+    let slot = usize::try_from(index).ok()
+        .and_then(|index| embeds.get_mut(index))
+        .with_context(|| format!("Message has no embed at position {}", index))?;
+    *slot = embed;
+    
+    Ok(embeds)
+}
+
+/// Replace the field at `field_index` of the embed at `embed_index` among the embeds of a message
+fn replace_field(mut embeds: Vec<Embed>, embed_index: i64, field_index: i64, field: (String, String)) -> Result<Vec<CreateEmbed>> {
+    let slot = usize::try_from(embed_index).ok()
+        .and_then(|index| embeds.get_mut(index))
+        .with_context(|| format!("Message has no embed at position {}", embed_index))?;
+    let slot = usize::try_from(field_index).ok()
+        .and_then(|index| slot.fields.get_mut(index))
+        .with_context(|| format!("Embed has no field at position {}", field_index))?;
+    (slot.name, slot.value) = field;
+    
+    Ok(embeds.into_iter().map(CreateEmbed::from).collect())
+}
+
+/// Create a Discord HTTP client that records rate limits. The client waits out rate
+/// limits announced by Discord's headers and retries 429 responses after `Retry-After`
+/// on its own, so it has to be reused for that to work across messages.
+fn create_http(token: &str) -> Http {
+    let mut http = Http::new(token);
+    http.ratelimiter.set_ratelimit_callback(Box::new(|info: RatelimitInfo| {
+        debug!("Discord rate limit on {}, waiting {:?}", info.path, info.timeout);
+        metrics::discord_rate_limited(info.global);
+    }));
+    
+    http
 }
 
 /// Handler for Discord events
-struct Handler;
+struct Handler {
+    state: Arc<Mutex<AppState>>,
+    guild_id: Option<GuildId>,
+}
 
 #[async_trait]
 impl EventHandler for Handler {
-    async fn ready(&self, _: Context, ready: Ready) {
+    async fn ready(&self, ctx: Context, ready: Ready) {
         info!("Connected to Discord as {}", ready.user.name);
+        self.state.lock().await.discord_auth = DiscordAuth::Authenticated;
+        
+        // Guild commands show up immediately, global ones can take up to an hour
+        let result = match self.guild_id {
+            Some(guild_id) => guild_id
+                .set_application_commands(&ctx.http, commands::register_commands)
+                .await,
+            None => Command::set_global_application_commands(&ctx.http, commands::register_commands).await,
+        };
+        
+        match result {
+            Ok(registered) => info!("Registered {} slash commands", registered.len()),
+            Err(e) => error!("Failed to register slash commands: {}", e),
+        }
     }
     
-    async fn message(&self, ctx: Context, msg: Message) {
-        // Handle commands if needed in the future
-        if msg.content.starts_with('!') {
-            debug!("Received command: {}", msg.content);
-            // Command handling can be added here
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let Interaction::ApplicationCommand(command) = interaction else {
+            return;
+        };
+        
+        debug!("Received slash command: /{}", command.data.name);
+        let embed = match commands::handle_command(&self.state, &command).await {
+            Ok(embed) => embed,
+            Err(e) => {
+                error!("Slash command /{} failed: {}", command.data.name, e);
+                commands::error_embed("Something went wrong, please try again later")
+            }
+        };
+        
+        if let Err(e) = command
+            .create_interaction_response(&ctx.http, |response| {
+                response
+                    .kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|message| message.set_embed(embed))
+            })
+            .await
+        {
+            error!("Failed to respond to /{}: {}", command.data.name, e);
         }
     }
 }
 
-/// Initialize Discord client
-pub async fn initialize_discord(config: &Config) -> Result<DiscordClient> {
+/// Initialize Discord client, recording whether the bot token works in the app state
+pub async fn initialize_discord(config: &Config, state: &Arc<Mutex<AppState>>) -> Result<DiscordClient> {
     info!("Initializing Discord client");
     
     let token_result = config::get_discord_token();
-    let webhook_url = config.discord.webhook_url.clone();
-    let channel_id = config.discord.channel_id.clone();
+    let has_webhook = config.discord.webhook_url.is_some()
+        || config.discord.targets.iter().any(|target| target.webhook_url.is_some());
     
     let token_client = match token_result {
         Ok(token) => {
             info!("Using Discord bot token for authentication");
-            let http = Http::new(&token);
+            let http = create_http(&token);
             
             // Validate the token by fetching the current user
             match http.get_current_user().await {
                 Ok(user) => {
                     info!("Authenticated as Discord user: {}", user.name);
+                    state.lock().await.discord_auth = DiscordAuth::Authenticated;
                     Some(Arc::new(http))
                 }
                 Err(e) => {
                     warn!("Failed to validate Discord token: {}", e);
+                    state.lock().await.discord_auth = DiscordAuth::Failed;
                     None
                 }
             }
         }
         Err(e) => {
-            if webhook_url.is_some() {
+            if has_webhook {
                 info!("Discord token not found, using webhook URL instead");
                 None
             } else {
@@ -193,31 +714,355 @@ pub async fn initialize_discord(config: &Config) -> Result<DiscordClient> {
     // Create and return the client
     let client = DiscordClient::new(
         token_client,
-        webhook_url,
-        channel_id,
         Arc::new(config.clone()),
     );
     
+    if client.targets.is_empty() {
+        warn!("No Discord notification targets configured, coupons won't be posted");
+    } else {
+        info!("Posting coupons to {} notification targets", client.targets.len());
+    }
+    
     Ok(client)
 }
 
-/// Start a full Discord bot (optional for future expansion)
-pub async fn start_discord_bot(config: &Config) -> Result<Client> {
+/// Start the Discord gateway bot that answers slash commands, disconnecting cleanly
+/// once `shutdown` is cancelled
+pub async fn start_discord_bot(
+    state: Arc<Mutex<AppState>>,
+    config: &Config,
+    shutdown: CancellationToken,
+) -> Result<JoinHandle<()>> {
     let token = config::get_discord_token()?;
     
-    let intents = GatewayIntents::GUILD_MESSAGES
-        | GatewayIntents::MESSAGE_CONTENT
-        | GatewayIntents::DIRECT_MESSAGES;
+    let guild_id = match &config.discord.guild_id {
+        Some(id) => Some(GuildId(
+            id.parse::<u64>().context("discord.guild_id must be a numeric guild id")?,
+        )),
+        None => None,
+    };
+    
+    // Slash commands don't need any privileged intents
+    let intents = GatewayIntents::GUILDS;
     
-    let client = Client::builder(&token, intents)
-        .event_handler(Handler)
+    let mut client = Client::builder(&token, intents)
+        .event_handler(Handler { state: state.clone(), guild_id })
         .await
         .context("Error creating Discord client")?;
     
-    Ok(client)
+    let shard_manager = client.shard_manager.clone();
+    tokio::spawn(async move {
+        shutdown.cancelled().await;
+        shard_manager.lock().await.shutdown_all().await;
+    });
+    
+    let handle = tokio::spawn(async move {
+        if let Err(e) = client.start().await {
+            error!("Discord bot stopped: {}", e);
+            state.lock().await.discord_auth = DiscordAuth::Failed;
+        }
+    });
+    
+    Ok(handle)
+}
+
+/// Create a rich embed for a coupon
+pub fn create_coupon_embed(coupon: &Coupon) -> CreateEmbed {
+    let mut embed = CreateEmbed::default();
+    
+    // Set the title and URL
+    embed.title(format!("✅ {} AI Coupon", coupon.name));
+    embed.url(&coupon.url);
+    
+    // Set the description
+    embed.description(&coupon.description);
+    
+    // Add fields for discount, code, etc.
+    if let Some(discount) = coupon.discount_percentage {
+        embed.field("Discount", format!("{}%", discount), true);
+    }
+    
+    embed.field("Code", &coupon.code, true);
+    embed.field("Source", &coupon.source, true);
+    
+    // Add expiry if available
+    if let Some(expiry) = coupon.expiry {
+        let now = Utc::now();
+        let days_left = (expiry - now).num_days();
+        
+        if days_left > 0 {
+            embed.field("Expires", format!("In {} days", days_left), true);
+        } else {
+            embed.field("Expires", "Today", true);
+        }
+    }
+    
+    // Set the color and timestamp
+    embed.color(0x00_c8_ff); // Light blue color
+    embed.timestamp(Utc::now());
+    
+    // Set footer
+    embed.footer(|f| {
+        f.text("RinKokonoe Coupon Bot")
+    });
+    
+    embed
+}
+
+/// Name and value of the field listing a coupon in a digest
+fn digest_field(coupon: &Coupon) -> (String, String) {
+    let mut value = format!("Code: `{}` • {}", coupon.code, coupon.source);
+    if let Some(discount) = coupon.discount_percentage {
+        value = format!("{}% off • {}", discount, value);
+    }
+    
+    (coupon.name.clone(), format!("{}\n[Apply here]({})", value, coupon.url))
+}
+
+/// Name and value of the field listing a coupon in a digest once it expired or stopped working
+fn retracted_digest_field(coupon: &Coupon, status: &str, reason: Option<&str>) -> (String, String) {
+    let mut value = format!("~~Code: `{}`~~ • {}", coupon.code, status);
+    if let Some(reason) = reason {
+        value.push_str(&format!(": {}", reason));
+    }
+    
+    (format!("~~{}~~", coupon.name), value)
+}
+
+/// Create the embeds of a digest listing several coupons, best discount first. Each embed
+/// lists up to 25 coupons and fits in a message of its own, so every coupon is listed.
+/// Returns the positions in `coupons` of the coupons each embed lists, in field order.
+pub fn create_digest_embeds(coupons: &[&Coupon]) -> Vec<(Vec<usize>, CreateEmbed)> {
+    // Room for the part number appended to the title
+    const PART_NOTE: usize = 10;
+    
+    let mut order: Vec<usize> = (0..coupons.len()).collect();
+    order.sort_by(|&a, &b| best_discount_first(&coupons[a], &coupons[b]));
+    
+    let title = format!("✅ {} new AI coupons", coupons.len());
+    let footer = "RinKokonoe Coupon Bot";
+    let room = MAX_MESSAGE_EMBED_CHARS - title.chars().count() - PART_NOTE - footer.len();
+    
+    let mut parts: Vec<Vec<usize>> = Vec::new();
+    let mut used = 0;
+    for position in order {
+        let (name, value) = digest_field(coupons[position]);
+        let length = name.chars().count() + value.chars().count();
+        match parts.last_mut() {
+            Some(part) if part.len() < MAX_EMBED_FIELDS && used + length <= room => {
+                part.push(position);
+                used += length;
+            }
+            _ => {
+                parts.push(vec![position]);
+                used = length;
+            }
+        }
+    }
+    
+    let count = parts.len();
+    parts.into_iter()
+        .enumerate()
+        .map(|(part, positions)| {
+            let mut embed = CreateEmbed::default();
+            if count > 1 {
+                embed.title(format!("{} ({}/{})", title, part + 1, count));
+            } else {
+                embed.title(&title);
+            }
+            for &position in &positions {
+                let (name, value) = digest_field(coupons[position]);
+                embed.field(name, value, false);
+            }
+            embed.color(0x00_c8_ff);
+            embed.timestamp(Utc::now());
+            embed.footer(|f| f.text(footer));
+            
+            (positions, embed)
+        })
+        .collect()
+}
+
+/// Group embeds into messages of up to `batch_size` embeds that stay within the characters
+/// Discord accepts across a message's embeds. Returns the positions of each message's embeds.
+fn batch_embeds(embeds: &[CreateEmbed], batch_size: usize) -> Vec<Vec<usize>> {
+    let mut batches: Vec<Vec<usize>> = Vec::new();
+    let mut used = 0;
+    for (position, embed) in embeds.iter().enumerate() {
+        let length = embed_length(embed);
+        match batches.last_mut() {
+            Some(batch) if batch.len() < batch_size && used + length <= MAX_MESSAGE_EMBED_CHARS => {
+                batch.push(position);
+                used += length;
+            }
+            _ => {
+                batches.push(vec![position]);
+                used = length;
+            }
+        }
+    }
+    
+    batches
+}
+
+/// Characters of an embed that count towards Discord's limit for a message: title,
+/// description, field names and values, footer text and author name
+fn embed_length(embed: &CreateEmbed) -> usize {
+    let length = |value: Option<&Value>| value.and_then(Value::as_str).map_or(0, |text| text.chars().count());
+    let fields: usize = embed.0.get("fields")
+        .and_then(Value::as_array)
+        .map_or(0, |fields| fields.iter()
+            .map(|field| length(field.get("name")) + length(field.get("value")))
+            .sum());
+    
+    length(embed.0.get("title"))
+        + length(embed.0.get("description"))
+        + fields
+        + length(embed.0.get("footer").and_then(|footer| footer.get("text")))
+        + length(embed.0.get("author").and_then(|author| author.get("name")))
+}
+
+/// Create the embeds of a daily or weekly digest: an overview highlighting the coupons
+/// that expire soon, then an embed per source listing its coupons as fields, best discount
+/// first. Coupons that don't fit in a message are counted instead of listed. Returns the
+/// positions in `coupons` of the coupons each embed lists, in field order.
+pub fn create_scheduled_digest_embeds(digest: &CouponDigest, coupons: &[&Coupon]) -> Vec<(Vec<usize>, CreateEmbed)> {
+    let expiring_soon = |coupon: &Coupon| coupon.expiry.is_some_and(|expiry| expiry < digest.expiring_before);
+    
+    // Group the positions of the coupons by source; sources with the best discounts come first
+    let mut groups: Vec<(&str, Vec<usize>)> = Vec::new();
+    for (position, coupon) in coupons.iter().enumerate() {
+        match groups.iter_mut().find(|(source, _)| *source == coupon.source) {
+            Some((_, group)) => group.push(position),
+            None => groups.push((&coupon.source, vec![position])),
+        }
+    }
+    for (_, group) in &mut groups {
+        group.sort_by(|&a, &b| best_discount_first(&coupons[a], &coupons[b]));
+    }
+    groups.sort_by(|(a_source, a), (b_source, b)| {
+        best_discount_first(&coupons[a[0]], &coupons[b[0]]).then_with(|| a_source.cmp(b_source))
+    });
+    
+    let frequency = match digest.frequency {
+        DigestFrequency::Daily => "Daily",
+        DigestFrequency::Weekly => "Weekly",
+    };
+    let title = format!("📰 {} coupon digest", frequency);
+    let mut description = format!(
+        "{} new coupons from {} sources, <t:{}:f> to <t:{}:f>",
+        coupons.len(),
+        groups.len(),
+        digest.window_start.timestamp(),
+        digest.window_end.timestamp()
+    );
+    let max_sources = MAX_EMBEDS_PER_MESSAGE - 1;
+    if groups.len() > max_sources {
+        description.push_str(&format!(
+            "\nShowing the top {} sources. Use /coupons for the rest.",
+            max_sources
+        ));
+    }
+    
+    let mut expiring: Vec<&Coupon> = coupons.iter().copied().filter(|coupon| expiring_soon(coupon)).collect();
+    expiring.sort_by_key(|coupon| coupon.expiry);
+    let mut expiring_lines: Vec<String> = expiring.iter()
+        .take(MAX_EXPIRING_LISTED)
+        .map(|coupon| format!(
+            "**{}** `{}` expires <t:{}:R>",
+            coupon.name,
+            coupon.code,
+            coupon.expiry.map(|expiry| expiry.timestamp()).unwrap_or_default()
+        ))
+        .collect();
+    if expiring.len() > MAX_EXPIRING_LISTED {
+        expiring_lines.push(format!("…and {} more", expiring.len() - MAX_EXPIRING_LISTED));
+    }
+    let expiring_field = fit_lines(&expiring_lines, 1024);
+    
+    let mut overview = CreateEmbed::default();
+    overview.title(title);
+    overview.description(description);
+    if !expiring_field.is_empty() {
+        overview.field("⏰ Expiring soon", expiring_field, false);
+    }
+    overview.color(0x00_c8_ff);
+    overview.timestamp(digest.window_end);
+    overview.footer(|f| f.text("RinKokonoe Coupon Bot"));
+    
+    // What's left of the message's characters for the per-source embeds
+    let mut budget = MAX_MESSAGE_EMBED_CHARS.saturating_sub(embed_length(&overview));
+    
+    let mut embeds = vec![(Vec::new(), overview)];
+    for (source, group) in groups.iter().take(max_sources) {
+        let title = format!("{} ({})", source, group.len());
+        let mut room = budget.saturating_sub(title.chars().count());
+        
+        let mut listed: Vec<usize> = Vec::new();
+        for (i, &position) in group.iter().enumerate() {
+            let (name, value) = digest_field(coupons[position]);
+            let length = name.chars().count() + value.chars().count();
+            let reserved = if i + 1 < group.len() { OMITTED_NOTE } else { 0 };
+            if listed.len() == MAX_EMBED_FIELDS || length + reserved > room {
+                break;
+            }
+            listed.push(position);
+            room -= length;
+        }
+        if listed.is_empty() {
+            break;
+        }
+        
+        let mut embed = CreateEmbed::default();
+        embed.title(&title);
+        if listed.len() < group.len() {
+            embed.description(format!("…and {} more", group.len() - listed.len()));
+        }
+        for &position in &listed {
+            let (name, value) = digest_field(coupons[position]);
+            embed.field(name, value, false);
+        }
+        embed.color(0x00_c8_ff);
+        budget = budget.saturating_sub(embed_length(&embed));
+        
+        embeds.push((listed, embed));
+    }
+    
+    embeds
+}
+
+/// Room for a "…and N more" line
+const OMITTED_NOTE: usize = 20;
+
+/// Join as many lines as fit in `max` characters, counting the ones left out in a last line
+fn fit_lines(lines: &[String], max: usize) -> String {
+    let mut text = String::new();
+    let mut used = 0;
+    for (i, line) in lines.iter().enumerate() {
+        let length = line.chars().count() + 1;
+        let reserved = if i + 1 < lines.len() { OMITTED_NOTE } else { 0 };
+        if used + length + reserved > max {
+            if used + OMITTED_NOTE <= max && i > 0 {
+                text.push_str(&format!("…and {} more", lines.len() - i));
+            }
+            return text;
+        }
+        text.push_str(line);
+        text.push('\n');
+        used += length;
+    }
+    
+    text.trim_end().to_string()
+}
+
+/// Order coupons by discount, largest first; coupons without a discount come last
+fn best_discount_first(a: &&Coupon, b: &&Coupon) -> Ordering {
+    b.discount_percentage.unwrap_or(0.0)
+        .total_cmp(&a.discount_percentage.unwrap_or(0.0))
 }
 
 /// Format a coupon notification message
+#[allow(dead_code)]
 pub fn format_coupon_message(coupon: &Coupon) -> String {
     let mut message = format!("✅ **{}**\n", coupon.name);
     
@@ -231,3 +1076,474 @@ pub fn format_coupon_message(coupon: &Coupon) -> String {
     message
 }
 
+/// Create the embed that replaces a post once its coupon expired or stopped working
+pub fn create_retracted_embed(coupon: &Coupon, status: &str, reason: Option<&str>) -> CreateEmbed {
+    let mut embed = CreateEmbed::default();
+    
+    embed.title(format!("~~{} AI Coupon~~", coupon.name));
+    embed.url(&coupon.url);
+    embed.description(format!("~~{}~~", coupon.description));
+    
+    embed.field("Code", format!("~~{}~~", coupon.code), true);
+    embed.field("Source", &coupon.source, true);
+    embed.field("Status", status, true);
+    
+    if let Some(reason) = reason {
+        embed.field("Reason", reason, false);
+    }
+    
+    embed.color(0xff_44_44);
+    embed.timestamp(Utc::now());
+    embed.footer(|f| {
+        f.text("RinKokonoe Coupon Bot")
+    });
+    
+    embed
+}
+
+/// Create an embed announcing that a coupon stopped working
+pub fn create_dead_coupon_embed(coupon: &Coupon, reason: Option<&str>) -> CreateEmbed {
+    let mut embed = CreateEmbed::default();
+    
+    embed.title(format!("❌ {} no longer works", coupon.name));
+    embed.url(&coupon.url);
+    embed.description(format!(
+        "Code `{}` from {} failed re-validation and has been marked invalid.",
+        coupon.code, coupon.source
+    ));
+    
+    if let Some(reason) = reason {
+        embed.field("Reason", reason, false);
+    }
+    
+    embed.color(0xff_44_44);
+    embed.timestamp(Utc::now());
+    embed.footer(|f| {
+        f.text("RinKokonoe Coupon Bot")
+    });
+    
+    embed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::record_requests_with;
+    use axum::http::StatusCode;
+    use serenity::http::HttpBuilder;
+    
+    const WEBHOOK_URL: &str = "https://discord.com/api/webhooks/12345678901234567/\
+        aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    
+    const WEBHOOK: &str = r#"{"id": "12345678901234567", "type": 1, "token": "token", "channel_id": "5"}"#;
+    
+    /// A message as Discord returns it, listing two coupons in a digest embed
+    const DIGEST_MESSAGE: &str = r#"{
+        "id": "10", "channel_id": "5", "content": "2 new coupons",
+        "author": {"id": "1", "username": "Rin", "discriminator": "0000", "avatar": null},
+        "timestamp": "2026-10-16T00:00:00+00:00", "edited_timestamp": null, "tts": false,
+        "mention_everyone": false, "mentions": [], "mention_roles": [], "attachments": [],
+        "pinned": false, "type": 0,
+        "embeds": [{"title": "✅ 2 new AI coupons", "fields": [
+            {"name": "big", "value": "50% off • Code: `BIG-CODE`", "inline": false},
+            {"name": "small", "value": "10% off • Code: `SMALL-CODE`", "inline": false}
+        ]}]
+    }"#;
+    
+    /// A message as Discord returns it, announcing two coupons in separate embeds
+    const SHARED_MESSAGE: &str = r#"{
+        "id": "10", "channel_id": "5", "content": "2 new coupons",
+        "author": {"id": "1", "username": "Rin", "discriminator": "0000", "avatar": null},
+        "timestamp": "2026-10-16T00:00:00+00:00", "edited_timestamp": null, "tts": false,
+        "mention_everyone": false, "mentions": [], "mention_roles": [], "attachments": [],
+        "pinned": false, "type": 0,
+        "embeds": [{"title": "✅ big AI Coupon"}, {"title": "✅ small AI Coupon"}]
+    }"#;
+    
+    /// A client posting through a webhook to a mock Discord API
+    fn mock_client(base_url: &str, settings: &str) -> DiscordClient {
+        let toml = format!("[discord]\nwebhook_url = \"{}\"\n{}", WEBHOOK_URL, settings);
+        let http = HttpBuilder::new("")
+            .proxy(base_url)
+            .unwrap()
+            .ratelimiter_disabled(true)
+            .build();
+        DiscordClient::with_webhook_http(None, http, config::config_from_toml(&toml).unwrap())
+    }
+    
+    fn post(embed_index: i64, embed_count: i64) -> DiscordMessage {
+        DiscordMessage {
+            coupon_id: 1,
+            message_id: "10".to_string(),
+            target: DEFAULT_TARGET.to_string(),
+            channel_id: "5".to_string(),
+            via_webhook: true,
+            posted_at: Utc::now(),
+            embed_index,
+            embed_count,
+            field_index: None,
+        }
+    }
+    
+    fn digest_post(field_index: i64) -> DiscordMessage {
+        DiscordMessage {
+            field_index: Some(field_index),
+            ..post(0, 1)
+        }
+    }
+    
+    fn coupon(name: &str, discount: Option<f64>) -> Coupon {
+        Coupon::new(
+            name.to_string(),
+            String::new(),
+            discount,
+            format!("{}-CODE", name.to_uppercase()),
+            format!("https://example.com/{}", name),
+            "Test Source".to_string(),
+            None,
+        )
+    }
+    
+    #[test]
+    fn digest_lists_best_discounts_first() {
+        let coupons = [coupon("small", Some(10.0)), coupon("none", None), coupon("big", Some(50.0))];
+        let embeds = create_digest_embeds(&coupons.iter().collect::<Vec<_>>());
+        assert_eq!(embeds.len(), 1);
+        
+        let (positions, embed) = &embeds[0];
+        assert_eq!(positions, &[2, 0, 1]);
+        let fields = embed.0["fields"].as_array().unwrap();
+        let names: Vec<&str> = fields.iter().map(|field| field["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["big", "small", "none"]);
+        assert!(fields[0]["value"].as_str().unwrap().starts_with("50% off • Code: `BIG-CODE`"));
+    }
+    
+    #[test]
+    fn digest_is_split_at_the_embed_field_limit() {
+        let coupons: Vec<Coupon> = (0..30).map(|i| coupon(&format!("c{}", i), Some(i as f64))).collect();
+        let embeds = create_digest_embeds(&coupons.iter().collect::<Vec<_>>());
+        
+        assert_eq!(embeds.len(), 2);
+        assert_eq!(embeds[0].1.0["fields"].as_array().unwrap().len(), MAX_EMBED_FIELDS);
+        assert_eq!(embeds[0].1.0["fields"][0]["name"], "c29");
+        assert_eq!(embeds[0].1.0["title"], "✅ 30 new AI coupons (1/2)");
+        assert_eq!(embeds[1].0, [4, 3, 2, 1, 0]);
+    }
+    
+    #[test]
+    fn digest_embeds_fit_in_a_message() {
+        let coupons: Vec<Coupon> = (0..25)
+            .map(|i| {
+                let mut coupon = coupon(&format!("c{}", i), Some(i as f64));
+                coupon.name = format!("{} {}", coupon.name, "x".repeat(240));
+                coupon.url = format!("{}?{}", coupon.url, "y".repeat(200));
+                coupon
+            })
+            .collect();
+        let embeds = create_digest_embeds(&coupons.iter().collect::<Vec<_>>());
+        
+        assert!(embeds.len() > 1);
+        assert_eq!(embeds.iter().map(|(positions, _)| positions.len()).sum::<usize>(), 25);
+        for (_, embed) in &embeds {
+            assert!(embed_length(embed) <= MAX_MESSAGE_EMBED_CHARS);
+        }
+    }
+    
+    #[test]
+    fn batches_stay_within_the_message_limit() {
+        let embeds: Vec<CreateEmbed> = (0..6)
+            .map(|i| {
+                let mut coupon = coupon(&format!("c{}", i), None);
+                coupon.description = "z".repeat(2000);
+                create_coupon_embed(&coupon)
+            })
+            .collect();
+        
+        assert_eq!(batch_embeds(&embeds, 10), [vec![0, 1], vec![2, 3], vec![4, 5]]);
+        assert_eq!(batch_embeds(&embeds[..1], 10), [vec![0]]);
+        assert_eq!(batch_embeds(&embeds[..3], 1), [vec![0], vec![1], vec![2]]);
+    }
+    
+    #[test]
+    fn scheduled_digest_groups_coupons_by_source() {
+        let now = Utc::now();
+        let mut cheap = coupon("cheap", Some(10.0));
+        cheap.source = "Replit".to_string();
+        let mut ending = coupon("ending", Some(30.0));
+        ending.expiry = Some(now + chrono::Duration::hours(5));
+        let best = coupon("best", Some(50.0));
+        let coupons = vec![cheap, ending, best];
+        let digest = CouponDigest {
+            frequency: DigestFrequency::Weekly,
+            target: DEFAULT_TARGET.to_string(),
+            window_start: now - chrono::Duration::weeks(1),
+            window_end: now,
+            coupons: coupons.clone(),
+            expiring_before: now + chrono::Duration::hours(48),
+        };
+        
+        let embeds = create_scheduled_digest_embeds(&digest, &coupons.iter().collect::<Vec<_>>());
+        assert_eq!(embeds.len(), 3);
+        assert!(embeds[0].0.is_empty());
+        assert_eq!(embeds[0].1.0["title"], "📰 Weekly coupon digest");
+        let expiring = embeds[0].1.0["fields"][0]["value"].as_str().unwrap();
+        assert!(expiring.starts_with("**ending** `ENDING-CODE`"));
+        
+        // The source with the best discount comes first, its coupons best first
+        assert_eq!(embeds[1].0, [2, 1]);
+        assert_eq!(embeds[1].1.0["title"], "Test Source (2)");
+        let fields = embeds[1].1.0["fields"].as_array().unwrap();
+        assert_eq!(fields[0]["name"], "best");
+        assert!(fields[1]["value"].as_str().unwrap().starts_with("30% off • Code: `ENDING-CODE`"));
+        assert_eq!(embeds[2].0, [0]);
+        assert_eq!(embeds[2].1.0["title"], "Replit (1)");
+    }
+    
+    #[test]
+    fn maximal_scheduled_digest_fits_in_a_message() {
+        let now = Utc::now();
+        let coupons: Vec<Coupon> = (0..300)
+            .map(|i| {
+                let mut coupon = coupon(&format!("c{} {}", i, "x".repeat(200)), Some((i % 90) as f64));
+                coupon.source = format!("Source {} {}", i % 30, "s".repeat(200));
+                coupon.url = format!("{}?{}", coupon.url, "y".repeat(200));
+                coupon.expiry = Some(now + chrono::Duration::hours(1));
+                coupon
+            })
+            .collect();
+        let digest = CouponDigest {
+            frequency: DigestFrequency::Weekly,
+            target: DEFAULT_TARGET.to_string(),
+            window_start: now - chrono::Duration::weeks(1),
+            window_end: now,
+            coupons: coupons.clone(),
+            expiring_before: now + chrono::Duration::hours(48),
+        };
+        
+        let embeds = create_scheduled_digest_embeds(&digest, &coupons.iter().collect::<Vec<_>>());
+        assert!(embeds.len() <= MAX_EMBEDS_PER_MESSAGE);
+        assert!(embeds[0].1.0["fields"][0]["value"].as_str().unwrap().ends_with("more"));
+        assert!(embeds.iter().map(|(_, embed)| embed_length(embed)).sum::<usize>() <= MAX_MESSAGE_EMBED_CHARS);
+        for (positions, embed) in &embeds[1..] {
+            assert_eq!(embed.0["fields"].as_array().unwrap().len(), positions.len());
+            assert!(embed.0["description"].as_str().unwrap().ends_with("more"));
+        }
+    }
+    
+    #[test]
+    fn fit_lines_counts_what_is_left_out() {
+        let lines: Vec<String> = (0..100).map(|i| format!("coupon line number {:03}", i)).collect();
+        let text = fit_lines(&lines, 200);
+        
+        assert!(text.chars().count() <= 200);
+        assert!(text.starts_with("coupon line number 000\n"));
+        assert!(text.ends_with("more"));
+        assert_eq!(fit_lines(&lines[..2], 200), "coupon line number 000\ncoupon line number 001");
+    }
+    
+    #[tokio::test]
+    async fn digest_targets_accept_coupons_without_posting() {
+        let (base_url, requests) = record_requests_with(vec![]).await;
+        let client = mock_client(&base_url, "digest = \"daily\"");
+        let coupon = coupon("big", Some(50.0));
+        
+        let delivery = client.send_coupon(&coupon, "post").await.unwrap();
+        
+        // The coupon waits for the daily digest
+        assert!(requests.lock().unwrap().is_empty());
+        assert_eq!(delivery.delivered, ["discord:default"]);
+        assert!(delivery.messages.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn digest_posts_are_remembered_for_every_coupon() {
+        let (base_url, requests) =
+            record_requests_with(vec![(StatusCode::OK, WEBHOOK), (StatusCode::OK, DIGEST_MESSAGE)]).await;
+        let client = mock_client(&base_url, "digest_threshold = 1");
+        let coupons = [coupon("small", Some(10.0)), coupon("big", Some(50.0))];
+        let announcements: Vec<Announcement> = coupons.iter()
+            .map(|coupon| Announcement {
+                coupon,
+                idempotency_key: "post",
+                delivered: &[],
+            })
+            .collect();
+        
+        let deliveries: Vec<Delivery> = client.send_coupons(&announcements).await
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        
+        // Both coupons are listed in one message, best discount first
+        assert_eq!(requests.lock().unwrap().len(), 2);
+        assert_eq!(deliveries[0].messages[0].field_index, Some(1));
+        assert_eq!(deliveries[1].messages[0].field_index, Some(0));
+        assert_eq!(deliveries[0].messages[0].message_id, MessageId(10));
+        assert_eq!(deliveries[0].delivered, ["discord:default"]);
+    }
+    
+    #[tokio::test]
+    async fn scheduled_digest_posts_are_remembered_for_every_coupon() {
+        let (base_url, requests) =
+            record_requests_with(vec![(StatusCode::OK, WEBHOOK), (StatusCode::OK, DIGEST_MESSAGE)]).await;
+        let client = mock_client(&base_url, "digest = \"daily\"");
+        let mut small = coupon("small", Some(10.0));
+        small.id = Some(1);
+        let mut big = coupon("big", Some(50.0));
+        big.id = Some(2);
+        let now = Utc::now();
+        let digest = CouponDigest {
+            frequency: DigestFrequency::Daily,
+            target: DEFAULT_TARGET.to_string(),
+            window_start: now - chrono::Duration::days(1),
+            window_end: now,
+            coupons: vec![small, big],
+            expiring_before: now,
+        };
+        
+        let delivery = client.send_digest(&digest).await.unwrap();
+        
+        // Both coupons are fields of the source's embed, following the overview
+        assert_eq!(requests.lock().unwrap().len(), 2);
+        assert_eq!(delivery.targets, 1);
+        let listed: Vec<(i64, usize, Option<usize>)> = delivery.messages.iter()
+            .map(|(coupon_id, message)| (*coupon_id, message.embed_index, message.field_index))
+            .collect();
+        assert_eq!(listed, [(2, 1, Some(0)), (1, 1, Some(1))]);
+        assert_eq!(delivery.messages[0].1.message_id, MessageId(10));
+    }
+    
+    #[tokio::test]
+    async fn retracting_a_digest_coupon_strikes_through_its_field() {
+        let (base_url, requests) = record_requests_with(vec![
+            (StatusCode::OK, WEBHOOK),
+            (StatusCode::OK, DIGEST_MESSAGE),
+            (StatusCode::OK, DIGEST_MESSAGE),
+        ])
+        .await;
+        let client = mock_client(&base_url, "invalid_post_action = \"delete\"");
+        
+        let deleted = client.retract_post(&digest_post(1), &coupon("small", Some(10.0)), "Expired", None)
+            .await
+            .unwrap();
+        assert!(!deleted);
+        
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[2].method, "PATCH");
+        let body: Value = serde_json::from_str(&requests[2].body).unwrap();
+        let fields = &body["embeds"][0]["fields"];
+        assert_eq!(fields[0]["name"], "big");
+        assert_eq!(fields[1]["name"], "~~small~~");
+        assert_eq!(fields[1]["value"], "~~Code: `SMALL-CODE`~~ • Expired");
+    }
+    
+    #[tokio::test]
+    async fn restoring_a_digest_coupon_restores_its_field() {
+        let (base_url, requests) = record_requests_with(vec![
+            (StatusCode::OK, WEBHOOK),
+            (StatusCode::OK, DIGEST_MESSAGE),
+            (StatusCode::OK, DIGEST_MESSAGE),
+        ])
+        .await;
+        let client = mock_client(&base_url, "");
+        
+        client.restore_post(&digest_post(0), &coupon("big", Some(50.0))).await.unwrap();
+        
+        let requests = requests.lock().unwrap();
+        let body: Value = serde_json::from_str(&requests[2].body).unwrap();
+        assert_eq!(body["embeds"][0]["fields"][0]["name"], "big");
+        assert!(body["embeds"][0]["fields"][0]["value"].as_str().unwrap().contains("[Apply here]"));
+        assert!(body.get("content").is_none());
+    }
+    
+    #[tokio::test]
+    async fn retracting_a_post_edits_it_by_default() {
+        let (base_url, requests) =
+            record_requests_with(vec![(StatusCode::OK, WEBHOOK), (StatusCode::OK, SHARED_MESSAGE)]).await;
+        let client = mock_client(&base_url, "");
+        
+        let deleted = client.retract_post(&post(0, 1), &coupon("big", Some(50.0)), "Expired", None)
+            .await
+            .unwrap();
+        assert!(!deleted);
+        
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].method, "PATCH");
+        assert_eq!(requests[1].path, "/api/v10/webhooks/12345678901234567/token/messages/10");
+        let body: Value = serde_json::from_str(&requests[1].body).unwrap();
+        assert_eq!(body["content"], "~~big~~");
+        assert_eq!(body["embeds"][0]["title"], "~~big AI Coupon~~");
+    }
+    
+    #[tokio::test]
+    async fn retracting_a_post_deletes_it_when_configured() {
+        let (base_url, requests) =
+            record_requests_with(vec![(StatusCode::OK, WEBHOOK), (StatusCode::NO_CONTENT, "")]).await;
+        let client = mock_client(&base_url, "invalid_post_action = \"delete\"");
+        
+        let deleted = client.retract_post(&post(0, 1), &coupon("big", Some(50.0)), "Invalid", None)
+            .await
+            .unwrap();
+        assert!(deleted);
+        
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].method, "DELETE");
+        assert_eq!(requests[1].path, "/api/v10/webhooks/12345678901234567/token/messages/10");
+    }
+    
+    #[tokio::test]
+    async fn retracting_a_shared_post_only_edits_its_embed() {
+        let (base_url, requests) = record_requests_with(vec![
+            (StatusCode::OK, WEBHOOK),
+            (StatusCode::OK, SHARED_MESSAGE),
+            (StatusCode::OK, SHARED_MESSAGE),
+        ])
+        .await;
+        let client = mock_client(&base_url, "invalid_post_action = \"delete\"");
+        
+        let deleted = client.retract_post(&post(1, 2), &coupon("small", Some(10.0)), "Expired", Some("Gone"))
+            .await
+            .unwrap();
+        assert!(!deleted);
+        
+        // The other coupon's embed and the shared content are left alone
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[1].method, "GET");
+        assert_eq!(requests[2].method, "PATCH");
+        let body: Value = serde_json::from_str(&requests[2].body).unwrap();
+        assert!(body.get("content").is_none());
+        assert_eq!(body["embeds"][0]["title"], "✅ big AI Coupon");
+        assert_eq!(body["embeds"][1]["title"], "~~small AI Coupon~~");
+        assert_eq!(body["embeds"][1]["fields"][3]["value"], "Gone");
+    }
+    
+    #[tokio::test]
+    async fn kept_posts_are_left_alone() {
+        let (base_url, requests) = record_requests_with(vec![]).await;
+        let client = mock_client(&base_url, "invalid_post_action = \"keep\"");
+        let coupon = coupon("big", Some(50.0));
+        
+        assert!(!client.retract_post(&post(0, 1), &coupon, "Expired", None).await.unwrap());
+        client.restore_post(&post(0, 1), &coupon).await.unwrap();
+        
+        assert!(requests.lock().unwrap().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn restoring_a_post_restores_its_announcement() {
+        let (base_url, requests) =
+            record_requests_with(vec![(StatusCode::OK, WEBHOOK), (StatusCode::OK, SHARED_MESSAGE)]).await;
+        let client = mock_client(&base_url, "");
+        
+        client.restore_post(&post(0, 1), &coupon("big", Some(50.0))).await.unwrap();
+        
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[1].method, "PATCH");
+        let body: Value = serde_json::from_str(&requests[1].body).unwrap();
+        assert_eq!(body["content"], "big");
+        assert_eq!(body["embeds"][0]["title"], "✅ big AI Coupon");
+        assert_eq!(body["embeds"][0]["fields"][0]["value"], "50%");
+    }
+}
@@ -0,0 +1,369 @@
+use anyhow::{Context as AnyhowContext, Result};
+use atom_syndication::{EntryBuilder, Feed, FeedBuilder, LinkBuilder, PersonBuilder, Text};
+use chrono::Utc;
+use rss::{Channel, ChannelBuilder, GuidBuilder, Item, ItemBuilder};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+
+use crate::config;
+use crate::db;
+use crate::models::{AppState, Config, Coupon};
+
+/// File name (without extension) of the feed containing coupons from every source
+const GLOBAL_FEED_NAME: &str = "coupons";
+
+/// Generator that renders stored coupons into RSS 2.0 and Atom feed files
+pub struct FeedGenerator {
+    output_dir: String,
+    base_url: String,
+    items_per_feed: usize,
+    /// Whether items can link to the coupon's API resource
+    api_enabled: bool,
+}
+
+impl FeedGenerator {
+    /// Create a new feed generator
+    pub fn new(output_dir: String, base_url: String, items_per_feed: usize, api_enabled: bool) -> Self {
+        Self {
+            output_dir,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            items_per_feed,
+            api_enabled,
+        }
+    }
+
+    /// Render the global feed and one feed per source from the given coupons. Sources
+    /// without valid coupons get empty feeds, so their dead coupons stop being published.
+    pub async fn generate(&self, coupons: &[Coupon]) -> Result<()> {
+        tokio::fs::create_dir_all(&self.output_dir)
+            .await
+            .with_context(|| format!("Failed to create feed directory {}", self.output_dir))?;
+
+        // Only publish coupons that are still usable, newest first
+        let mut valid_coupons: Vec<&Coupon> = coupons
+            .iter()
+            .filter(|c| c.is_valid && !c.is_expired())
+            .collect();
+        valid_coupons.sort_by_key(|c| std::cmp::Reverse(c.created_at));
+
+        self.write_feeds(GLOBAL_FEED_NAME, "All sources", &valid_coupons).await?;
+
+        let mut by_source: BTreeMap<&str, Vec<&Coupon>> = coupons
+            .iter()
+            .map(|coupon| (coupon.source.as_str(), Vec::new()))
+            .collect();
+        for coupon in &valid_coupons {
+            by_source.entry(coupon.source.as_str()).or_default().push(coupon);
+        }
+
+        let names = source_feed_names(by_source.keys().copied());
+        for (source, source_coupons) in &by_source {
+            self.write_feeds(&names[source], source, source_coupons).await?;
+        }
+
+        info!(
+            "Generated feeds from {} valid coupons across {} sources",
+            valid_coupons.len(),
+            by_source.len()
+        );
+        Ok(())
+    }
+
+    /// Write the RSS and Atom variants of a single feed
+    async fn write_feeds(&self, name: &str, label: &str, coupons: &[&Coupon]) -> Result<()> {
+        let coupons = &coupons[..coupons.len().min(self.items_per_feed)];
+        let title = format!("RinKokonoe Coupons: {}", label);
+
+        let channel = self.build_rss_channel(&title, coupons);
+        let rss_path = Path::new(&self.output_dir).join(format!("{}.rss", name));
+        write_atomically(&rss_path, channel.to_string().into_bytes()).await?;
+
+        let feed = self.build_atom_feed(name, &title, coupons);
+        let atom_path = Path::new(&self.output_dir).join(format!("{}.atom", name));
+        write_atomically(&atom_path, feed.to_string().into_bytes()).await?;
+
+        debug!("Wrote {} items to {:?} and {:?}", coupons.len(), rss_path, atom_path);
+        Ok(())
+    }
+
+    /// Build an RSS 2.0 channel for a list of coupons
+    fn build_rss_channel(&self, title: &str, coupons: &[&Coupon]) -> Channel {
+        let items: Vec<Item> = coupons
+            .iter()
+            .map(|coupon| {
+                let link = self.coupon_link(coupon);
+                ItemBuilder::default()
+                    .title(coupon.name.clone())
+                    .link(link.clone())
+                    .description(describe_coupon(coupon))
+                    .category(rss::Category {
+                        name: coupon.source.clone(),
+                        domain: None,
+                    })
+                    .guid(GuidBuilder::default().value(link).permalink(true).build())
+                    .pub_date(coupon.created_at.map(|dt| dt.to_rfc2822()))
+                    .build()
+            })
+            .collect();
+
+        ChannelBuilder::default()
+            .title(title)
+            .link(self.base_url.clone())
+            .description("Validated coupons for AI tools and developer resources")
+            .last_build_date(Utc::now().to_rfc2822())
+            .generator("RinKokonoe Coupon Bot".to_string())
+            .items(items)
+            .build()
+    }
+
+    /// Build an Atom feed for a list of coupons
+    fn build_atom_feed(&self, name: &str, title: &str, coupons: &[&Coupon]) -> Feed {
+        let entries = coupons
+            .iter()
+            .map(|coupon| {
+                let link = self.coupon_link(coupon);
+                let created_at = coupon.created_at.unwrap_or_else(Utc::now);
+                EntryBuilder::default()
+                    .title(coupon.name.as_str())
+                    .id(link.clone())
+                    .updated(coupon.validated_at.unwrap_or(created_at))
+                    .published(created_at.fixed_offset())
+                    .link(LinkBuilder::default().href(link).build())
+                    .summary(Text::plain(describe_coupon(coupon)))
+                    .build()
+            })
+            .collect::<Vec<_>>();
+
+        FeedBuilder::default()
+            .title(title)
+            .id(format!("{}/feeds/{}", self.base_url, name))
+            .updated(Utc::now())
+            .author(PersonBuilder::default().name("RinKokonoe Coupon Bot").build())
+            .link(LinkBuilder::default().href(self.base_url.clone()).build())
+            .entries(entries)
+            .build()
+    }
+
+    /// Link to a coupon's API resource, or to the page the coupon applies to if the API
+    /// isn't served
+    fn coupon_link(&self, coupon: &Coupon) -> String {
+        match coupon.id {
+            Some(id) if self.api_enabled => format!("{}/api/coupons/{}", self.base_url, id),
+            _ => coupon.url.clone(),
+        }
+    }
+}
+
+/// Human-readable summary used as the feed item description
+fn describe_coupon(coupon: &Coupon) -> String {
+    let mut text = coupon.description.clone();
+
+    if let Some(discount) = coupon.discount_percentage {
+        text.push_str(&format!("\nDiscount: {}%", discount));
+    }
+
+    text.push_str(&format!("\nCode: {}", coupon.code));
+    text.push_str(&format!("\nApply at: {}", coupon.url));
+
+    if let Some(expiry) = coupon.expiry {
+        text.push_str(&format!("\nExpires: {}", expiry.format("%Y-%m-%d")));
+    }
+
+    text
+}
+
+/// Turn a source name such as "Cursor AI" into a file-name-safe slug ("cursor-ai")
+fn source_slug(source: &str) -> String {
+    source
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// File name (without extension) of a source's feed: its slug, unless the slug is empty
+/// or taken by the global feed. Those fall back to names with an underscore, which
+/// slugs never contain.
+fn source_feed_name(source: &str) -> String {
+    let slug = source_slug(source);
+    if slug.is_empty() {
+        hashed_feed_name(source)
+    } else if slug == GLOBAL_FEED_NAME {
+        format!("source_{}", slug)
+    } else {
+        slug
+    }
+}
+
+/// Feed name derived from a hash of the source name, for sources without a usable slug
+fn hashed_feed_name(source: &str) -> String {
+    let hash = hex::encode(Sha256::digest(source.as_bytes()));
+    format!("source_{}", &hash[..12])
+}
+
+/// Feed names of the given sources, in name order. Sources whose slugs collide, such as
+/// "Cursor AI" and "cursor-ai", would overwrite each other's files: the first keeps the
+/// name and the others fall back to hashed names.
+fn source_feed_names<'a>(sources: impl Iterator<Item = &'a str>) -> BTreeMap<&'a str, String> {
+    let mut taken = HashSet::new();
+    let mut names = BTreeMap::new();
+
+    for source in sources {
+        let mut name = source_feed_name(source);
+        if taken.contains(&name) {
+            let hashed = hashed_feed_name(source);
+            warn!("Feed {} of source {} is taken by another source, using {}", name, source, hashed);
+            name = hashed;
+        }
+        taken.insert(name.clone());
+        names.insert(source, name);
+    }
+
+    names
+}
+
+/// Write a file via a temporary sibling so readers never see a partial feed
+async fn write_atomically(path: &Path, contents: Vec<u8>) -> Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = std::path::PathBuf::from(tmp_path);
+
+    tokio::fs::write(&tmp_path, contents)
+        .await
+        .with_context(|| format!("Failed to write {:?}", tmp_path))?;
+    tokio::fs::rename(&tmp_path, path)
+        .await
+        .with_context(|| format!("Failed to move feed into place at {:?}", path))?;
+
+    Ok(())
+}
+
+/// Start the periodic feed generation task, which stops once `shutdown` is cancelled
+pub async fn start_feed_generator(
+    state: Arc<Mutex<AppState>>,
+    config: &Config,
+    shutdown: CancellationToken,
+) -> Result<JoinHandle<()>> {
+    let generator = FeedGenerator::new(
+        config::get_rss_output_dir(),
+        config::get_base_url(),
+        config.rss.items_per_feed as usize,
+        config.api.enable,
+    );
+    let refresh_interval = StdDuration::from_secs(config.rss.refresh_interval * 60);
+    info!("Writing feeds to {}", generator.output_dir);
+
+    let handle = tokio::spawn(async move {
+        while !shutdown.is_cancelled() {
+            let db_pool = state.lock().await.db_pool.clone();
+
+            match db::get_all_coupons(&db_pool).await {
+                Ok(coupons) => {
+                    if let Err(e) = generator.generate(&coupons).await {
+                        error!("Feed generation failed: {}", e);
+                    }
+                }
+                Err(e) => error!("Failed to load coupons for feeds: {}", e),
+            }
+
+            tokio::select! {
+                _ = sleep(refresh_interval) => {}
+                _ = shutdown.cancelled() => {}
+            }
+        }
+    });
+
+    Ok(handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generator(api_enabled: bool) -> FeedGenerator {
+        FeedGenerator::new("rss".to_string(), "http://localhost:8080/".to_string(), 30, api_enabled)
+    }
+
+    fn stored_coupon() -> Coupon {
+        let mut coupon = Coupon::new(
+            "Cursor Pro".to_string(),
+            String::new(),
+            Some(20.0),
+            "SAVE20".to_string(),
+            "https://cursor.com/pricing".to_string(),
+            "Cursor AI".to_string(),
+            None,
+        );
+        coupon.id = Some(42);
+        coupon
+    }
+
+    #[test]
+    fn source_feeds_are_named_by_slug() {
+        assert_eq!(source_feed_name("Cursor AI"), "cursor-ai");
+        assert_eq!(source_feed_name("  GitHub / Education "), "github-education");
+    }
+
+    #[test]
+    fn source_feeds_do_not_replace_the_global_feed() {
+        assert_eq!(source_feed_name("Coupons"), "source_coupons");
+        assert_ne!(source_feed_name("Source Coupons"), source_feed_name("Coupons"));
+    }
+
+    #[test]
+    fn sources_without_a_slug_get_a_hashed_name() {
+        let name = source_feed_name("!!!");
+        assert!(name.starts_with("source_"));
+        assert_eq!(name.len(), "source_".len() + 12);
+        assert_ne!(name, source_feed_name("???"));
+    }
+
+    #[test]
+    fn colliding_sources_get_separate_feeds() {
+        let names = source_feed_names(["Cursor AI", "cursor-ai", "GitHub"].into_iter());
+        assert_eq!(names["Cursor AI"], "cursor-ai");
+        assert_eq!(names["cursor-ai"], hashed_feed_name("cursor-ai"));
+        assert_eq!(names["GitHub"], "github");
+    }
+
+    #[tokio::test]
+    async fn sources_without_valid_coupons_get_empty_feeds() {
+        let output_dir = std::env::temp_dir().join(format!("rin_feeds_{}", std::process::id()));
+        let generator = FeedGenerator::new(
+            output_dir.to_string_lossy().into_owned(),
+            "http://localhost:8080".to_string(),
+            30,
+            true,
+        );
+        let feed_path = output_dir.join("cursor-ai.rss");
+
+        let mut coupon = stored_coupon();
+        coupon.is_valid = true;
+        generator.generate(&[coupon.clone()]).await.unwrap();
+        let feed = std::fs::read_to_string(&feed_path).unwrap();
+        assert_eq!(Channel::read_from(feed.as_bytes()).unwrap().items().len(), 1);
+
+        coupon.is_valid = false;
+        generator.generate(&[coupon]).await.unwrap();
+        let feed = std::fs::read_to_string(&feed_path).unwrap();
+        assert!(Channel::read_from(feed.as_bytes()).unwrap().items().is_empty());
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn items_link_to_the_api_when_it_is_served() {
+        let coupon = stored_coupon();
+        assert_eq!(generator(true).coupon_link(&coupon), "http://localhost:8080/api/coupons/42");
+        assert_eq!(generator(false).coupon_link(&coupon), "https://cursor.com/pricing");
+    }
+}
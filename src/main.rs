@@ -1,17 +1,40 @@
 use anyhow::{Context, Result};
+use chrono::Utc;
 use dotenv::dotenv;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
-use tracing::{error, info};
+use tokio::time::timeout;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
 
 // These will be our modules
+mod api;
+mod commands;
 mod config;
 mod db;
+mod dedup;
+mod declarative;
+mod digest;
 mod discord;
+mod feed;
+mod metrics;
 mod models;
+mod notifier;
+mod outbox;
+mod proxy;
 mod scheduler;
 mod scraper;
+#[cfg(test)]
+mod testing;
 mod validator;
+mod webhook;
+
+/// How long to wait for tasks to stop after they were aborted on shutdown
+const ABORT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for the database connections to close on shutdown
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -33,49 +56,179 @@ async fn main() -> Result<()> {
         .context("Failed to initialize database")?;
     info!("Database connection established");
 
+    // Cancelled on Ctrl+C or SIGTERM to let every task wind down
+    let shutdown = CancellationToken::new();
+
+    // Initialize shared state
+    let state = Arc::new(Mutex::new(models::AppState {
+        config: config.clone(),
+        db_pool: db_pool.clone(),
+        started_at: Utc::now(),
+        discord_auth: models::DiscordAuth::NotConfigured,
+    }));
+
+    // Initialize the proxy pool shared by scrapers and validators
+    let proxy_pool = proxy::initialize_proxy_pool(&config)
+        .context("Failed to initialize proxy pool")?;
+
     // Initialize coupon scrapers
     let scrapers = scraper::initialize_scrapers(&config)
         .context("Failed to initialize scrapers")?;
+    let http_client = scraper::create_http_client(&config, proxy_pool.clone())
+        .context("Failed to create scraping HTTP client")?;
     info!("Scrapers initialized successfully");
 
     // Initialize coupon validator
-    let validator = validator::initialize_validator(&config)
+    let validator = validator::initialize_validator(&config, proxy_pool)
         .context("Failed to initialize validator")?;
     info!("Validator initialized successfully");
 
-    // Initialize Discord client
-    let discord_client = discord::initialize_discord(&config)
+    // Initialize Discord and the other notification backends
+    let notifier = notifier::initialize_notifiers(&config, &state)
         .await
-        .context("Failed to initialize Discord client")?;
-    info!("Discord client initialized successfully");
+        .context("Failed to initialize notifiers")?;
+    info!("Notifiers initialized successfully");
 
-    // Initialize shared state
-    let state = Arc::new(Mutex::new(models::AppState {
-        config: config.clone(),
-        db_pool: db_pool.clone(),
-        last_scrape: None,
-    }));
+    // Initialize coupon event webhooks
+    let webhooks = webhook::initialize_webhooks(&config, db_pool.clone(), shutdown.clone())
+        .context("Failed to initialize webhooks")?;
 
     // Start the scheduler for periodic scraping
     let scheduler_handle = scheduler::start_scheduler(
         state.clone(),
         scrapers,
+        http_client,
         validator,
-        discord_client.clone(),
+        notifier.clone(),
+        webhooks.clone(),
         &config,
+        shutdown.clone(),
     )
     .await
     .context("Failed to start scheduler")?;
     info!("Scheduler started successfully");
 
+    // Start delivering queued coupon announcements
+    let outbox_handle = outbox::start_dispatcher(
+        state.clone(),
+        notifier.clone(),
+        webhooks.clone(),
+        &config,
+        shutdown.clone(),
+    )
+    .await
+    .context("Failed to start outbox dispatcher")?;
+    info!("Outbox dispatcher started successfully");
+
+    // Start posting daily and weekly digests to the targets that want them
+    let digest_handle =
+        digest::start_digest_scheduler(state.clone(), notifier.clone(), &config, shutdown.clone())
+            .await
+            .context("Failed to start digest scheduler")?;
+    if digest_handle.is_some() {
+        info!("Digest scheduler started successfully");
+    }
+
+    // Start periodic RSS/Atom feed generation
+    let feed_handle = feed::start_feed_generator(state.clone(), &config, shutdown.clone())
+        .await
+        .context("Failed to start feed generator")?;
+    info!("Feed generator started successfully");
+
+    // Start the Discord bot for slash commands if a bot token is available
+    let bot_handle = if config.discord.enable_commands && config::get_discord_token().is_ok() {
+        let handle = discord::start_discord_bot(state.clone(), &config, shutdown.clone())
+            .await
+            .context("Failed to start Discord bot")?;
+        info!("Discord bot started successfully");
+        Some(handle)
+    } else {
+        info!("Discord slash commands disabled");
+        None
+    };
+
+    // Start the HTTP API server if enabled
+    let api_handle = if config.api.enable {
+        let handle = api::start_api_server(state.clone(), &config, shutdown.clone())
+            .await
+            .context("Failed to start API server")?;
+        info!("API server started successfully");
+        Some(handle)
+    } else {
+        info!("API server disabled");
+        None
+    };
+
     // Keep the main thread alive
-    tokio::signal::ctrl_c().await?;
-    info!("Shutdown signal received, cleaning up...");
+    wait_for_shutdown_signal().await?;
+    info!("Shutdown signal received, finishing in-flight work...");
+    shutdown.cancel();
+
+    // Give the tasks up to the drain timeout to finish what they are doing
+    let mut handles = vec![scheduler_handle, outbox_handle, feed_handle];
+    handles.extend(digest_handle);
+    handles.extend(bot_handle);
+    handles.extend(api_handle);
 
-    // Cleanup
-    scheduler_handle.abort();
+    let drain = async {
+        for handle in handles.iter_mut() {
+            if let Err(e) = handle.await {
+                error!("Task failed during shutdown: {}", e);
+            }
+        }
+        webhooks.drain().await;
+    };
+    let drain_timeout = Duration::from_secs(config.shutdown.drain_timeout);
+    if timeout(drain_timeout, drain).await.is_err() {
+        warn!(
+            "In-flight work didn't finish within {} seconds, stopping anyway",
+            drain_timeout.as_secs()
+        );
+        for handle in &handles {
+            handle.abort();
+        }
+        webhooks.abort();
+
+        // Aborted tasks drop their scrapes and database connections once they stop
+        let stopped = async {
+            for handle in handles {
+                handle.await.ok();
+            }
+            webhooks.drain().await;
+        };
+        if timeout(ABORT_TIMEOUT, stopped).await.is_err() {
+            warn!("Some tasks didn't stop within {} seconds", ABORT_TIMEOUT.as_secs());
+        }
+    }
+
+    // Close the database last, once nothing writes to it anymore
+    if timeout(CLOSE_TIMEOUT, db_pool.close()).await.is_err() {
+        warn!(
+            "Database connections weren't released within {} seconds, exiting anyway",
+            CLOSE_TIMEOUT.as_secs()
+        );
+    }
     info!("RinKokonoe bot shutting down");
 
     Ok(())
 }
 
+/// Wait for Ctrl+C, or SIGTERM as sent by `docker stop`
+async fn wait_for_shutdown_signal() -> Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = terminate.recv() => {}
+        }
+    }
+
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
+
+    Ok(())
+}
+
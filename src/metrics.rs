@@ -0,0 +1,157 @@
+use lazy_static::lazy_static;
+use prometheus::{
+    register_histogram_vec, register_int_counter, register_int_counter_vec, Encoder,
+    HistogramTimer, HistogramVec, IntCounter, IntCounterVec, TextEncoder,
+};
+use reqwest::StatusCode;
+use tracing::error;
+
+/// Buckets of scraper run durations, in seconds
+const SCRAPE_DURATION_BUCKETS: &[f64] = &[0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0];
+
+/// Buckets of database query latencies, in seconds
+const QUERY_LATENCY_BUCKETS: &[f64] = &[0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5];
+
+lazy_static! {
+    static ref SCRAPE_DURATION: HistogramVec = register_histogram_vec!(
+        "rin_scrape_duration_seconds",
+        "Duration of scraper runs by scraper and outcome",
+        &["scraper", "status"],
+        SCRAPE_DURATION_BUCKETS.to_vec()
+    )
+    .unwrap();
+    static ref COUPONS_FOUND: IntCounterVec = register_int_counter_vec!(
+        "rin_coupons_found_total",
+        "Coupons returned by scrapers, including ones already stored",
+        &["scraper"]
+    )
+    .unwrap();
+    static ref COUPONS_INSERTED: IntCounter = register_int_counter!(
+        "rin_coupons_inserted_total",
+        "New coupons stored in the database"
+    )
+    .unwrap();
+    static ref VALIDATIONS: IntCounterVec = register_int_counter_vec!(
+        "rin_validations_total",
+        "Coupon validations by validator and outcome (valid, invalid or error)",
+        &["validator", "outcome"]
+    )
+    .unwrap();
+    static ref COUPONS_POSTED: IntCounter = register_int_counter!(
+        "rin_coupons_posted_total",
+        "Coupons sent to at least one notification target"
+    )
+    .unwrap();
+    static ref HTTP_ERRORS: IntCounterVec = register_int_counter_vec!(
+        "rin_http_errors_total",
+        "Failed HTTP requests of scrapers and validators by status code, or `network` when no response arrived",
+        &["code"]
+    )
+    .unwrap();
+    static ref DISCORD_SEND_FAILURES: IntCounterVec = register_int_counter_vec!(
+        "rin_discord_send_failures_total",
+        "Messages that could not be sent to a Discord target",
+        &["target"]
+    )
+    .unwrap();
+    static ref DISCORD_RATE_LIMITS: IntCounterVec = register_int_counter_vec!(
+        "rin_discord_rate_limit_waits_total",
+        "Requests to Discord delayed by a rate limit, by scope (route or global)",
+        &["scope"]
+    )
+    .unwrap();
+    static ref DB_QUERY_DURATION: HistogramVec = register_histogram_vec!(
+        "rin_db_query_duration_seconds",
+        "Latency of database operations by function",
+        &["query"],
+        QUERY_LATENCY_BUCKETS.to_vec()
+    )
+    .unwrap();
+}
+
+/// Record a finished scraper run
+pub fn observe_scrape(scraper: &str, status: &str, seconds: f64, coupons_found: usize) {
+    SCRAPE_DURATION
+        .with_label_values(&[scraper, status])
+        .observe(seconds);
+    COUPONS_FOUND
+        .with_label_values(&[scraper])
+        .inc_by(coupons_found as u64);
+}
+
+/// Count a newly stored coupon
+pub fn coupon_inserted() {
+    COUPONS_INSERTED.inc();
+}
+
+/// Count a validation attempt; `is_valid` is `None` if the validator failed
+pub fn coupon_validated(validator: &str, is_valid: Option<bool>) {
+    let outcome = match is_valid {
+        Some(true) => "valid",
+        Some(false) => "invalid",
+        None => "error",
+    };
+    VALIDATIONS.with_label_values(&[validator, outcome]).inc();
+}
+
+/// Count a coupon posted to its notification targets
+pub fn coupon_posted() {
+    COUPONS_POSTED.inc();
+}
+
+/// Count an HTTP request that failed, with the status it failed with if any
+pub fn http_error(status: Option<StatusCode>) {
+    let code = status.map_or_else(|| "network".to_string(), |status| status.as_u16().to_string());
+    HTTP_ERRORS.with_label_values(&[&code]).inc();
+}
+
+/// Count a message that could not be sent to a Discord target
+pub fn discord_send_failed(target: &str) {
+    DISCORD_SEND_FAILURES.with_label_values(&[target]).inc();
+}
+
+/// Count a request to Discord that had to wait for a rate limit
+pub fn discord_rate_limited(global: bool) {
+    let scope = if global { "global" } else { "route" };
+    DISCORD_RATE_LIMITS.with_label_values(&[scope]).inc();
+}
+
+/// Start timing a database operation; the latency is recorded when the timer is dropped
+pub fn time_query(query: &str) -> HistogramTimer {
+    DB_QUERY_DURATION.with_label_values(&[query]).start_timer()
+}
+
+/// Render all metrics in the Prometheus text exposition format
+pub fn render() -> String {
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&prometheus::gather(), &mut buffer) {
+        error!("Failed to encode metrics: {}", e);
+    }
+
+    String::from_utf8(buffer).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_uses_prometheus_text_format() {
+        observe_scrape("Test Scraper", "success", 1.5, 3);
+        coupon_validated("Test Validator", Some(false));
+        http_error(Some(StatusCode::IM_A_TEAPOT));
+        http_error(None);
+        drop(time_query("insert_coupon"));
+
+        let text = render();
+        assert!(text.contains("# TYPE rin_scrape_duration_seconds histogram"));
+        assert!(text.contains(
+            r#"rin_scrape_duration_seconds_bucket{scraper="Test Scraper",status="success",le="2.5"} 1"#
+        ));
+        assert!(text.contains(r#"rin_coupons_found_total{scraper="Test Scraper"} 3"#));
+        assert!(text.contains(r#"rin_validations_total{outcome="invalid",validator="Test Validator"} 1"#));
+        assert!(text.contains(r#"rin_http_errors_total{code="418"} 1"#));
+        assert!(text.contains(r#"rin_http_errors_total{code="network"}"#));
+        assert!(text.contains(r#"rin_db_query_duration_seconds_count{query="insert_coupon"}"#));
+    }
+}
@@ -1,6 +1,11 @@
+use anyhow::{Context as AnyhowContext, Result};
 use chrono::{DateTime, Utc};
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use sqlx::{Pool, Sqlite};
+use std::collections::HashMap;
+use std::str::FromStr;
 use std::sync::Arc;
 
 /// Application state shared between components
@@ -8,7 +13,30 @@ use std::sync::Arc;
 pub struct AppState {
     pub config: Arc<Config>,
     pub db_pool: Pool<Sqlite>,
-    pub last_scrape: Option<DateTime<Utc>>,
+    pub started_at: DateTime<Utc>,
+    /// Outcome of the last attempt to authenticate with the Discord bot token
+    pub discord_auth: DiscordAuth,
+}
+
+/// Whether the bot is authenticated with Discord
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscordAuth {
+    /// No bot token is set; Discord is reached through webhooks, if at all
+    NotConfigured,
+    /// The token was rejected or Discord couldn't be reached
+    Failed,
+    Authenticated,
+}
+
+impl DiscordAuth {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiscordAuth::NotConfigured => "not_configured",
+            DiscordAuth::Failed => "failed",
+            DiscordAuth::Authenticated => "authenticated",
+        }
+    }
 }
 
 /// Configuration structure matching config.toml
@@ -20,6 +48,15 @@ pub struct Config {
     pub api: ApiConfig,
     pub proxy: ProxyConfig,
     pub validation: ValidationConfig,
+    pub outbox: OutboxConfig,
+    pub digest: DigestConfig,
+    pub shutdown: ShutdownConfig,
+    /// Notification backends besides Discord
+    #[serde(default)]
+    pub notifiers: Vec<NotifierConfig>,
+    /// Endpoints receiving signed coupon lifecycle events
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -30,13 +67,366 @@ pub struct DiscordConfig {
     pub webhook_url: Option<String>,
     #[serde(default)]
     pub channel_id: Option<String>,
+    pub enable_commands: bool,
+    /// Register slash commands in this guild only (instant) instead of globally
+    #[serde(default)]
+    pub guild_id: Option<String>,
+    /// What to do with posts of coupons that expire or stop working
+    #[serde(default)]
+    pub invalid_post_action: InvalidPostAction,
+    /// Additional channels, each receiving the coupons that match its filter
+    #[serde(default)]
+    pub targets: Vec<NotificationTarget>,
+    /// Coupons posted together in one message, one embed each, when several are due at once
+    pub batch_size: u64,
+    /// Post a single digest instead once more coupons than this are due at once; 0 disables digests
+    pub digest_threshold: u64,
+    /// Post the default target's coupons as a daily or weekly digest instead of one by one
+    #[serde(default)]
+    pub digest: Option<DigestFrequency>,
+}
+
+/// A Discord channel or webhook that coupons are routed to
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NotificationTarget {
+    /// Unique name, used in logs and to find the target of stored posts
+    pub name: String,
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+    /// Channel posted to with the bot token
+    #[serde(default)]
+    pub channel_id: Option<String>,
+    /// Post the target's coupons as a daily or weekly digest instead of one by one
+    #[serde(default)]
+    pub digest: Option<DigestFrequency>,
+    #[serde(flatten)]
+    pub filter: NotificationFilter,
+}
+
+/// A notification backend other than Discord
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NotifierConfig {
+    /// Unique name, used in logs
+    pub name: String,
+    #[serde(flatten)]
+    pub backend: NotifierBackend,
+    #[serde(flatten)]
+    pub filter: NotificationFilter,
+}
+
+/// Backend-specific settings, selected by `type`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum NotifierBackend {
+    /// Slack incoming webhook
+    Slack { webhook_url: String },
+    /// Telegram Bot API
+    Telegram {
+        bot_token: String,
+        chat_id: String,
+        #[serde(default = "default_telegram_api_url")]
+        api_url: String,
+    },
+    /// Matrix client-server API
+    Matrix {
+        homeserver: String,
+        access_token: String,
+        room_id: String,
+    },
+    /// Email over SMTP
+    Email {
+        smtp_host: String,
+        #[serde(default = "default_smtp_port")]
+        smtp_port: u16,
+        #[serde(default)]
+        smtp_tls: SmtpTls,
+        #[serde(default)]
+        username: Option<String>,
+        #[serde(default)]
+        password: Option<String>,
+        from: String,
+        to: Vec<String>,
+    },
+    /// Signed JSON POST to any URL, in the format of the `[[webhooks]]` events
+    Webhook {
+        url: String,
+        /// Key for the HMAC-SHA256 signature header
+        secret: String,
+        /// Extra request headers, e.g. for authentication
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+}
+
+fn default_telegram_api_url() -> String {
+    "https://api.telegram.org".to_string()
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+/// How the SMTP connection is secured
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Upgrade a plain connection with STARTTLS
+    #[default]
+    Starttls,
+    /// Connect with TLS from the start, usually on port 465
+    Tls,
+    /// No encryption, for local relays only
+    None,
+}
+
+/// An endpoint receiving signed JSON coupon events
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Key for the HMAC-SHA256 signature header
+    pub secret: String,
+    /// Events to send; all events if empty
+    #[serde(default)]
+    pub events: Vec<CouponEvent>,
+    /// Delivery attempts before an event is moved to the dead-letter table
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+}
+
+fn default_max_attempts() -> u32 {
+    5
+}
+
+/// Lifecycle events of a coupon
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum CouponEvent {
+    /// A new coupon was scraped
+    #[serde(rename = "coupon.discovered")]
+    Discovered,
+    /// A coupon passed validation
+    #[serde(rename = "coupon.validated")]
+    Validated,
+    /// A coupon failed validation
+    #[serde(rename = "coupon.invalidated")]
+    Invalidated,
+    /// A valid coupon was announced to the notification targets
+    #[serde(rename = "coupon.posted")]
+    Posted,
+    /// A coupon passed its expiry and is being removed
+    #[serde(rename = "coupon.expired")]
+    Expired,
+}
+
+impl CouponEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CouponEvent::Discovered => "coupon.discovered",
+            CouponEvent::Validated => "coupon.validated",
+            CouponEvent::Invalidated => "coupon.invalidated",
+            CouponEvent::Posted => "coupon.posted",
+            CouponEvent::Expired => "coupon.expired",
+        }
+    }
+}
+
+/// A webhook event that could not be delivered
+#[derive(Debug, Clone)]
+pub struct WebhookDeadLetter {
+    pub url: String,
+    pub event_id: String,
+    pub event_type: String,
+    pub payload: String,
+    pub attempts: i64,
+    pub last_error: String,
+    pub http_status: Option<i64>,
+    pub failed_at: DateTime<Utc>,
+}
+
+/// Rules deciding which coupons a notification target receives.
+/// Empty rules match every coupon.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct NotificationFilter {
+    /// Coupon sources, e.g. "GitHub"; matched case-insensitively
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// Minimum discount percentage; coupons without a known discount don't match
+    #[serde(default)]
+    pub min_discount: Option<f64>,
+    /// Words of which at least one must appear in the coupon's name or description
+    #[serde(default)]
+    pub keywords: Vec<String>,
+}
+
+impl NotificationFilter {
+    /// Check whether a coupon passes every rule of the filter
+    pub fn matches(&self, coupon: &Coupon) -> bool {
+        if !self.sources.is_empty()
+            && !self.sources.iter().any(|source| source.eq_ignore_ascii_case(&coupon.source))
+        {
+            return false;
+        }
+        
+        if let Some(min_discount) = self.min_discount {
+            if !coupon.discount_percentage.is_some_and(|discount| discount >= min_discount) {
+                return false;
+            }
+        }
+        
+        if !self.keywords.is_empty() {
+            let text = format!("{} {}", coupon.name, coupon.description).to_lowercase();
+            if !self.keywords.iter().any(|keyword| text.contains(&keyword.to_lowercase())) {
+                return false;
+            }
+        }
+        
+        true
+    }
+}
+
+/// How Discord posts of expired or invalid coupons are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InvalidPostAction {
+    /// Strike the post through and mark it as expired or invalid
+    #[default]
+    Edit,
+    /// Delete the post
+    Delete,
+    /// Leave the post untouched
+    Keep,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ScrapingConfig {
     pub default_interval: u64,
     pub max_concurrent: u64,
+    pub scraper_timeout: u64,
     pub user_agent: String,
+    #[serde(default)]
+    pub schedules: Vec<ScraperScheduleConfig>,
+    #[serde(default)]
+    pub sources: Vec<SourceConfig>,
+    /// Directory of TOML/YAML scraper definitions
+    pub definitions_dir: String,
+    /// Title similarity (0-1) above which coupons with the same code are merged
+    pub duplicate_similarity: f64,
+    /// Runs a scraper must miss a coupon in before it is marked likely dead (0 disables)
+    pub likely_dead_after: u64,
+    /// Runs in a row without coupons after which a scraper is reported unhealthy
+    pub unhealthy_after: u64,
+}
+
+/// A deal site scraped by the generic AI tools scraper
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SourceConfig {
+    /// Display name used in coupon titles
+    pub name: String,
+    pub url: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub selectors: SourceSelectors,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Optional CSS selectors for extracting coupons from a deal site.
+/// Without a `code` selector the page text is searched for coupon code patterns instead.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SourceSelectors {
+    /// Element containing a single deal; other selectors are matched inside it
+    #[serde(default)]
+    pub item: Option<String>,
+    #[serde(default)]
+    pub code: Option<String>,
+    #[serde(default)]
+    pub discount: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub expiry: Option<String>,
+}
+
+/// Schedule override for a single scraper, matched by scraper name
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ScraperScheduleConfig {
+    pub scraper: String,
+    /// Minutes between runs
+    #[serde(default)]
+    pub interval: Option<u64>,
+    /// Cron expression evaluated in UTC, used instead of `interval`
+    #[serde(default)]
+    pub cron: Option<String>,
+}
+
+/// When a scraper should run
+#[derive(Debug, Clone)]
+pub enum ScrapeSchedule {
+    /// Run at a fixed interval after the previous run
+    Interval(chrono::Duration),
+    /// Run at the times matched by a cron expression (UTC)
+    Cron(Box<cron::Schedule>),
+}
+
+impl ScrapeSchedule {
+    /// Build a schedule from a config override
+    pub fn from_config(schedule: &ScraperScheduleConfig) -> Result<Self> {
+        match (&schedule.interval, &schedule.cron) {
+            (Some(_), Some(_)) => Err(anyhow::anyhow!(
+                "Schedule for {} must set either interval or cron, not both",
+                schedule.scraper
+            )),
+            (Some(interval), None) => {
+                if *interval < 1 {
+                    return Err(anyhow::anyhow!(
+                        "Schedule interval for {} must be at least 1 minute",
+                        schedule.scraper
+                    ));
+                }
+                Ok(Self::Interval(chrono::Duration::minutes(*interval as i64)))
+            }
+            (None, Some(expression)) => Self::parse_cron(expression).with_context(|| {
+                format!("Invalid cron expression for {}", schedule.scraper)
+            }),
+            (None, None) => Err(anyhow::anyhow!(
+                "Schedule for {} must set interval or cron",
+                schedule.scraper
+            )),
+        }
+    }
+    
+    /// Parse a cron expression; standard five-field expressions are run at second 0
+    fn parse_cron(expression: &str) -> Result<Self> {
+        let expression = expression.trim();
+        let expression = if expression.split_whitespace().count() == 5 {
+            format!("0 {}", expression)
+        } else {
+            expression.to_string()
+        };
+        
+        let schedule = cron::Schedule::from_str(&expression)
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        Ok(Self::Cron(Box::new(schedule)))
+    }
+    
+    /// The first run time after the given instant
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Interval(interval) => Some(after + *interval),
+            Self::Cron(schedule) => schedule.after(&after).next(),
+        }
+    }
+}
+
+impl std::fmt::Display for ScrapeSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Interval(interval) => write!(f, "every {} minutes", interval.num_minutes()),
+            Self::Cron(schedule) => write!(f, "cron '{}'", schedule),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -63,6 +453,68 @@ pub struct ProxyConfig {
 pub struct ValidationConfig {
     pub enable: bool,
     pub timeout: u64,
+    /// Minutes between re-validation runs, 0 to disable re-validation
+    pub revalidate_interval: u64,
+    /// Hours after which a coupon's validation is considered stale
+    pub revalidate_after: u64,
+    /// Maximum number of coupons re-validated per run
+    pub revalidate_batch: u64,
+    /// Post a follow-up to Discord when a posted coupon stops working
+    pub notify_dead: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OutboxConfig {
+    /// Seconds between checks for due announcements
+    pub poll_interval: u64,
+    /// Attempts before an announcement is marked failed
+    pub max_attempts: u32,
+    /// Seconds a new announcement waits, so coupons found together are sent together
+    pub batch_delay: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DigestConfig {
+    /// Local time digests are posted at, as `HH:MM`
+    pub time: String,
+    /// IANA name of the timezone of `time`, e.g. `Europe/Berlin`
+    pub timezone: String,
+    /// Day weekly digests are posted on, e.g. `mon`
+    pub weekday: String,
+    /// Hours before expiry a coupon is highlighted as expiring soon
+    pub expiring_within: u64,
+}
+
+/// How often a digest is posted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum DigestFrequency {
+    Daily,
+    Weekly,
+}
+
+impl DigestFrequency {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DigestFrequency::Daily => "daily",
+            DigestFrequency::Weekly => "weekly",
+        }
+    }
+    
+    /// Length of the window a digest covers
+    pub fn period(&self) -> chrono::Duration {
+        match self {
+            DigestFrequency::Daily => chrono::Duration::days(1),
+            DigestFrequency::Weekly => chrono::Duration::weeks(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ShutdownConfig {
+    /// Seconds to wait for in-flight work after a shutdown signal before exiting anyway
+    pub drain_timeout: u64,
 }
 
 /// Represents a coupon with all metadata
@@ -81,10 +533,19 @@ pub struct Coupon {
     pub created_at: Option<DateTime<Utc>>,
     #[sqlx(default)]
     pub validated_at: Option<DateTime<Utc>>,
+    /// When a scrape first found the coupon
+    #[sqlx(default)]
+    pub first_seen: Option<DateTime<Utc>>,
+    /// When a scrape last found the coupon
+    #[sqlx(default)]
+    pub last_seen: Option<DateTime<Utc>>,
     #[sqlx(default)]
     pub is_valid: bool,
     #[sqlx(default)]
     pub is_posted: bool,
+    /// Set once the scrapers that found the coupon stop finding it
+    #[sqlx(default)]
+    pub likely_dead: bool,
     #[sqlx(default)]
     pub hash: String,
 }
@@ -101,7 +562,7 @@ impl Coupon {
         expiry: Option<DateTime<Utc>>,
     ) -> Self {
         let now = Utc::now();
-        let hash = Self::generate_hash(&name, &code, &url);
+        let hash = Self::identity_hash(&source, &code, &url);
         
         Self {
             id: None,
@@ -114,22 +575,26 @@ impl Coupon {
             expiry,
             created_at: Some(now),
             validated_at: None,
+            first_seen: Some(now),
+            last_seen: Some(now),
             is_valid: false,
             is_posted: false,
+            likely_dead: false,
             hash,
         }
     }
     
-    /// Generate a unique hash for the coupon to help with deduplication
-    fn generate_hash(name: &str, code: &str, url: &str) -> String {
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
-        
-        let mut hasher = DefaultHasher::new();
-        name.hash(&mut hasher);
-        code.hash(&mut hasher);
-        url.hash(&mut hasher);
-        format!("{:x}", hasher.finish())
+    /// Hash identifying a coupon across scrapes and releases: SHA-256 over the source,
+    /// the uppercased code and the canonical URL. The coupon's name is left out since
+    /// scrapers may reword it between runs.
+    pub fn identity_hash(source: &str, code: &str, url: &str) -> String {
+        let identity = format!(
+            "{}\n{}\n{}",
+            source.trim().to_lowercase(),
+            code.trim().to_uppercase(),
+            canonical_url(url)
+        );
+        hex::encode(Sha256::digest(identity.as_bytes()))
     }
     
     /// Check if the coupon is expired
@@ -141,6 +606,257 @@ impl Coupon {
     }
 }
 
+/// Query parameters that only record where a visitor came from
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid", "gclid", "dclid", "msclkid", "yclid", "mc_cid", "mc_eid", "ref", "ref_src", "_hsenc",
+    "_hsmi",
+];
+
+/// Normalize a coupon URL so links to the same page compare equal: http becomes https,
+/// `www.`, fragments, trailing slashes and tracking parameters are dropped and the
+/// remaining query parameters are sorted
+pub fn canonical_url(url: &str) -> String {
+    let url = url.trim();
+    let mut parsed = match Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(_) => return url.to_lowercase(),
+    };
+
+    if parsed.scheme() == "http" {
+        let _ = parsed.set_scheme("https");
+    }
+    if let Some(host) = parsed.host_str().and_then(|host| host.strip_prefix("www.")) {
+        let host = host.to_string();
+        let _ = parsed.set_host(Some(&host));
+    }
+    parsed.set_fragment(None);
+
+    let path = parsed.path().trim_end_matches('/').to_string();
+    parsed.set_path(&path);
+
+    let mut params: Vec<(String, String)> = parsed
+        .query_pairs()
+        .filter(|(key, _)| {
+            let key = key.to_lowercase();
+            !key.starts_with("utm_") && !TRACKING_PARAMS.contains(&key.as_str())
+        })
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    params.sort();
+
+    if params.is_empty() {
+        parsed.set_query(None);
+    } else {
+        parsed.query_pairs_mut().clear().extend_pairs(&params);
+    }
+
+    parsed.to_string()
+}
+
+/// Persisted run times of a scraper's schedule
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ScraperScheduleState {
+    pub scraper: String,
+    pub last_run: Option<DateTime<Utc>>,
+    pub next_run: DateTime<Utc>,
+}
+
+/// Outcome of a scraper run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum ScrapeStatus {
+    /// The scraper found at least one coupon
+    Success,
+    /// The scraper finished without finding anything, which usually means the markup changed
+    Empty,
+    Failed,
+    TimedOut,
+}
+
+impl ScrapeStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScrapeStatus::Success => "success",
+            ScrapeStatus::Empty => "empty",
+            ScrapeStatus::Failed => "failed",
+            ScrapeStatus::TimedOut => "timed out",
+        }
+    }
+}
+
+/// A recorded run of a single scraper
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ScrapeRun {
+    #[sqlx(default)]
+    pub id: Option<i64>,
+    pub scraper: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub status: ScrapeStatus,
+    pub coupons_found: i64,
+    /// Statuses of the HTTP responses the scraper received, in order
+    pub http_statuses: sqlx::types::Json<Vec<u16>>,
+    pub bytes_fetched: i64,
+    pub error: Option<String>,
+}
+
+/// Health of a scraper, summarized from its recent runs
+#[derive(Debug, Clone, Serialize)]
+pub struct ScraperHealth {
+    pub scraper: String,
+    pub healthy: bool,
+    pub last_run: Option<DateTime<Utc>>,
+    pub last_status: Option<ScrapeStatus>,
+    pub last_error: Option<String>,
+    pub last_coupons_found: i64,
+    /// Latest runs in a row that failed, timed out or found nothing
+    pub consecutive_failures: i64,
+    /// Share of the recent runs that found coupons, from 0 to 1
+    pub success_rate: f64,
+    /// Number of recent runs the summary is based on
+    pub runs: i64,
+}
+
+impl ScraperHealth {
+    /// Summarize a scraper's runs, given newest first. The scraper is unhealthy once
+    /// `unhealthy_after` runs in a row didn't find any coupons.
+    pub fn from_runs(scraper: &str, runs: &[ScrapeRun], unhealthy_after: u64) -> Self {
+        let consecutive_failures = runs
+            .iter()
+            .take_while(|run| run.status != ScrapeStatus::Success)
+            .count() as i64;
+        let successes = runs
+            .iter()
+            .filter(|run| run.status == ScrapeStatus::Success)
+            .count();
+        let success_rate = if runs.is_empty() {
+            0.0
+        } else {
+            successes as f64 / runs.len() as f64
+        };
+        let last = runs.first();
+
+        Self {
+            scraper: scraper.to_string(),
+            healthy: consecutive_failures < unhealthy_after as i64,
+            last_run: last.map(|run| run.started_at),
+            last_status: last.map(|run| run.status),
+            last_error: last.and_then(|run| run.error.clone()),
+            last_coupons_found: last.map(|run| run.coupons_found).unwrap_or_default(),
+            consecutive_failures,
+            success_rate,
+            runs: runs.len() as i64,
+        }
+    }
+}
+
+/// Delivery state of a queued coupon announcement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum OutboxStatus {
+    /// Waiting for its first or next attempt
+    Pending,
+    Delivered,
+    /// Dropped without sending, e.g. because the coupon expired while it waited
+    Skipped,
+    /// Gave up after `outbox.max_attempts` failed attempts
+    Failed,
+}
+
+/// A coupon announcement in the notification outbox
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct OutboxEntry {
+    pub id: i64,
+    pub coupon_id: i64,
+    pub idempotency_key: String,
+    pub status: OutboxStatus,
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+impl OutboxEntry {
+    /// Idempotency key of a coupon's announcement, stable across retries and restarts
+    pub fn post_key(coupon: &Coupon) -> String {
+        format!("post-{}", coupon.hash)
+    }
+}
+
+/// Coupons found during a digest window, to be summarized in a single post
+#[derive(Debug, Clone)]
+pub struct CouponDigest {
+    pub frequency: DigestFrequency,
+    /// Discord target the digest is posted to
+    pub target: String,
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+    /// Valid, unexpired coupons the target accepted during the window
+    pub coupons: Vec<Coupon>,
+    /// Coupons expiring before this are highlighted
+    pub expiring_before: DateTime<Utc>,
+}
+
+/// A posted digest, or one that was skipped because its window had no coupons
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct DigestRun {
+    #[sqlx(default)]
+    pub id: Option<i64>,
+    pub frequency: DigestFrequency,
+    /// Discord target of the digest; empty for runs that covered every target
+    pub target: String,
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+    pub coupon_count: i64,
+    /// Notification targets the digest was posted to: 1, or 0 if nothing was posted
+    pub targets: i64,
+    pub posted_at: DateTime<Utc>,
+}
+
+/// Number of items waiting in the bot's queues
+#[derive(Debug, Clone, Serialize)]
+pub struct QueueSizes {
+    /// Valid coupons that haven't been posted to any notification target
+    pub unposted_coupons: i64,
+    /// Coupon announcements waiting in the outbox
+    pub outbox_pending: i64,
+    /// Coupon announcements the outbox gave up on
+    pub outbox_failed: i64,
+    /// Webhook events that could not be delivered
+    pub webhook_dead_letters: i64,
+}
+
+/// Aggregate counts over all stored coupons
+#[derive(Debug, Clone, Serialize)]
+pub struct CouponStats {
+    pub total: i64,
+    pub valid: i64,
+    pub posted: i64,
+}
+
+/// Coupon counts for a single source
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct SourceStats {
+    pub source: String,
+    pub total: i64,
+    pub valid: i64,
+}
+
+/// Filter and pagination options for querying stored coupons
+#[derive(Debug, Clone)]
+pub struct CouponFilter {
+    pub source: Option<String>,
+    pub is_valid: Option<bool>,
+    pub expired: Option<bool>,
+    pub expires_before: Option<DateTime<Utc>>,
+    pub limit: i64,
+    pub offset: i64,
+}
+
 /// Enum representing different coupon sources/types
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum CouponSource {
@@ -152,16 +868,17 @@ pub enum CouponSource {
     Generic,
 }
 
-impl ToString for CouponSource {
-    fn to_string(&self) -> String {
-        match self {
-            CouponSource::CursorAI => "Cursor AI".to_string(),
-            CouponSource::GitHub => "GitHub".to_string(),
-            CouponSource::Replit => "Replit".to_string(),
-            CouponSource::Warp => "Warp".to_string(),
-            CouponSource::Tabnine => "Tabnine".to_string(),
-            CouponSource::Generic => "Generic".to_string(),
-        }
+impl std::fmt::Display for CouponSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            CouponSource::CursorAI => "Cursor AI",
+            CouponSource::GitHub => "GitHub",
+            CouponSource::Replit => "Replit",
+            CouponSource::Warp => "Warp",
+            CouponSource::Tabnine => "Tabnine",
+            CouponSource::Generic => "Generic",
+        };
+        f.write_str(name)
     }
 }
 
@@ -171,5 +888,148 @@ pub struct ValidationResult {
     pub is_valid: bool,
     pub message: Option<String>,
     pub validated_at: DateTime<Utc>,
+    /// Status of the page the validator checked, if it made a request
+    pub http_status: Option<u16>,
 }
 
+/// A Discord message announcing a coupon
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct DiscordMessage {
+    pub coupon_id: i64,
+    pub message_id: String,
+    /// Name of the notification target the message was sent to
+    pub target: String,
+    pub channel_id: String,
+    /// Whether the message was sent through the webhook rather than by the bot
+    pub via_webhook: bool,
+    pub posted_at: DateTime<Utc>,
+    /// Position of the coupon's embed in the message
+    pub embed_index: i64,
+    /// Number of embeds in the message, more than one if it announced several coupons
+    pub embed_count: i64,
+    /// Position of the coupon's field if the message is a digest listing it
+    pub field_index: Option<i64>,
+}
+
+/// A near-duplicate of a coupon found on another source or page, merged into the coupon
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct CouponListing {
+    #[sqlx(default)]
+    pub id: Option<i64>,
+    /// Canonical coupon the listing was merged into
+    pub coupon_id: i64,
+    pub source: String,
+    pub name: String,
+    pub url: String,
+    pub hash: String,
+    pub first_seen: DateTime<Utc>,
+}
+
+/// A scrape run that found a coupon
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct CouponSighting {
+    pub scraper: String,
+    pub source: String,
+    pub seen_at: DateTime<Utc>,
+}
+
+/// A recorded validation attempt for a coupon
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ValidationRun {
+    #[sqlx(default)]
+    pub id: Option<i64>,
+    pub coupon_id: i64,
+    pub validator: String,
+    /// `None` if the validator failed with an error
+    pub is_valid: Option<bool>,
+    pub message: Option<String>,
+    pub http_status: Option<i64>,
+    pub duration_ms: i64,
+    pub validated_at: DateTime<Utc>,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coupon(source: &str, discount: Option<f64>, name: &str) -> Coupon {
+        Coupon::new(
+            name.to_string(),
+            "Free Pro plan for verified students".to_string(),
+            discount,
+            "STUDENT".to_string(),
+            "https://example.com".to_string(),
+            source.to_string(),
+            None,
+        )
+    }
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        let filter = NotificationFilter::default();
+        assert!(filter.matches(&coupon("GitHub", None, "GitHub Student Pack")));
+    }
+
+    #[test]
+    fn filter_rules_must_all_match() {
+        let filter = NotificationFilter {
+            sources: vec!["cursor ai".to_string()],
+            min_discount: Some(50.0),
+            keywords: vec!["Student".to_string()],
+        };
+
+        assert!(filter.matches(&coupon("Cursor AI", Some(100.0), "Cursor AI Student Discount")));
+        assert!(!filter.matches(&coupon("GitHub", Some(100.0), "GitHub Student Pack")));
+        assert!(!filter.matches(&coupon("Cursor AI", Some(20.0), "Cursor AI Promo")));
+        assert!(!filter.matches(&coupon("Cursor AI", None, "Cursor AI Student Discount")));
+
+        let filter = NotificationFilter {
+            keywords: vec!["black friday".to_string()],
+            ..Default::default()
+        };
+        assert!(!filter.matches(&coupon("Cursor AI", Some(100.0), "Cursor AI Student Discount")));
+    }
+
+    #[test]
+    fn canonical_url_drops_tracking_and_formatting_noise() {
+        assert_eq!(
+            canonical_url("http://www.Example.com/deals/?utm_source=x&b=2&a=1&fbclid=abc#top"),
+            "https://example.com/deals?a=1&b=2"
+        );
+        assert_eq!(canonical_url("https://example.com"), "https://example.com/");
+        assert_eq!(canonical_url(" not a url "), "not a url");
+    }
+
+    #[test]
+    fn identity_hash_is_stable_and_ignores_wording() {
+        // printf 'github\nSTUDENT\nhttps://example.com/' | sha256sum
+        assert_eq!(
+            Coupon::identity_hash("GitHub", "STUDENT", "https://example.com"),
+            "94a60929b726de6ee8eece0d2878ad3bd04f0094885d24a469fe59dfdaac05ce"
+        );
+
+        let original = coupon("GitHub", None, "GitHub Student Pack");
+        let reworded = Coupon::new(
+            "50% off GitHub Student Pack".to_string(),
+            "Another description".to_string(),
+            Some(50.0),
+            " student ".to_string(),
+            "http://www.example.com/?utm_campaign=spring".to_string(),
+            "GitHub".to_string(),
+            None,
+        );
+        assert_eq!(original.hash, reworded.hash);
+
+        let other_code = Coupon::new(
+            "GitHub Student Pack".to_string(),
+            String::new(),
+            None,
+            "TEACHER".to_string(),
+            "https://example.com".to_string(),
+            "GitHub".to_string(),
+            None,
+        );
+        assert_ne!(original.hash, other_code.hash);
+    }
+}
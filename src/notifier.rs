@@ -0,0 +1,1233 @@
+use anyhow::{Context as AnyhowContext, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use lettre::{
+    message::{header::ContentType, Mailbox, MultiPart, SinglePart},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+use crate::config;
+use crate::discord::{self, SentMessage};
+use crate::models::{
+    AppState, Config, Coupon, CouponDigest, CouponEvent, DiscordMessage, NotificationFilter, NotifierBackend,
+    NotifierConfig, SmtpTls,
+};
+use crate::webhook;
+
+/// Timeout for requests to notification backends
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Trait for notification backends
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Returns the name of the notifier, used in logs
+    fn name(&self) -> &str;
+
+    /// Checks if coupons like this one should be sent to this notifier
+    fn accepts(&self, _coupon: &Coupon) -> bool {
+        true
+    }
+
+    /// Announce a new valid coupon. `idempotency_key` is the same for every attempt to
+    /// announce it, so backends that support it can drop retried duplicates.
+    async fn send_coupon(&self, coupon: &Coupon, idempotency_key: &str) -> Result<Delivery>;
+
+    /// Announce several new valid coupons, returning the outcome for each of them.
+    /// Backends that can group coupons into fewer messages override this;
+    /// by default they are sent one by one, skipping those this notifier already received.
+    async fn send_coupons(&self, announcements: &[Announcement<'_>]) -> Vec<Result<Delivery>> {
+        let mut results = Vec::with_capacity(announcements.len());
+        for announcement in announcements {
+            if announcement.was_delivered_to(self.name()) {
+                results.push(Ok(Delivery::default()));
+                continue;
+            }
+            results.push(self.send_coupon(announcement.coupon, announcement.idempotency_key).await);
+        }
+
+        results
+    }
+
+    /// Announce that a previously announced coupon no longer works
+    async fn send_dead_coupon(&self, coupon: &Coupon, reason: Option<&str>) -> Result<()>;
+
+    /// Update a stored post of a coupon that expired or stopped working.
+    /// Returns `true` if the post was deleted.
+    async fn retract_post(
+        &self,
+        _post: &DiscordMessage,
+        _coupon: &Coupon,
+        _status: &str,
+        _reason: Option<&str>,
+    ) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Restore a stored post of a coupon that works again
+    async fn restore_post(&self, _post: &DiscordMessage, _coupon: &Coupon) -> Result<()> {
+        Ok(())
+    }
+
+    /// Post a digest to its target, if the target asked for digests of its frequency
+    async fn send_digest(&self, _digest: &CouponDigest) -> Result<DigestDelivery> {
+        Ok(DigestDelivery::default())
+    }
+}
+
+/// A new valid coupon to announce
+#[derive(Debug, Clone, Copy)]
+pub struct Announcement<'a> {
+    pub coupon: &'a Coupon,
+    /// Key shared by every attempt to announce the coupon
+    pub idempotency_key: &'a str,
+    /// Targets that received the coupon in an earlier attempt and are skipped
+    pub delivered: &'a [String],
+}
+
+impl Announcement<'_> {
+    /// Checks if an earlier attempt already delivered the coupon to `target`
+    pub fn was_delivered_to(&self, target: &str) -> bool {
+        self.delivered.iter().any(|delivered| delivered == target)
+    }
+}
+
+/// Outcome of announcing a coupon
+#[derive(Debug, Default)]
+pub struct Delivery {
+    /// Targets the coupon was sent to: notifier names, or `discord:<target>` for Discord
+    pub delivered: Vec<String>,
+    /// Discord messages that can be edited or deleted later
+    pub messages: Vec<SentMessage>,
+    /// Targets the coupon could not be sent to, with the error
+    pub failed: Vec<(String, String)>,
+}
+
+impl Delivery {
+    /// A delivery to a single target that can't be edited later
+    fn single(target: &str) -> Self {
+        Self {
+            delivered: vec![target.to_string()],
+            ..Default::default()
+        }
+    }
+
+    /// Add the outcome of sending the coupon to more targets
+    pub fn extend(&mut self, other: Delivery) {
+        self.delivered.extend(other.delivered);
+        self.messages.extend(other.messages);
+        self.failed.extend(other.failed);
+    }
+
+    /// Describe the targets that failed, or `None` if all succeeded
+    pub fn error(&self) -> Option<String> {
+        if self.failed.is_empty() {
+            return None;
+        }
+
+        let errors: Vec<String> = self
+            .failed
+            .iter()
+            .map(|(target, error)| format!("{}: {}", target, error))
+            .collect();
+        Some(errors.join("; "))
+    }
+}
+
+/// Outcome of posting a digest
+#[derive(Debug, Default)]
+pub struct DigestDelivery {
+    /// Number of destinations the digest was posted to
+    pub targets: usize,
+    /// Discord messages listing the coupons, with the id of the coupon each one lists
+    pub messages: Vec<(i64, SentMessage)>,
+}
+
+/// Fans notifications out to every configured notifier
+pub struct NotifierSet {
+    notifiers: Vec<Box<dyn Notifier>>,
+}
+
+impl NotifierSet {
+    pub fn new(notifiers: Vec<Box<dyn Notifier>>) -> Self {
+        Self { notifiers }
+    }
+}
+
+#[async_trait]
+impl Notifier for NotifierSet {
+    fn name(&self) -> &str {
+        "all notifiers"
+    }
+
+    async fn send_coupon(&self, coupon: &Coupon, idempotency_key: &str) -> Result<Delivery> {
+        let announcement = Announcement {
+            coupon,
+            idempotency_key,
+            delivered: &[],
+        };
+        self.send_coupons(&[announcement]).await.remove(0)
+    }
+
+    /// Send each coupon to every notifier that accepts it. Notifiers that fail are
+    /// reported in the coupon's delivery next to the ones that succeeded, so a retry
+    /// can skip the latter.
+    async fn send_coupons(&self, announcements: &[Announcement<'_>]) -> Vec<Result<Delivery>> {
+        let mut deliveries: Vec<Delivery> = announcements.iter().map(|_| Delivery::default()).collect();
+
+        for notifier in &self.notifiers {
+            let (indices, accepted): (Vec<usize>, Vec<Announcement>) = announcements
+                .iter()
+                .enumerate()
+                .filter(|(_, announcement)| notifier.accepts(announcement.coupon))
+                .map(|(i, announcement)| (i, *announcement))
+                .unzip();
+            if accepted.is_empty() {
+                continue;
+            }
+
+            for (i, result) in indices.into_iter().zip(notifier.send_coupons(&accepted).await) {
+                match result {
+                    Ok(delivery) => deliveries[i].extend(delivery),
+                    Err(e) => {
+                        let coupon = &announcements[i].coupon.name;
+                        error!("Failed to notify {} of {}: {:#}", notifier.name(), coupon, e);
+                        deliveries[i].failed.push((notifier.name().to_string(), format!("{:#}", e)));
+                    }
+                }
+            }
+        }
+
+        deliveries.into_iter().map(Ok).collect()
+    }
+
+    async fn send_dead_coupon(&self, coupon: &Coupon, reason: Option<&str>) -> Result<()> {
+        for notifier in self.notifiers.iter().filter(|notifier| notifier.accepts(coupon)) {
+            if let Err(e) = notifier.send_dead_coupon(coupon, reason).await {
+                error!("Failed to notify {} of dead coupon {}: {:#}", notifier.name(), coupon.name, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn retract_post(
+        &self,
+        post: &DiscordMessage,
+        coupon: &Coupon,
+        status: &str,
+        reason: Option<&str>,
+    ) -> Result<bool> {
+        let mut deleted = false;
+        let mut errors = Vec::new();
+        for notifier in &self.notifiers {
+            match notifier.retract_post(post, coupon, status, reason).await {
+                Ok(post_deleted) => deleted |= post_deleted,
+                Err(e) => {
+                    error!("Failed to retract post of {} on {}: {:#}", coupon.name, notifier.name(), e);
+                    errors.push(format!("{}: {:#}", notifier.name(), e));
+                }
+            }
+        }
+
+        combine_errors(errors)?;
+        Ok(deleted)
+    }
+
+    async fn restore_post(&self, post: &DiscordMessage, coupon: &Coupon) -> Result<()> {
+        let mut errors = Vec::new();
+        for notifier in &self.notifiers {
+            if let Err(e) = notifier.restore_post(post, coupon).await {
+                error!("Failed to restore post of {} on {}: {:#}", coupon.name, notifier.name(), e);
+                errors.push(format!("{}: {:#}", notifier.name(), e));
+            }
+        }
+
+        combine_errors(errors)
+    }
+
+    async fn send_digest(&self, digest: &CouponDigest) -> Result<DigestDelivery> {
+        let mut sent = DigestDelivery::default();
+        let mut errors = Vec::new();
+        for notifier in &self.notifiers {
+            match notifier.send_digest(digest).await {
+                Ok(delivery) => {
+                    sent.targets += delivery.targets;
+                    sent.messages.extend(delivery.messages);
+                }
+                Err(e) => {
+                    error!("Failed to send digest to {}: {:#}", notifier.name(), e);
+                    errors.push(format!("{}: {:#}", notifier.name(), e));
+                }
+            }
+        }
+
+        combine_errors(errors)?;
+        Ok(sent)
+    }
+}
+
+/// Turn the errors of the notifiers in a set into one error naming each of them
+fn combine_errors(errors: Vec<String>) -> Result<()> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(errors.join("; ")))
+    }
+}
+
+/// Backend-independent content of a notification
+struct Notice<'a> {
+    coupon: &'a Coupon,
+    /// Why a dead coupon stopped working; `None` for new coupons
+    dead: Option<Option<&'a str>>,
+    /// Key shared by retries of the same announcement
+    idempotency_key: Option<&'a str>,
+}
+
+impl<'a> Notice<'a> {
+    fn coupon(coupon: &'a Coupon, idempotency_key: &'a str) -> Self {
+        Self {
+            coupon,
+            dead: None,
+            idempotency_key: Some(idempotency_key),
+        }
+    }
+
+    fn dead_coupon(coupon: &'a Coupon, reason: Option<&'a str>) -> Self {
+        Self {
+            coupon,
+            dead: Some(reason),
+            idempotency_key: None,
+        }
+    }
+
+    /// Event sent by the generic webhook
+    fn event(&self) -> CouponEvent {
+        match self.dead {
+            None => CouponEvent::Posted,
+            Some(_) => CouponEvent::Invalidated,
+        }
+    }
+
+    /// Headline, also used as email subject
+    fn title(&self) -> String {
+        match self.dead {
+            None => format!("✅ {} AI Coupon", self.coupon.name),
+            Some(_) => format!("❌ Coupon no longer valid: {}", self.coupon.name),
+        }
+    }
+
+    /// Detail lines shown below the title
+    fn details(&self) -> Vec<(&'static str, String)> {
+        let coupon = self.coupon;
+        let mut details = Vec::new();
+
+        if let Some(discount) = coupon.discount_percentage {
+            details.push(("Discount", format!("{}%", discount)));
+        }
+        details.push(("Code", coupon.code.clone()));
+        details.push(("Source", coupon.source.clone()));
+
+        if let Some(reason) = self.dead.flatten() {
+            details.push(("Reason", reason.to_string()));
+        } else if let Some(expiry) = coupon.expiry {
+            details.push(("Expires", expiry.format("%Y-%m-%d").to_string()));
+        }
+
+        details
+    }
+
+    /// Plain text rendering
+    fn text(&self) -> String {
+        let mut text = format!("{}\n{}\n\n", self.title(), self.coupon.description);
+        for (label, value) in self.details() {
+            text.push_str(&format!("{}: {}\n", label, value));
+        }
+        text.push_str(&self.coupon.url);
+        text
+    }
+
+    /// HTML rendering, understood by Telegram, Matrix and email clients
+    fn html(&self) -> String {
+        let mut html = format!(
+            "<b>{}</b><br>\n{}<br>\n",
+            escape_html(&self.title()),
+            escape_html(&self.coupon.description)
+        );
+        for (label, value) in self.details() {
+            let value = if label == "Code" {
+                format!("<code>{}</code>", escape_html(&value))
+            } else {
+                escape_html(&value)
+            };
+            html.push_str(&format!("<b>{}:</b> {}<br>\n", label, value));
+        }
+        html.push_str(&format!(
+            "<a href=\"{}\">Apply here</a>",
+            escape_html(&self.coupon.url)
+        ));
+        html
+    }
+
+    /// Slack mrkdwn rendering
+    fn mrkdwn(&self) -> String {
+        let mut text = format!(
+            "*{}*\n{}\n",
+            escape_slack(&self.title()),
+            escape_slack(&self.coupon.description)
+        );
+        for (label, value) in self.details() {
+            let value = if label == "Code" {
+                format!("`{}`", escape_slack(&value))
+            } else {
+                escape_slack(&value)
+            };
+            text.push_str(&format!("*{}:* {}\n", label, value));
+        }
+        text.push_str(&format!("<{}|Apply here>", self.coupon.url));
+        text
+    }
+}
+
+/// Escape text for HTML message bodies
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Escape the control characters of Slack's mrkdwn
+fn escape_slack(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// A backend that sends each coupon as a single message rendered from a `Notice`.
+/// Backends only implement `deliver`; the `Notifier` methods are shared.
+#[async_trait]
+trait NoticeBackend: Send + Sync {
+    /// Name of the `[[notifiers]]` entry
+    fn name(&self) -> &str;
+
+    /// Rules deciding which coupons the backend receives
+    fn filter(&self) -> &NotificationFilter;
+
+    /// Send a rendered notification
+    async fn deliver(&self, notice: &Notice<'_>) -> Result<()>;
+}
+
+#[async_trait]
+impl<T: NoticeBackend> Notifier for T {
+    fn name(&self) -> &str {
+        NoticeBackend::name(self)
+    }
+
+    fn accepts(&self, coupon: &Coupon) -> bool {
+        self.filter().matches(coupon)
+    }
+
+    async fn send_coupon(&self, coupon: &Coupon, idempotency_key: &str) -> Result<Delivery> {
+        self.deliver(&Notice::coupon(coupon, idempotency_key)).await?;
+        Ok(Delivery::single(NoticeBackend::name(self)))
+    }
+
+    async fn send_dead_coupon(&self, coupon: &Coupon, reason: Option<&str>) -> Result<()> {
+        self.deliver(&Notice::dead_coupon(coupon, reason)).await
+    }
+}
+
+/// Slack incoming webhook notifier
+pub struct SlackNotifier {
+    name: String,
+    webhook_url: String,
+    filter: NotificationFilter,
+    client: Client,
+}
+
+impl SlackNotifier {
+    pub fn new(name: &str, webhook_url: &str, filter: NotificationFilter, client: Client) -> Self {
+        Self {
+            name: name.to_string(),
+            webhook_url: webhook_url.to_string(),
+            filter,
+            client,
+        }
+    }
+}
+
+#[async_trait]
+impl NoticeBackend for SlackNotifier {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn filter(&self) -> &NotificationFilter {
+        &self.filter
+    }
+
+    async fn deliver(&self, notice: &Notice<'_>) -> Result<()> {
+        self.client
+            .post(&self.webhook_url)
+            .json(&json!({
+                "text": notice.mrkdwn(),
+                "unfurl_links": false,
+            }))
+            .send()
+            .await
+            .context("Failed to reach Slack webhook")?
+            .error_for_status()
+            .context("Slack webhook rejected the message")?;
+
+        Ok(())
+    }
+}
+
+/// Telegram Bot API notifier
+pub struct TelegramNotifier {
+    name: String,
+    api_url: String,
+    bot_token: String,
+    chat_id: String,
+    filter: NotificationFilter,
+    client: Client,
+}
+
+/// Envelope of every Telegram Bot API response
+#[derive(Debug, Deserialize)]
+struct TelegramResponse {
+    ok: bool,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+impl TelegramNotifier {
+    pub fn new(
+        name: &str,
+        api_url: &str,
+        bot_token: &str,
+        chat_id: &str,
+        filter: NotificationFilter,
+        client: Client,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            api_url: api_url.trim_end_matches('/').to_string(),
+            bot_token: bot_token.to_string(),
+            chat_id: chat_id.to_string(),
+            filter,
+            client,
+        }
+    }
+}
+
+#[async_trait]
+impl NoticeBackend for TelegramNotifier {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn filter(&self) -> &NotificationFilter {
+        &self.filter
+    }
+
+    async fn deliver(&self, notice: &Notice<'_>) -> Result<()> {
+        // Telegram's HTML mode has no <br>, line breaks are kept as-is
+        let text = notice.html().replace("<br>", "");
+
+        let response: TelegramResponse = self.client
+            .post(format!("{}/bot{}/sendMessage", self.api_url, self.bot_token))
+            .json(&json!({
+                "chat_id": self.chat_id,
+                "text": text,
+                "parse_mode": "HTML",
+                "disable_web_page_preview": true,
+            }))
+            .send()
+            .await
+            .context("Failed to reach the Telegram Bot API")?
+            .json()
+            .await
+            .context("Invalid response from the Telegram Bot API")?;
+
+        if !response.ok {
+            return Err(anyhow::anyhow!(
+                "Telegram rejected the message: {}",
+                response.description.unwrap_or_else(|| "no description".to_string())
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Matrix client-server API notifier
+pub struct MatrixNotifier {
+    name: String,
+    homeserver: Url,
+    access_token: String,
+    room_id: String,
+    filter: NotificationFilter,
+    client: Client,
+    /// Makes transaction ids unique within this process
+    next_txn: AtomicU64,
+}
+
+impl MatrixNotifier {
+    pub fn new(
+        name: &str,
+        homeserver: &str,
+        access_token: &str,
+        room_id: &str,
+        filter: NotificationFilter,
+        client: Client,
+    ) -> Result<Self> {
+        let homeserver = Url::parse(homeserver)
+            .with_context(|| format!("Invalid Matrix homeserver URL: {}", homeserver))?;
+
+        Ok(Self {
+            name: name.to_string(),
+            homeserver,
+            access_token: access_token.to_string(),
+            room_id: room_id.to_string(),
+            filter,
+            client,
+            next_txn: AtomicU64::new(0),
+        })
+    }
+}
+
+#[async_trait]
+impl NoticeBackend for MatrixNotifier {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn filter(&self) -> &NotificationFilter {
+        &self.filter
+    }
+
+    async fn deliver(&self, notice: &Notice<'_>) -> Result<()> {
+        // Transaction ids let the homeserver drop retried duplicates
+        let txn_id = match notice.idempotency_key {
+            Some(key) => format!("rin-{}", key),
+            None => format!(
+                "rin-{}-{}",
+                Utc::now().timestamp_millis(),
+                self.next_txn.fetch_add(1, Ordering::Relaxed)
+            ),
+        };
+
+        let mut url = self.homeserver.clone();
+        url.path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("Matrix homeserver URL can't be a base"))?
+            .pop_if_empty()
+            .extend(["_matrix", "client", "v3", "rooms", self.room_id.as_str()])
+            .extend(["send", "m.room.message", txn_id.as_str()]);
+
+        self.client
+            .put(url)
+            .bearer_auth(&self.access_token)
+            .json(&json!({
+                "msgtype": "m.text",
+                "body": notice.text(),
+                "format": "org.matrix.custom.html",
+                "formatted_body": notice.html(),
+            }))
+            .send()
+            .await
+            .context("Failed to reach the Matrix homeserver")?
+            .error_for_status()
+            .context("Matrix homeserver rejected the message")?;
+
+        Ok(())
+    }
+}
+
+/// SMTP email notifier
+pub struct EmailNotifier {
+    name: String,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    filter: NotificationFilter,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl EmailNotifier {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        name: &str,
+        smtp_host: &str,
+        smtp_port: u16,
+        smtp_tls: SmtpTls,
+        credentials: Option<(String, String)>,
+        from: &str,
+        to: &[String],
+        filter: NotificationFilter,
+    ) -> Result<Self> {
+        let from = from.parse::<Mailbox>()
+            .with_context(|| format!("Invalid from address: {}", from))?;
+        let to = to
+            .iter()
+            .map(|address| {
+                address.parse::<Mailbox>()
+                    .with_context(|| format!("Invalid to address: {}", address))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut builder = match smtp_tls {
+            SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(smtp_host)?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(smtp_host)?,
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(smtp_host),
+        }
+        .port(smtp_port)
+        .timeout(Some(NOTIFY_TIMEOUT));
+
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            from,
+            to,
+            filter,
+            transport: builder.build(),
+        })
+    }
+}
+
+#[async_trait]
+impl NoticeBackend for EmailNotifier {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn filter(&self) -> &NotificationFilter {
+        &self.filter
+    }
+
+    async fn deliver(&self, notice: &Notice<'_>) -> Result<()> {
+        let mut message = Message::builder()
+            .from(self.from.clone())
+            .subject(notice.title());
+        for to in &self.to {
+            message = message.to(to.clone());
+        }
+
+        let message = message
+            .multipart(
+                MultiPart::alternative()
+                    .singlepart(
+                        SinglePart::builder()
+                            .header(ContentType::TEXT_PLAIN)
+                            .body(notice.text()),
+                    )
+                    .singlepart(
+                        SinglePart::builder()
+                            .header(ContentType::TEXT_HTML)
+                            .body(notice.html()),
+                    ),
+            )
+            .context("Failed to build email")?;
+
+        self.transport
+            .send(message)
+            .await
+            .context("Failed to send email")?;
+
+        Ok(())
+    }
+}
+
+/// Generic JSON webhook notifier, posting signed events in the format of `[[webhooks]]`
+pub struct WebhookNotifier {
+    name: String,
+    url: String,
+    secret: String,
+    headers: HashMap<String, String>,
+    filter: NotificationFilter,
+    client: Client,
+}
+
+impl WebhookNotifier {
+    pub fn new(
+        name: &str,
+        url: &str,
+        secret: &str,
+        headers: HashMap<String, String>,
+        filter: NotificationFilter,
+        client: Client,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            url: url.to_string(),
+            secret: secret.to_string(),
+            headers,
+            filter,
+            client,
+        }
+    }
+}
+
+#[async_trait]
+impl NoticeBackend for WebhookNotifier {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn filter(&self) -> &NotificationFilter {
+        &self.filter
+    }
+
+    /// Send a single signed attempt; the outbox retries failed announcements
+    async fn deliver(&self, notice: &Notice<'_>) -> Result<()> {
+        // Retried announcements keep their event id, so receivers can drop duplicates
+        let event_id = match notice.idempotency_key {
+            Some(key) => key.to_string(),
+            None => format!("{:032x}", rand::random::<u128>()),
+        };
+        let body = webhook::event_body(notice.event(), &event_id, notice.coupon, notice.dead.flatten())
+            .context("Failed to serialize webhook event")?;
+
+        let mut request =
+            webhook::event_request(&self.client, &self.url, &self.secret, notice.event(), &event_id, &body);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        if let Some(key) = notice.idempotency_key {
+            request = request.header("Idempotency-Key", key);
+        }
+
+        request
+            .send()
+            .await
+            .context("Failed to reach webhook")?
+            .error_for_status()
+            .context("Webhook rejected the event")?;
+
+        Ok(())
+    }
+}
+
+/// Create the notifier for a `[[notifiers]]` entry
+pub fn create_notifier(notifier: &NotifierConfig, client: &Client) -> Result<Box<dyn Notifier>> {
+    let name = notifier.name.as_str();
+    let filter = notifier.filter.clone();
+
+    let notifier: Box<dyn Notifier> = match &notifier.backend {
+        NotifierBackend::Slack { webhook_url } => {
+            Box::new(SlackNotifier::new(name, webhook_url, filter, client.clone()))
+        }
+        NotifierBackend::Telegram { bot_token, chat_id, api_url } => Box::new(TelegramNotifier::new(
+            name,
+            api_url,
+            bot_token,
+            chat_id,
+            filter,
+            client.clone(),
+        )),
+        NotifierBackend::Matrix { homeserver, access_token, room_id } => Box::new(MatrixNotifier::new(
+            name,
+            homeserver,
+            access_token,
+            room_id,
+            filter,
+            client.clone(),
+        )?),
+        NotifierBackend::Email { smtp_host, smtp_port, smtp_tls, username, password, from, to } => {
+            let credentials = username
+                .clone()
+                .map(|username| (username, password.clone().unwrap_or_default()));
+            Box::new(EmailNotifier::new(
+                name,
+                smtp_host,
+                *smtp_port,
+                *smtp_tls,
+                credentials,
+                from,
+                to,
+                filter,
+            )?)
+        }
+        NotifierBackend::Webhook { url, secret, headers } => Box::new(WebhookNotifier::new(
+            name,
+            url,
+            secret,
+            headers.clone(),
+            filter,
+            client.clone(),
+        )),
+    };
+
+    Ok(notifier)
+}
+
+/// Initialize Discord, if configured, and every `[[notifiers]]` backend
+pub async fn initialize_notifiers(
+    config: &Config,
+    state: &Arc<Mutex<AppState>>,
+) -> Result<Arc<dyn Notifier>> {
+    info!("Initializing notifiers");
+
+    let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+
+    let discord_configured = config::get_discord_token().is_ok()
+        || config.discord.webhook_url.is_some()
+        || config.discord.targets.iter().any(|target| target.webhook_url.is_some());
+    if discord_configured {
+        let discord_client = discord::initialize_discord(config, state)
+            .await
+            .context("Failed to initialize Discord client")?;
+        notifiers.push(Box::new(discord_client));
+    }
+
+    let client = Client::builder()
+        .timeout(NOTIFY_TIMEOUT)
+        .user_agent(&config.scraping.user_agent)
+        .build()
+        .context("Failed to build notification HTTP client")?;
+
+    for notifier in &config.notifiers {
+        notifiers.push(
+            create_notifier(notifier, &client)
+                .with_context(|| format!("Invalid notifier {}", notifier.name))?,
+        );
+        info!("Initialized notifier {}", notifier.name);
+    }
+
+    if notifiers.is_empty() {
+        warn!("No notifiers configured, coupons won't be announced anywhere");
+    }
+
+    Ok(Arc::new(NotifierSet::new(notifiers)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DigestFrequency;
+    use crate::testing::{record_requests, serve_smtp};
+    use serde_json::Value;
+
+    fn coupon() -> Coupon {
+        Coupon::new(
+            "Cursor AI Student Discount".to_string(),
+            "Free Pro plan for verified <students>".to_string(),
+            Some(100.0),
+            "STUDENT".to_string(),
+            "https://cursor.sh/student".to_string(),
+            "Cursor AI".to_string(),
+            None,
+        )
+    }
+
+    fn client() -> Client {
+        Client::builder().timeout(NOTIFY_TIMEOUT).build().unwrap()
+    }
+
+    fn json_body(body: &str) -> Value {
+        serde_json::from_str(body).expect("Request body is not JSON")
+    }
+
+    #[tokio::test]
+    async fn slack_posts_mrkdwn_text() {
+        let (base_url, requests) = record_requests("{}").await;
+        let notifier = SlackNotifier::new(
+            "slack",
+            &format!("{}/services/T000/B000/XXX", base_url),
+            NotificationFilter::default(),
+            client(),
+        );
+
+        let delivery = notifier.send_coupon(&coupon(), "post-1").await.unwrap();
+        assert_eq!(delivery.delivered, vec!["slack"]);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/services/T000/B000/XXX");
+        let text = json_body(&requests[0].body)["text"].as_str().unwrap().to_string();
+        assert!(text.starts_with("*✅ Cursor AI Student Discount AI Coupon*"));
+        assert!(text.contains("verified &lt;students&gt;"));
+        assert!(text.contains("*Code:* `STUDENT`"));
+        assert!(text.ends_with("<https://cursor.sh/student|Apply here>"));
+    }
+
+    #[tokio::test]
+    async fn telegram_sends_html_message_to_chat() {
+        let (base_url, requests) = record_requests(r#"{"ok": true, "result": {}}"#).await;
+        let notifier = TelegramNotifier::new(
+            "telegram",
+            &base_url,
+            "123:ABC",
+            "-10042",
+            NotificationFilter::default(),
+            client(),
+        );
+
+        notifier.send_dead_coupon(&coupon(), Some("Code rejected")).await.unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[0].path, "/bot123:ABC/sendMessage");
+        let body = json_body(&requests[0].body);
+        assert_eq!(body["chat_id"], "-10042");
+        assert_eq!(body["parse_mode"], "HTML");
+        let text = body["text"].as_str().unwrap();
+        assert!(text.starts_with("<b>❌ Coupon no longer valid: Cursor AI Student Discount</b>\n"));
+        assert!(text.contains("<b>Reason:</b> Code rejected\n"));
+        assert!(!text.contains("<br>"));
+    }
+
+    #[tokio::test]
+    async fn telegram_reports_rejected_messages() {
+        let (base_url, _) =
+            record_requests(r#"{"ok": false, "description": "Bad Request: chat not found"}"#).await;
+        let notifier = TelegramNotifier::new(
+            "telegram",
+            &base_url,
+            "123:ABC",
+            "1",
+            NotificationFilter::default(),
+            client(),
+        );
+
+        let error = notifier.send_coupon(&coupon(), "post-1").await.unwrap_err();
+        assert!(error.to_string().contains("chat not found"));
+    }
+
+    #[tokio::test]
+    async fn matrix_puts_message_into_room() {
+        let (base_url, requests) = record_requests(r#"{"event_id": "$event"}"#).await;
+        let notifier = MatrixNotifier::new(
+            "matrix",
+            &base_url,
+            "secret-token",
+            "!room:example.org",
+            NotificationFilter::default(),
+            client(),
+        )
+        .unwrap();
+
+        notifier.send_coupon(&coupon(), "post-1").await.unwrap();
+        notifier.send_coupon(&coupon(), "post-2").await.unwrap();
+        notifier.send_dead_coupon(&coupon(), None).await.unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[0].method, "PUT");
+        assert_eq!(
+            requests[0].path,
+            "/_matrix/client/v3/rooms/!room:example.org/send/m.room.message/rin-post-1"
+        );
+        assert_ne!(requests[0].path, requests[1].path, "transaction ids must differ");
+        assert!(requests[2]
+            .path
+            .starts_with("/_matrix/client/v3/rooms/!room:example.org/send/m.room.message/rin-"));
+        assert_eq!(requests[0].headers["authorization"], "Bearer secret-token");
+
+        let body = json_body(&requests[0].body);
+        assert_eq!(body["msgtype"], "m.text");
+        assert_eq!(body["format"], "org.matrix.custom.html");
+        assert!(body["body"].as_str().unwrap().contains("Code: STUDENT"));
+        assert!(body["formatted_body"].as_str().unwrap().contains("<code>STUDENT</code>"));
+    }
+
+    #[tokio::test]
+    async fn webhook_posts_event_json_with_headers() {
+        let (base_url, requests) = record_requests("{}").await;
+        let headers = HashMap::from([("x-api-key".to_string(), "hunter2".to_string())]);
+        let notifier = WebhookNotifier::new(
+            "webhook",
+            &format!("{}/hooks/coupons", base_url),
+            "s3cret",
+            headers,
+            NotificationFilter::default(),
+            client(),
+        );
+
+        notifier.send_coupon(&coupon(), "post-1").await.unwrap();
+        notifier.send_dead_coupon(&coupon(), Some("Expired")).await.unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[0].headers["x-api-key"], "hunter2");
+        assert_eq!(requests[0].headers["idempotency-key"], "post-1");
+        assert_eq!(requests[0].headers["x-rin-delivery"], "post-1");
+        assert!(!requests[1].headers.contains_key("idempotency-key"));
+
+        // Signed like the lifecycle events of [[webhooks]]
+        let timestamp: i64 = requests[0].headers["x-rin-timestamp"].to_str().unwrap().parse().unwrap();
+        assert_eq!(
+            requests[0].headers["x-rin-signature"],
+            webhook::sign("s3cret", timestamp, &requests[0].body).as_str()
+        );
+
+        let posted = json_body(&requests[0].body);
+        assert_eq!(posted["type"], "coupon.posted");
+        assert_eq!(posted["id"], "post-1");
+        assert_eq!(posted["coupon"]["code"], "STUDENT");
+        assert!(posted.get("reason").is_none());
+
+        let dead = json_body(&requests[1].body);
+        assert_eq!(dead["type"], "coupon.invalidated");
+        assert_eq!(requests[1].headers["x-rin-event"], "coupon.invalidated");
+        assert_eq!(dead["reason"], "Expired");
+    }
+
+    #[tokio::test]
+    async fn email_sends_text_and_html_parts() {
+        let (port, messages) = serve_smtp().await;
+        let notifier = EmailNotifier::new(
+            "email",
+            "127.0.0.1",
+            port,
+            SmtpTls::None,
+            None,
+            "RinKokonoe <bot@example.com>",
+            &["deals@example.com".to_string()],
+            NotificationFilter::default(),
+        )
+        .unwrap();
+
+        notifier.send_coupon(&coupon(), "post-1").await.unwrap();
+
+        let messages = messages.lock().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].contains("To: deals@example.com"));
+        assert!(messages[0].contains("Content-Type: text/plain"));
+        assert!(messages[0].contains("Content-Type: text/html"));
+    }
+
+    #[tokio::test]
+    async fn notifier_set_only_sends_to_accepting_notifiers() {
+        let (base_url, requests) = record_requests("{}").await;
+        let matching = NotificationFilter {
+            sources: vec!["Cursor AI".to_string()],
+            ..Default::default()
+        };
+        let other = NotificationFilter {
+            sources: vec!["GitHub".to_string()],
+            ..Default::default()
+        };
+        let notifiers = NotifierSet::new(vec![
+            Box::new(WebhookNotifier::new("cursor", &format!("{}/cursor", base_url), "s3cret", HashMap::new(), matching, client())),
+            Box::new(WebhookNotifier::new("github", &format!("{}/github", base_url), "s3cret", HashMap::new(), other, client())),
+            Box::new(SlackNotifier::new("broken", "http://127.0.0.1:1/", NotificationFilter::default(), client())),
+        ]);
+
+        let delivery = notifiers.send_coupon(&coupon(), "post-1").await.unwrap();
+        assert_eq!(delivery.delivered, vec!["cursor"]);
+        assert_eq!(delivery.failed.len(), 1);
+        assert_eq!(delivery.failed[0].0, "broken");
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/cursor");
+    }
+
+    /// Notifier counting the posts and digests it handled, failing them if `broken`
+    struct PostRecorder {
+        name: &'static str,
+        broken: bool,
+        calls: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    impl PostRecorder {
+        fn record(&self, call: &str) -> Result<()> {
+            self.calls.lock().unwrap().push(format!("{} {}", self.name, call));
+            if self.broken {
+                anyhow::bail!("Target is down");
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl Notifier for PostRecorder {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        async fn send_coupon(&self, _coupon: &Coupon, _idempotency_key: &str) -> Result<Delivery> {
+            Ok(Delivery::default())
+        }
+
+        async fn send_dead_coupon(&self, _coupon: &Coupon, _reason: Option<&str>) -> Result<()> {
+            Ok(())
+        }
+
+        async fn retract_post(
+            &self,
+            _post: &DiscordMessage,
+            _coupon: &Coupon,
+            _status: &str,
+            _reason: Option<&str>,
+        ) -> Result<bool> {
+            self.record("retract")?;
+            Ok(true)
+        }
+
+        async fn restore_post(&self, _post: &DiscordMessage, _coupon: &Coupon) -> Result<()> {
+            self.record("restore")
+        }
+
+        async fn send_digest(&self, _digest: &CouponDigest) -> Result<DigestDelivery> {
+            self.record("digest")?;
+            Ok(DigestDelivery {
+                targets: 1,
+                messages: Vec::new(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn notifier_set_keeps_going_after_a_failing_notifier() {
+        let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let notifiers = NotifierSet::new(vec![
+            Box::new(PostRecorder { name: "broken", broken: true, calls: calls.clone() }),
+            Box::new(PostRecorder { name: "working", broken: false, calls: calls.clone() }),
+        ]);
+        let post = DiscordMessage {
+            coupon_id: 1,
+            message_id: "1".to_string(),
+            target: "default".to_string(),
+            channel_id: "2".to_string(),
+            via_webhook: true,
+            posted_at: Utc::now(),
+            embed_index: 0,
+            embed_count: 1,
+            field_index: None,
+        };
+        let digest = CouponDigest {
+            frequency: DigestFrequency::Daily,
+            target: "default".to_string(),
+            window_start: Utc::now(),
+            window_end: Utc::now(),
+            coupons: vec![coupon()],
+            expiring_before: Utc::now(),
+        };
+
+        let error = notifiers.retract_post(&post, &coupon(), "Expired", None).await.unwrap_err();
+        assert_eq!(error.to_string(), "broken: Target is down");
+        assert!(notifiers.restore_post(&post, &coupon()).await.is_err());
+        assert!(notifiers.send_digest(&digest).await.is_err());
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                "broken retract", "working retract",
+                "broken restore", "working restore",
+                "broken digest", "working digest",
+            ]
+        );
+    }
+}
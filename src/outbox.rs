@@ -0,0 +1,391 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+
+use crate::db;
+use crate::metrics;
+use crate::models::{AppState, Config, Coupon, CouponEvent, OutboxEntry};
+use crate::notifier::{Announcement, Delivery, Notifier};
+use crate::webhook::WebhookEmitter;
+
+/// Delay before the first retry of an announcement; doubled after every failed attempt
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Longest delay between two attempts
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+
+/// Most announcements delivered per poll
+const BATCH_SIZE: i64 = 100;
+
+/// Delay before the attempt following `attempts` failed ones
+fn retry_delay(attempts: i64) -> Duration {
+    let doublings = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    (INITIAL_RETRY_DELAY * 2u32.pow(doublings)).min(MAX_RETRY_DELAY)
+}
+
+/// Check whether a queued announcement should still be sent, skipping it in the outbox
+/// if not. Returns the coupon to announce.
+async fn check_entry(db_pool: &SqlitePool, entry: &OutboxEntry) -> Result<Option<Coupon>> {
+    // The coupon may have changed while the announcement waited
+    let reason = match db::get_coupon_by_id(db_pool, entry.coupon_id).await? {
+        None => "Coupon no longer exists",
+        Some(coupon) if !coupon.is_valid => "Coupon is no longer valid",
+        Some(coupon) if coupon.is_expired() => "Coupon expired",
+        Some(coupon) if coupon.is_posted => "Coupon was already posted",
+        Some(coupon) => return Ok(Some(coupon)),
+    };
+
+    db::skip_outbox_entry(db_pool, entry.id, reason).await?;
+    Ok(None)
+}
+
+/// Record the outcome of delivering a queued announcement in the outbox. The announcement
+/// stays pending until every target accepting the coupon received it; `delivered` are the
+/// targets that did so in earlier attempts. Completed announcements emit `coupon.posted`.
+async fn record_delivery(
+    db_pool: &SqlitePool,
+    webhooks: &WebhookEmitter,
+    entry: &OutboxEntry,
+    coupon: &Coupon,
+    delivered: &[String],
+    result: Result<Delivery>,
+    max_attempts: u32,
+) -> Result<()> {
+    let (delivery, error) = match result {
+        Ok(delivery) => {
+            let error = delivery.error();
+            (delivery, error)
+        }
+        Err(e) => (Delivery::default(), Some(format!("{:#}", e))),
+    };
+
+    let Some(error) = error else {
+        if delivery.delivered.is_empty() && delivered.is_empty() {
+            debug!("No notification target accepts coupon {}", coupon.name);
+            return db::skip_outbox_entry(db_pool, entry.id, "No notification target accepts the coupon").await;
+        }
+
+        db::complete_outbox_entry(db_pool, entry, &delivery.delivered, &delivery.messages).await?;
+        metrics::coupon_posted();
+        webhooks.emit(CouponEvent::Posted, coupon);
+        info!(
+            "Coupon sent to {} targets: {}",
+            delivered.len() + delivery.delivered.len(),
+            coupon.name
+        );
+        return Ok(());
+    };
+
+    let attempts = entry.attempts + 1;
+    let retry_at = if attempts < max_attempts as i64 {
+        let delay = retry_delay(attempts);
+        warn!(
+            "Failed to send coupon {} (attempt {}), retrying in {:?}: {}",
+            coupon.name, attempts, delay, error
+        );
+        Some(Utc::now() + chrono::Duration::seconds(delay.as_secs() as i64))
+    } else {
+        error!(
+            "Giving up on sending coupon {} after {} attempts: {}",
+            coupon.name, attempts, error
+        );
+        None
+    };
+
+    db::fail_outbox_attempt(db_pool, entry, &delivery.delivered, &delivery.messages, &error, retry_at).await
+}
+
+/// Deliver the announcements that are due at `now` together, so notifiers can batch them
+async fn dispatch_due(
+    db_pool: &SqlitePool,
+    notifier: &dyn Notifier,
+    webhooks: &WebhookEmitter,
+    now: DateTime<Utc>,
+    max_attempts: u32,
+) -> Result<()> {
+    let mut ready = Vec::new();
+    for entry in db::get_due_outbox_entries(db_pool, now, BATCH_SIZE).await? {
+        if let Some(coupon) = check_entry(db_pool, &entry).await? {
+            let delivered = db::get_outbox_deliveries(db_pool, entry.id).await?;
+            ready.push((entry, coupon, delivered));
+        }
+    }
+    if ready.is_empty() {
+        return Ok(());
+    }
+
+    let announcements: Vec<Announcement> = ready
+        .iter()
+        .map(|(entry, coupon, delivered)| Announcement {
+            coupon,
+            idempotency_key: &entry.idempotency_key,
+            delivered,
+        })
+        .collect();
+    let results = notifier.send_coupons(&announcements).await;
+
+    for ((entry, coupon, delivered), result) in ready.iter().zip(results) {
+        record_delivery(db_pool, webhooks, entry, coupon, delivered, result, max_attempts).await?;
+    }
+
+    Ok(())
+}
+
+/// Queue valid coupons that were never announced, e.g. ones found before the outbox
+/// existed or whose announcement was lost in a crash
+async fn enqueue_unposted(db_pool: &SqlitePool) -> Result<()> {
+    let mut queued = 0;
+    for coupon in db::get_valid_unposted_coupons(db_pool).await? {
+        let Some(coupon_id) = coupon.id else {
+            continue;
+        };
+
+        if db::enqueue_post(db_pool, coupon_id, &OutboxEntry::post_key(&coupon)).await? {
+            queued += 1;
+        }
+    }
+
+    if queued > 0 {
+        info!("Queued {} unposted coupons for announcement", queued);
+    }
+    Ok(())
+}
+
+/// Start the dispatcher that delivers queued coupon announcements
+pub async fn start_dispatcher(
+    state: Arc<Mutex<AppState>>,
+    notifier: Arc<dyn Notifier>,
+    webhooks: WebhookEmitter,
+    config: &Config,
+    shutdown: CancellationToken,
+) -> Result<JoinHandle<()>> {
+    let db_pool = state.lock().await.db_pool.clone();
+    enqueue_unposted(&db_pool).await?;
+
+    let poll_interval = Duration::from_secs(config.outbox.poll_interval);
+    let max_attempts = config.outbox.max_attempts;
+
+    let handle = tokio::spawn(async move {
+        while !shutdown.is_cancelled() {
+            if let Err(e) = dispatch_due(&db_pool, notifier.as_ref(), &webhooks, Utc::now(), max_attempts).await {
+                error!("Failed to dispatch coupon announcements: {}", e);
+            }
+
+            tokio::select! {
+                _ = sleep(poll_interval) => {}
+                _ = shutdown.cancelled() => {}
+            }
+        }
+    });
+
+    Ok(handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{NotificationFilter, OutboxStatus, WebhookConfig};
+    use crate::notifier::{NotifierSet, WebhookNotifier};
+    use crate::testing::{memory_database, record_requests_with};
+    use axum::http::StatusCode;
+    use reqwest::Client;
+    use std::collections::HashMap;
+
+    fn no_webhooks(pool: &SqlitePool) -> WebhookEmitter {
+        WebhookEmitter::new(&[], Client::new(), pool.clone(), CancellationToken::new())
+    }
+
+    async fn insert_valid_coupon(pool: &SqlitePool) -> Coupon {
+        let mut coupon = Coupon::new(
+            "Cursor Pro".to_string(),
+            String::new(),
+            Some(20.0),
+            "SAVE20".to_string(),
+            "https://cursor.com/pricing".to_string(),
+            "Cursor AI".to_string(),
+            None,
+        );
+        let coupon_id = db::insert_coupon(pool, &coupon).await.unwrap();
+        db::mark_valid_and_enqueue_post(pool, coupon_id, &OutboxEntry::post_key(&coupon), Utc::now())
+            .await
+            .unwrap();
+        coupon.id = Some(coupon_id);
+        coupon
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_the_maximum() {
+        assert_eq!(retry_delay(1), Duration::from_secs(30));
+        assert_eq!(retry_delay(2), Duration::from_secs(60));
+        assert_eq!(retry_delay(4), Duration::from_secs(240));
+        assert_eq!(retry_delay(100), MAX_RETRY_DELAY);
+    }
+
+    #[tokio::test]
+    async fn failed_announcements_are_retried_with_the_same_key() {
+        let pool = memory_database().await;
+        let webhooks = no_webhooks(&pool);
+        let (base_url, requests) = record_requests_with(vec![
+            (StatusCode::SERVICE_UNAVAILABLE, "{}"),
+            (StatusCode::OK, "{}"),
+        ])
+        .await;
+        let notifier = WebhookNotifier::new(
+            "webhook",
+            &base_url,
+            "s3cret",
+            HashMap::new(),
+            NotificationFilter::default(),
+            Client::new(),
+        );
+        let coupon = insert_valid_coupon(&pool).await;
+
+        // The first attempt fails and is scheduled for a retry
+        dispatch_due(&pool, &notifier, &webhooks, Utc::now(), 3).await.unwrap();
+        assert!(db::get_due_outbox_entries(&pool, Utc::now(), 10).await.unwrap().is_empty());
+
+        let later = Utc::now() + chrono::Duration::minutes(1);
+        let entry = db::get_due_outbox_entries(&pool, later, 10).await.unwrap().remove(0);
+        assert_eq!(entry.attempts, 1);
+        assert!(entry.last_error.is_some());
+
+        // The retry succeeds and marks the coupon posted
+        dispatch_due(&pool, &notifier, &webhooks, later, 3).await.unwrap();
+        let stored = db::get_coupon_by_id(&pool, coupon.id.unwrap()).await.unwrap().unwrap();
+        assert!(stored.is_posted);
+        assert!(db::get_due_outbox_entries(&pool, later, 10).await.unwrap().is_empty());
+
+        // Enqueueing the delivered announcement again is a no-op
+        assert!(!db::enqueue_post(&pool, coupon.id.unwrap(), &OutboxEntry::post_key(&coupon))
+            .await
+            .unwrap());
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        let key = OutboxEntry::post_key(&coupon);
+        assert_eq!(requests[0].headers["idempotency-key"], key.as_str());
+        assert_eq!(requests[1].headers["idempotency-key"], key.as_str());
+    }
+
+    #[tokio::test]
+    async fn partly_failed_announcements_are_retried_for_the_failed_targets() {
+        let pool = memory_database().await;
+        let webhooks = no_webhooks(&pool);
+        let (flaky_url, flaky_requests) = record_requests_with(vec![
+            (StatusCode::SERVICE_UNAVAILABLE, "{}"),
+            (StatusCode::OK, "{}"),
+        ])
+        .await;
+        let (stable_url, stable_requests) = record_requests_with(vec![(StatusCode::OK, "{}")]).await;
+        let notifier = NotifierSet::new(vec![
+            Box::new(WebhookNotifier::new("flaky", &flaky_url, "s3cret", HashMap::new(), NotificationFilter::default(), Client::new())),
+            Box::new(WebhookNotifier::new("stable", &stable_url, "s3cret", HashMap::new(), NotificationFilter::default(), Client::new())),
+        ]);
+        let coupon = insert_valid_coupon(&pool).await;
+
+        // One target fails: the announcement stays pending and the coupon unposted
+        dispatch_due(&pool, &notifier, &webhooks, Utc::now(), 3).await.unwrap();
+        let stored = db::get_coupon_by_id(&pool, coupon.id.unwrap()).await.unwrap().unwrap();
+        assert!(!stored.is_posted);
+
+        let later = Utc::now() + chrono::Duration::minutes(1);
+        let entry = db::get_due_outbox_entries(&pool, later, 10).await.unwrap().remove(0);
+        assert!(entry.last_error.unwrap().starts_with("flaky: "));
+        assert_eq!(db::get_outbox_deliveries(&pool, entry.id).await.unwrap(), vec!["stable"]);
+
+        // The retry only goes to the target that failed
+        dispatch_due(&pool, &notifier, &webhooks, later, 3).await.unwrap();
+        let stored = db::get_coupon_by_id(&pool, coupon.id.unwrap()).await.unwrap().unwrap();
+        assert!(stored.is_posted);
+        assert!(db::get_due_outbox_entries(&pool, later, 10).await.unwrap().is_empty());
+
+        assert_eq!(flaky_requests.lock().unwrap().len(), 2);
+        assert_eq!(stable_requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn giving_up_keeps_the_coupon_posted_where_it_was_delivered() {
+        let pool = memory_database().await;
+        let webhooks = no_webhooks(&pool);
+        let (failing_url, _) = record_requests_with(vec![(StatusCode::SERVICE_UNAVAILABLE, "{}")]).await;
+        let (stable_url, _) = record_requests_with(vec![(StatusCode::OK, "{}")]).await;
+        let notifier = NotifierSet::new(vec![
+            Box::new(WebhookNotifier::new("failing", &failing_url, "s3cret", HashMap::new(), NotificationFilter::default(), Client::new())),
+            Box::new(WebhookNotifier::new("stable", &stable_url, "s3cret", HashMap::new(), NotificationFilter::default(), Client::new())),
+        ]);
+        let coupon = insert_valid_coupon(&pool).await;
+
+        dispatch_due(&pool, &notifier, &webhooks, Utc::now(), 1).await.unwrap();
+
+        // Posts that went out can still be retracted later
+        let stored = db::get_coupon_by_id(&pool, coupon.id.unwrap()).await.unwrap().unwrap();
+        assert!(stored.is_posted);
+        let sizes = db::get_queue_sizes(&pool).await.unwrap();
+        assert_eq!(sizes.outbox_failed, 1);
+    }
+
+    #[tokio::test]
+    async fn announcements_of_invalidated_coupons_are_skipped() {
+        let pool = memory_database().await;
+        let webhooks = no_webhooks(&pool);
+        let (base_url, requests) = record_requests_with(vec![(StatusCode::OK, "{}")]).await;
+        let notifier = WebhookNotifier::new(
+            "webhook",
+            &base_url,
+            "s3cret",
+            HashMap::new(),
+            NotificationFilter::default(),
+            Client::new(),
+        );
+        let coupon = insert_valid_coupon(&pool).await;
+        db::update_validation_status(&pool, coupon.id.unwrap(), false).await.unwrap();
+
+        dispatch_due(&pool, &notifier, &webhooks, Utc::now(), 3).await.unwrap();
+        assert!(requests.lock().unwrap().is_empty());
+
+        // Becoming valid again queues the announcement once more
+        db::mark_valid_and_enqueue_post(&pool, coupon.id.unwrap(), &OutboxEntry::post_key(&coupon), Utc::now())
+            .await
+            .unwrap();
+        let entry = db::get_due_outbox_entries(&pool, Utc::now(), 10).await.unwrap().remove(0);
+        assert_eq!(entry.status, OutboxStatus::Pending);
+        assert_eq!(entry.attempts, 0);
+    }
+
+    #[tokio::test]
+    async fn delivered_announcements_emit_posted_events() {
+        let pool = memory_database().await;
+        let (base_url, _) = record_requests_with(vec![(StatusCode::OK, "{}")]).await;
+        let notifier = WebhookNotifier::new(
+            "webhook",
+            &base_url,
+            "s3cret",
+            HashMap::new(),
+            NotificationFilter::default(),
+            Client::new(),
+        );
+        let (events_url, events) = record_requests_with(vec![(StatusCode::OK, "{}")]).await;
+        let endpoint = WebhookConfig {
+            url: events_url,
+            secret: "s3cret".to_string(),
+            events: vec![CouponEvent::Posted],
+            max_attempts: 1,
+        };
+        let webhooks = WebhookEmitter::new(&[endpoint], Client::new(), pool.clone(), CancellationToken::new());
+        insert_valid_coupon(&pool).await;
+
+        dispatch_due(&pool, &notifier, &webhooks, Utc::now(), 3).await.unwrap();
+        webhooks.drain().await;
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].headers["x-rin-event"], "coupon.posted");
+    }
+}
@@ -1,187 +1,450 @@
-use anyhow::{Context as AnyhowContext, Result};
+use anyhow::Result;
 use chrono::{DateTime, Duration, Utc};
-use sqlx::SqlitePool;
+use sqlx::{types::Json, SqlitePool};
 use std::sync::Arc;
 use std::time::Duration as StdDuration;
-use tokio::sync::Mutex;
-use tokio::task::JoinHandle;
-use tokio::time::sleep;
+use tokio::sync::{Mutex, Semaphore};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::{sleep, timeout};
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 
 use crate::db;
-use crate::discord::DiscordClient;
-use crate::models::{AppState, Config, Coupon};
+use crate::dedup;
+use crate::metrics;
+use crate::models::{
+    AppState, Config, Coupon, CouponEvent, CouponListing, OutboxEntry, ScrapeRun, ScrapeStatus,
+    ScrapeSchedule,
+};
+use crate::notifier::Notifier;
+use crate::proxy::{FetchStats, HttpClient};
 use crate::scraper::Scraper;
 use crate::validator::Validator;
+use crate::webhook::WebhookEmitter;
+
+/// Longest the scheduler sleeps between checks, so cleanup still runs on time
+const MAX_SLEEP: StdDuration = StdDuration::from_secs(60 * 60);
+
+/// How long scraper runs are kept
+const SCRAPE_RUN_RETENTION: Duration = Duration::days(30);
+
+/// A scraper together with its schedule and next run time
+struct ScheduledScraper {
+    scraper: Arc<dyn Scraper>,
+    schedule: ScrapeSchedule,
+    next_run: DateTime<Utc>,
+}
 
 /// Scheduler for periodic tasks
 pub struct TaskScheduler {
     state: Arc<Mutex<AppState>>,
-    scrapers: Vec<Box<dyn Scraper>>,
-    validator: Validator,
-    discord_client: DiscordClient,
+    scrapers: Vec<Arc<dyn Scraper>>,
+    http_client: HttpClient,
+    validator: Arc<Validator>,
+    notifier: Arc<dyn Notifier>,
+    webhooks: WebhookEmitter,
     config: Arc<Config>,
+    shutdown: CancellationToken,
 }
 
 impl TaskScheduler {
     /// Create a new task scheduler
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         state: Arc<Mutex<AppState>>,
-        scrapers: Vec<Box<dyn Scraper>>,
+        scrapers: Vec<Arc<dyn Scraper>>,
+        http_client: HttpClient,
         validator: Validator,
-        discord_client: DiscordClient,
+        notifier: Arc<dyn Notifier>,
+        webhooks: WebhookEmitter,
         config: Arc<Config>,
+        shutdown: CancellationToken,
     ) -> Self {
         Self {
             state,
             scrapers,
-            validator,
-            discord_client,
+            http_client,
+            validator: Arc::new(validator),
+            notifier,
+            webhooks,
             config,
+            shutdown,
         }
     }
     
-    /// Start the scheduler
+    /// Start the scheduler. The task finishes once `shutdown` is cancelled and the
+    /// coupon being processed, if any, has been fully handled.
     pub async fn start(&self) -> Result<JoinHandle<()>> {
         info!("Starting task scheduler");
         
+        let db_pool = self.state.lock().await.db_pool.clone();
+        let mut scheduled = load_schedules(&db_pool, &self.scrapers, &self.config).await?;
+        
         // Clone the values needed for the async task
         let state = self.state.clone();
-        let scrapers = self.scrapers.clone();
+        let http_client = self.http_client.clone();
         let validator = self.validator.clone();
-        let discord_client = self.discord_client.clone();
+        let notifier = self.notifier.clone();
+        let webhooks = self.webhooks.clone();
         let config = self.config.clone();
+        let shutdown = self.shutdown.clone();
         
         // Start the main scheduler loop in a separate task
         let handle = tokio::spawn(async move {
             info!("Task scheduler started");
             
-            // Run initial scrape immediately
-            if let Err(e) = run_scrape_task(&state, &scrapers, &validator, &discord_client, &config).await {
-                error!("Initial scrape failed: {}", e);
-            }
-            
-            // Schedule periodic tasks
-            let scrape_interval = StdDuration::from_secs(config.scraping.default_interval * 60);
             let cleanup_interval = StdDuration::from_secs(24 * 60 * 60); // Daily cleanup
-            
             let mut last_cleanup = Utc::now();
             
-            loop {
-                // Wait for the next scrape interval
-                sleep(scrape_interval).await;
+            // Re-validate stored coupons periodically, starting with the first pass of the loop
+            let revalidate_interval = (config.validation.enable && config.validation.revalidate_interval > 0)
+                .then(|| Duration::minutes(config.validation.revalidate_interval as i64));
+            let mut next_revalidation = Utc::now();
+            
+            while !shutdown.is_cancelled() {
+                // Run every scraper whose next run time has passed
+                let started = Utc::now();
+                let due: Vec<Arc<dyn Scraper>> = scheduled
+                    .iter()
+                    .filter(|entry| entry.next_run <= started)
+                    .map(|entry| entry.scraper.clone())
+                    .collect();
                 
-                // Run the scrape task
-                if let Err(e) = run_scrape_task(&state, &scrapers, &validator, &discord_client, &config).await {
-                    error!("Scheduled scrape failed: {}", e);
+                if !due.is_empty() {
+                    if let Err(e) = run_scrape_task(&state, &due, &http_client, &validator, &webhooks, &config, &shutdown).await {
+                        error!("Scheduled scrape failed: {}", e);
+                    }
+                    
+                    // Keep the schedule of an interrupted scrape so it reruns after a restart
+                    if shutdown.is_cancelled() {
+                        break;
+                    }
+                    
+                    let finished = Utc::now();
+                    for entry in scheduled.iter_mut().filter(|entry| entry.next_run <= started) {
+                        entry.next_run = next_run_time(entry, finished, &config);
+                        debug!("Next run of {} at {}", entry.scraper.name(), entry.next_run);
+                        
+                        if let Err(e) = db::save_scraper_schedule(&db_pool, entry.scraper.name(), started, entry.next_run).await {
+                            error!("Failed to save schedule for {}: {}", entry.scraper.name(), e);
+                        }
+                    }
                 }
                 
                 // Check if we need to run cleanup (daily)
                 let now = Utc::now();
                 if (now - last_cleanup).num_seconds() >= (cleanup_interval.as_secs() as i64) {
-                    if let Err(e) = run_cleanup_task(&state).await {
+                    if let Err(e) = run_cleanup_task(&state, notifier.as_ref(), &webhooks).await {
                         error!("Cleanup task failed: {}", e);
                     }
                     last_cleanup = now;
                 }
+                
+                // Re-validate stale coupons when due
+                if let Some(interval) = revalidate_interval {
+                    if next_revalidation <= Utc::now() {
+                        if let Err(e) = run_revalidation_task(&state, &validator, notifier.as_ref(), &webhooks, &config, &shutdown).await {
+                            error!("Revalidation task failed: {}", e);
+                        }
+                        next_revalidation = Utc::now() + interval;
+                    }
+                }
+                
+                // Sleep until the next scraper or re-validation is due
+                let wait = scheduled
+                    .iter()
+                    .map(|entry| entry.next_run)
+                    .chain(revalidate_interval.map(|_| next_revalidation))
+                    .min()
+                    .and_then(|next_run| (next_run - Utc::now()).to_std().ok())
+                    .unwrap_or(StdDuration::ZERO)
+                    .clamp(StdDuration::from_secs(1), MAX_SLEEP);
+                tokio::select! {
+                    _ = sleep(wait) => {}
+                    _ = shutdown.cancelled() => {}
+                }
             }
+            
+            info!("Task scheduler stopped");
         });
         
         Ok(handle)
     }
 }
 
+/// Resolve each scraper's schedule and restore its next run time from the database
+async fn load_schedules(
+    db_pool: &SqlitePool,
+    scrapers: &[Arc<dyn Scraper>],
+    config: &Config,
+) -> Result<Vec<ScheduledScraper>> {
+    for schedule in &config.scraping.schedules {
+        if !scrapers.iter().any(|s| s.name().eq_ignore_ascii_case(&schedule.scraper)) {
+            warn!("Schedule configured for unknown scraper: {}", schedule.scraper);
+        }
+    }
+    
+    let persisted = db::get_scraper_schedules(db_pool).await?;
+    let now = Utc::now();
+    let mut scheduled = Vec::new();
+    
+    for scraper in scrapers {
+        let schedule = match config
+            .scraping
+            .schedules
+            .iter()
+            .find(|s| s.scraper.eq_ignore_ascii_case(scraper.name()))
+        {
+            Some(schedule) => ScrapeSchedule::from_config(schedule)?,
+            None => ScrapeSchedule::Interval(Duration::minutes(config.scraping.default_interval as i64)),
+        };
+        
+        // Resume at the stored next run so a restart doesn't re-scrape everything,
+        // moving it forward if the current schedule would run the scraper sooner
+        let next_run = persisted
+            .iter()
+            .find(|p| p.scraper == scraper.name())
+            .map(|p| match schedule.next_after(now) {
+                Some(scheduled) => p.next_run.min(scheduled),
+                None => p.next_run,
+            })
+            .unwrap_or(now);
+        
+        info!("Scheduled {} {}, next run at {}", scraper.name(), schedule, next_run);
+        scheduled.push(ScheduledScraper {
+            scraper: scraper.clone(),
+            schedule,
+            next_run,
+        });
+    }
+    
+    Ok(scheduled)
+}
+
+/// Compute the next run time of a scraper that just finished
+fn next_run_time(entry: &ScheduledScraper, finished: DateTime<Utc>, config: &Config) -> DateTime<Utc> {
+    entry.schedule.next_after(finished).unwrap_or_else(|| {
+        warn!(
+            "Schedule for {} has no upcoming runs, falling back to the default interval",
+            entry.scraper.name()
+        );
+        finished + Duration::minutes(config.scraping.default_interval as i64)
+    })
+}
+
 /// Run a scrape task
 async fn run_scrape_task(
     state: &Arc<Mutex<AppState>>,
-    scrapers: &[Box<dyn Scraper>],
+    scrapers: &[Arc<dyn Scraper>],
+    client: &HttpClient,
     validator: &Validator,
-    discord_client: &DiscordClient,
+    webhooks: &WebhookEmitter,
     config: &Config,
+    shutdown: &CancellationToken,
 ) -> Result<()> {
     info!("Running scrape task");
     
-    // Create HTTP client for scraping
-    let client = reqwest::Client::builder()
-        .timeout(StdDuration::from_secs(30))
-        .user_agent(&config.scraping.user_agent)
-        .build()
-        .context("Failed to build HTTP client")?;
-    
-    let mut state_guard = state.lock().await;
-    let db_pool = state_guard.db_pool.clone();
-    state_guard.last_scrape = Some(Utc::now());
-    drop(state_guard); // Release the lock
+    let started_at = Utc::now();
+    let db_pool = state.lock().await.db_pool.clone();
     
-    // Scrape coupons from all sources
-    let mut all_coupons = Vec::new();
+    // Scrape coupons from all sources in parallel, bounded by max_concurrent
+    let semaphore = Arc::new(Semaphore::new(config.scraping.max_concurrent as usize));
+    let scrape_timeout = StdDuration::from_secs(config.scraping.scraper_timeout);
+    let mut tasks = JoinSet::new();
     
     for scraper in scrapers {
-        info!("Scraping coupons from {}", scraper.name());
+        let scraper = scraper.clone();
+        let client = client.clone();
+        let semaphore = semaphore.clone();
+        let shutdown = shutdown.clone();
         
-        match scraper.scrape(&client).await {
-            Ok(coupons) => {
-                info!("Found {} coupons from {}", coupons.len(), scraper.name());
-                all_coupons.extend(coupons);
-            }
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await.ok();
+            info!("Scraping coupons from {}", scraper.name());
+            
+            let stats = Arc::new(FetchStats::default());
+            let client = client.with_stats(stats.clone());
+            let run_started_at = Utc::now();
+            
+            // Scraping only reads from the network, so it's safe to abandon on shutdown
+            let result = tokio::select! {
+                result = timeout(scrape_timeout, scraper.scrape(&client)) => result,
+                _ = shutdown.cancelled() => return None,
+            };
+            
+            let (status, coupons, error) = match result {
+                Ok(Ok(coupons)) if coupons.is_empty() => (ScrapeStatus::Empty, coupons, None),
+                Ok(Ok(coupons)) => (ScrapeStatus::Success, coupons, None),
+                Ok(Err(e)) => (ScrapeStatus::Failed, Vec::new(), Some(format!("{:#}", e))),
+                Err(_) => (
+                    ScrapeStatus::TimedOut,
+                    Vec::new(),
+                    Some(format!("Timed out after {} seconds", scrape_timeout.as_secs())),
+                ),
+            };
+            let (http_statuses, bytes_fetched) = stats.snapshot();
+            
+            let run = ScrapeRun {
+                id: None,
+                scraper: scraper.name().to_string(),
+                started_at: run_started_at,
+                finished_at: Utc::now(),
+                status,
+                coupons_found: coupons.len() as i64,
+                http_statuses: Json(http_statuses),
+                bytes_fetched: bytes_fetched as i64,
+                error,
+            };
+            Some((run, coupons))
+        });
+    }
+    
+    let mut all_coupons = Vec::new();
+    
+    while let Some(joined) = tasks.join_next().await {
+        let (run, coupons) = match joined {
+            Ok(Some(result)) => result,
+            Ok(None) => continue,
             Err(e) => {
-                error!("Failed to scrape coupons from {}: {}", scraper.name(), e);
+                error!("Scraper task failed: {}", e);
+                continue;
             }
+        };
+        
+        let seconds = (run.finished_at - run.started_at).num_milliseconds() as f64 / 1000.0;
+        metrics::observe_scrape(&run.scraper, run.status.as_str(), seconds, coupons.len());
+        
+        match run.status {
+            ScrapeStatus::Success => info!("Found {} coupons from {}", coupons.len(), run.scraper),
+            ScrapeStatus::Empty => warn!("Found no coupons from {}", run.scraper),
+            ScrapeStatus::Failed | ScrapeStatus::TimedOut => error!(
+                "Failed to scrape coupons from {}: {}",
+                run.scraper,
+                run.error.as_deref().unwrap_or("unknown error")
+            ),
         }
+        
+        if let Err(e) = db::insert_scrape_run(&db_pool, &run).await {
+            error!("Failed to record scrape run of {}: {}", run.scraper, e);
+        }
+        all_coupons.extend(coupons.into_iter().map(|coupon| (run.scraper.clone(), coupon)));
+    }
+    
+    if shutdown.is_cancelled() {
+        info!("Shutting down, discarding the coupons of the interrupted scrape");
+        return Ok(());
     }
     
     info!("Found {} coupons in total", all_coupons.len());
     
-    // Process each coupon
-    for coupon in all_coupons {
-        process_coupon(&db_pool, &coupon, validator, discord_client).await?;
+    // Process each coupon and record which scraper found it. A coupon is always handled
+    // completely, so shutdown is only checked between coupons.
+    let total = all_coupons.len();
+    for (processed, (scraper, coupon)) in all_coupons.into_iter().enumerate() {
+        if shutdown.is_cancelled() {
+            info!("Shutting down, leaving {} coupons for the next scrape", total - processed);
+            return Ok(());
+        }
+        
+        // A coupon that can't be stored doesn't hold up the rest of the scrape
+        let coupon_id = match process_coupon(&db_pool, &coupon, validator, webhooks, config).await {
+            Ok(coupon_id) => coupon_id,
+            Err(e) => {
+                error!("Failed to process coupon {} from {}: {:#}", coupon.name, scraper, e);
+                continue;
+            }
+        };
+        
+        match db::record_sighting(&db_pool, coupon_id, &scraper, &coupon.source, started_at).await {
+            Ok(true) => info!("Coupon is listed again: {}", coupon.name),
+            Ok(false) => {}
+            Err(e) => error!("Failed to record sighting of coupon {}: {:#}", coupon.name, e),
+        }
+    }
+    
+    // Coupons their scrapers keep missing have most likely been taken down
+    if config.scraping.likely_dead_after > 0 {
+        let missed_runs = config.scraping.likely_dead_after as i64;
+        for coupon in db::mark_likely_dead_coupons(&db_pool, missed_runs).await? {
+            info!(
+                "Coupon missing from the last {} scrapes, marking it likely dead: {}",
+                missed_runs, coupon.name
+            );
+        }
     }
     
     Ok(())
 }
 
-/// Process a single coupon
+/// Process a single coupon and return the id of the stored coupon it was found as
 async fn process_coupon(
     db_pool: &SqlitePool,
     coupon: &Coupon,
     validator: &Validator,
-    discord_client: &DiscordClient,
-) -> Result<()> {
+    webhooks: &WebhookEmitter,
+    config: &Config,
+) -> Result<i64> {
     // Check if coupon already exists in the database
-    if db::coupon_exists(db_pool, &coupon.hash).await? {
+    if let Some(coupon_id) = db::find_coupon_id_by_hash(db_pool, &coupon.hash).await? {
         debug!("Coupon already exists: {}", coupon.name);
-        return Ok(());
+        return Ok(coupon_id);
+    }
+    
+    // The same code listed on another source or page is only recorded as a listing
+    let candidates = db::get_coupons_by_code(db_pool, &coupon.code).await?;
+    let threshold = config.scraping.duplicate_similarity;
+    if let Some(canonical) = dedup::find_canonical(coupon, &candidates, threshold) {
+        let coupon_id = canonical.id.unwrap_or_default();
+        info!(
+            "Coupon {} from {} duplicates coupon {}: {}",
+            coupon.name, coupon.source, coupon_id, canonical.name
+        );
+        
+        let listing = CouponListing {
+            id: None,
+            coupon_id,
+            source: coupon.source.clone(),
+            name: coupon.name.clone(),
+            url: coupon.url.clone(),
+            hash: coupon.hash.clone(),
+            first_seen: Utc::now(),
+        };
+        db::insert_coupon_listing(db_pool, &listing).await?;
+        return Ok(coupon_id);
     }
     
     // Insert coupon into database
     let coupon_id = db::insert_coupon(db_pool, coupon).await?;
+    metrics::coupon_inserted();
     debug!("Inserted coupon with ID {}: {}", coupon_id, coupon.name);
     
+    let mut stored_coupon = coupon.clone();
+    stored_coupon.id = Some(coupon_id);
+    webhooks.emit(CouponEvent::Discovered, &stored_coupon);
+    
     // Validate the coupon
     info!("Validating coupon: {}", coupon.name);
-    match validator.validate_coupon(coupon).await {
+    match validator.validate_coupon(db_pool, coupon_id, coupon).await {
         Ok(validation_result) => {
-            // Update validation status in database
-            db::update_validation_status(db_pool, coupon_id, validation_result.is_valid).await?;
+            // Update validation status in database, queueing valid coupons for announcement
+            if validation_result.is_valid {
+                let key = OutboxEntry::post_key(coupon);
+                let due_at = Utc::now() + Duration::seconds(config.outbox.batch_delay as i64);
+                db::mark_valid_and_enqueue_post(db_pool, coupon_id, &key, due_at).await?;
+            } else {
+                db::update_validation_status(db_pool, coupon_id, false).await?;
+            }
+            
+            let mut validated_coupon = stored_coupon;
+            validated_coupon.is_valid = validation_result.is_valid;
+            validated_coupon.validated_at = Some(validation_result.validated_at);
             
             if validation_result.is_valid {
                 info!("Coupon is valid: {}", coupon.name);
-                
-                // Post validated coupon to Discord
-                let mut validated_coupon = coupon.clone();
-                validated_coupon.id = Some(coupon_id);
-                validated_coupon.is_valid = true;
-                validated_coupon.validated_at = Some(validation_result.validated_at);
-                
-                if let Err(e) = discord_client.send_coupon_notification(&validated_coupon).await {
-                    error!("Failed to send coupon notification: {}", e);
-                } else {
-                    // Mark coupon as posted
-                    db::mark_as_posted(db_pool, coupon_id).await?;
-                    info!("Coupon posted to Discord: {}", coupon.name);
-                }
+                webhooks.emit(CouponEvent::Validated, &validated_coupon);
             } else {
                 info!("Coupon is invalid: {}", coupon.name);
+                webhooks.emit(CouponEvent::Invalidated, &validated_coupon);
                 if let Some(message) = validation_result.message {
                     debug!("Validation message: {}", message);
                 }
@@ -192,40 +455,410 @@ async fn process_coupon(
         }
     }
     
+    Ok(coupon_id)
+}
+
+/// Re-validate the coupons with the stalest validation, flipping their status and
+/// updating the Discord posts of coupons that stopped or resumed working
+async fn run_revalidation_task(
+    state: &Arc<Mutex<AppState>>,
+    validator: &Validator,
+    notifier: &dyn Notifier,
+    webhooks: &WebhookEmitter,
+    config: &Config,
+    shutdown: &CancellationToken,
+) -> Result<()> {
+    info!("Running revalidation task");
+    
+    let db_pool = state.lock().await.db_pool.clone();
+    let checked_before = Utc::now() - Duration::hours(config.validation.revalidate_after as i64);
+    let coupons = db::get_stale_coupons(
+        &db_pool,
+        checked_before,
+        config.validation.revalidate_batch as i64,
+    )
+    .await?;
+    
+    let mut died = 0;
+    let mut revived = 0;
+    
+    for coupon in &coupons {
+        if shutdown.is_cancelled() {
+            info!("Shutting down, stopping revalidation");
+            break;
+        }
+        
+        let Some(coupon_id) = coupon.id else {
+            continue;
+        };
+        
+        let validation_result = match validator.validate_coupon(&db_pool, coupon_id, coupon).await {
+            Ok(validation_result) => validation_result,
+            Err(e) => {
+                warn!("Failed to revalidate coupon {}: {}", coupon.name, e);
+                // Try the next stale coupons first instead of retrying this one every run
+                db::mark_checked(&db_pool, coupon_id).await?;
+                continue;
+            }
+        };
+        
+        // Coupons that were invalid on first sight have never been posted
+        if !coupon.is_valid && validation_result.is_valid && !coupon.is_posted {
+            let key = OutboxEntry::post_key(coupon);
+            let due_at = Utc::now() + Duration::seconds(config.outbox.batch_delay as i64);
+            db::mark_valid_and_enqueue_post(&db_pool, coupon_id, &key, due_at).await?;
+        } else {
+            db::update_validation_status(&db_pool, coupon_id, validation_result.is_valid).await?;
+        }
+        
+        let mut validated_coupon = coupon.clone();
+        validated_coupon.is_valid = validation_result.is_valid;
+        validated_coupon.validated_at = Some(validation_result.validated_at);
+        
+        match (coupon.is_valid, validation_result.is_valid) {
+            (true, false) => {
+                died += 1;
+                info!("Coupon is no longer valid: {}", coupon.name);
+                webhooks.emit(CouponEvent::Invalidated, &validated_coupon);
+                
+                // Announcements still being retried have posts for the targets that got them
+                retract_coupon_posts(
+                    &db_pool,
+                    notifier,
+                    coupon_id,
+                    coupon,
+                    "No longer valid",
+                    validation_result.message.as_deref(),
+                )
+                .await?;
+                
+                if config.validation.notify_dead && coupon.is_posted {
+                    if let Err(e) = notifier
+                        .send_dead_coupon(coupon, validation_result.message.as_deref())
+                        .await
+                    {
+                        error!("Failed to send dead coupon notification: {}", e);
+                    }
+                }
+            }
+            (false, true) => {
+                revived += 1;
+                info!("Coupon is valid again: {}", coupon.name);
+                webhooks.emit(CouponEvent::Validated, &validated_coupon);
+                
+                for post in db::get_discord_messages(&db_pool, coupon_id).await? {
+                    if let Err(e) = notifier.restore_post(&post, &validated_coupon).await {
+                        error!("Failed to restore Discord post of {}: {}", coupon.name, e);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    
+    info!(
+        "Revalidated {} coupons: {} no longer valid, {} valid again",
+        coupons.len(),
+        died,
+        revived
+    );
+    Ok(())
+}
+
+/// Edit or delete every Discord post of a coupon that expired or stopped working,
+/// as configured by `discord.invalid_post_action`
+async fn retract_coupon_posts(
+    db_pool: &SqlitePool,
+    notifier: &dyn Notifier,
+    coupon_id: i64,
+    coupon: &Coupon,
+    status: &str,
+    reason: Option<&str>,
+) -> Result<()> {
+    for post in db::get_discord_messages(db_pool, coupon_id).await? {
+        match notifier.retract_post(&post, coupon, status, reason).await {
+            Ok(true) => db::delete_discord_message(db_pool, coupon_id, &post.message_id).await?,
+            Ok(false) => {}
+            Err(e) => error!("Failed to update Discord post of {}: {}", coupon.name, e),
+        }
+    }
+    
     Ok(())
 }
 
-/// Run a cleanup task to remove expired coupons
-async fn run_cleanup_task(state: &Arc<Mutex<AppState>>) -> Result<()> {
+/// Run a cleanup task to remove expired coupons, announcing their expiry and
+/// retracting their Discord posts first, and old scraper runs
+async fn run_cleanup_task(
+    state: &Arc<Mutex<AppState>>,
+    notifier: &dyn Notifier,
+    webhooks: &WebhookEmitter,
+) -> Result<()> {
     info!("Running cleanup task");
     
     let state_guard = state.lock().await;
     let db_pool = state_guard.db_pool.clone();
     drop(state_guard); // Release the lock
     
+    for coupon in db::get_expired_coupons(&db_pool).await? {
+        webhooks.emit(CouponEvent::Expired, &coupon);
+        
+        if let Some(coupon_id) = coupon.id {
+            retract_coupon_posts(&db_pool, notifier, coupon_id, &coupon, "Expired", None).await?;
+        }
+    }
+    
     // Delete expired coupons
     let deleted_count = db::delete_expired_coupons(&db_pool).await?;
     info!("Deleted {} expired coupons", deleted_count);
     
+    // Keep a month of scraper runs for the health summaries
+    let deleted_runs =
+        db::delete_scrape_runs_before(&db_pool, Utc::now() - SCRAPE_RUN_RETENTION).await?;
+    debug!("Deleted {} old scrape runs", deleted_runs);
+    
     Ok(())
 }
 
 /// Start the scheduler
+#[allow(clippy::too_many_arguments)]
 pub async fn start_scheduler(
     state: Arc<Mutex<AppState>>,
-    scrapers: Vec<Box<dyn Scraper>>,
+    scrapers: Vec<Arc<dyn Scraper>>,
+    http_client: HttpClient,
     validator: Validator,
-    discord_client: DiscordClient,
+    notifier: Arc<dyn Notifier>,
+    webhooks: WebhookEmitter,
     config: &Config,
+    shutdown: CancellationToken,
 ) -> Result<JoinHandle<()>> {
     let scheduler = TaskScheduler::new(
         state,
         scrapers,
+        http_client,
         validator,
-        discord_client,
+        notifier,
+        webhooks,
         Arc::new(config.clone()),
+        shutdown,
     );
     
     scheduler.start().await
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+    use crate::discord::SentMessage;
+    use crate::models::{DiscordAuth, DiscordMessage};
+    use crate::notifier::Delivery;
+    use crate::testing::memory_database;
+    use async_trait::async_trait;
+    use serenity::model::id::{ChannelId, MessageId};
+
+    struct IdleScraper;
+
+    #[async_trait]
+    impl Scraper for IdleScraper {
+        fn name(&self) -> &str {
+            "Idle"
+        }
+
+        fn source(&self) -> String {
+            "Idle".to_string()
+        }
+
+        async fn scrape(&self, _client: &HttpClient) -> Result<Vec<Coupon>> {
+            Ok(Vec::new())
+        }
+    }
+
+    async fn next_run_after_restart(pool: &SqlitePool, settings: &str) -> DateTime<Utc> {
+        let toml = format!("[discord]\nwebhook_url = \"https://discord.com/api/webhooks/1/token\"\n{}", settings);
+        let config = config::config_from_toml(&toml).unwrap();
+        let scrapers: Vec<Arc<dyn Scraper>> = vec![Arc::new(IdleScraper)];
+        let scheduled = load_schedules(pool, &scrapers, &config).await.unwrap();
+        scheduled[0].next_run
+    }
+
+    #[tokio::test]
+    async fn restarts_resume_at_the_stored_next_run() {
+        let pool = memory_database().await;
+        let last_run = Utc::now() - Duration::minutes(10);
+        // The last scrape took ten minutes
+        let next_run = last_run + Duration::minutes(40);
+        db::save_scraper_schedule(&pool, "Idle", last_run, next_run).await.unwrap();
+
+        let settings = "[[scraping.schedules]]\nscraper = \"Idle\"\ninterval = 30\n";
+        assert_eq!(next_run_after_restart(&pool, settings).await, next_run);
+    }
+
+    #[tokio::test]
+    async fn shorter_schedules_move_the_stored_next_run_forward() {
+        let pool = memory_database().await;
+        let next_run = Utc::now() + Duration::hours(12);
+        db::save_scraper_schedule(&pool, "Idle", Utc::now(), next_run).await.unwrap();
+
+        let settings = "[[scraping.schedules]]\nscraper = \"Idle\"\ninterval = 60\n";
+        let resumed = next_run_after_restart(&pool, settings).await;
+        assert!(resumed < next_run);
+        assert!(resumed <= Utc::now() + Duration::minutes(60));
+    }
+
+    #[tokio::test]
+    async fn scrapers_without_a_stored_schedule_run_right_away() {
+        let pool = memory_database().await;
+        let started = Utc::now();
+        assert!(next_run_after_restart(&pool, "").await >= started);
+        assert!(next_run_after_restart(&pool, "").await <= Utc::now());
+    }
+
+    /// Notifier recording the posts it was asked to retract
+    #[derive(Default)]
+    struct RetractRecorder {
+        retracted: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl Notifier for RetractRecorder {
+        fn name(&self) -> &str {
+            "recorder"
+        }
+
+        async fn send_coupon(&self, _coupon: &Coupon, _idempotency_key: &str) -> Result<Delivery> {
+            Ok(Delivery::default())
+        }
+
+        async fn send_dead_coupon(&self, _coupon: &Coupon, _reason: Option<&str>) -> Result<()> {
+            Ok(())
+        }
+
+        async fn retract_post(
+            &self,
+            post: &DiscordMessage,
+            _coupon: &Coupon,
+            _status: &str,
+            _reason: Option<&str>,
+        ) -> Result<bool> {
+            self.retracted.lock().unwrap().push(post.message_id.clone());
+            Ok(false)
+        }
+    }
+
+    #[tokio::test]
+    async fn cleanup_retracts_posts_of_announcements_still_being_retried() {
+        let pool = memory_database().await;
+        let toml = "[discord]\nwebhook_url = \"https://discord.com/api/webhooks/1/token\"\n";
+        let config = config::config_from_toml(toml).unwrap();
+        let state = Arc::new(Mutex::new(AppState {
+            config,
+            db_pool: pool.clone(),
+            started_at: Utc::now(),
+            discord_auth: DiscordAuth::NotConfigured,
+        }));
+        let webhooks = WebhookEmitter::new(&[], reqwest::Client::new(), pool.clone(), CancellationToken::new());
+
+        let coupon = Coupon::new(
+            "Cursor Pro".to_string(),
+            String::new(),
+            Some(20.0),
+            "SAVE20".to_string(),
+            "https://cursor.com/pricing".to_string(),
+            "Cursor AI".to_string(),
+            Some(Utc::now() + Duration::seconds(1)),
+        );
+        let coupon_id = db::insert_coupon(&pool, &coupon).await.unwrap();
+        db::mark_valid_and_enqueue_post(&pool, coupon_id, &OutboxEntry::post_key(&coupon), Utc::now())
+            .await
+            .unwrap();
+
+        // One target got the announcement, another failed and is waiting for a retry
+        let entry = db::get_due_outbox_entries(&pool, Utc::now(), 1).await.unwrap().remove(0);
+        let message = SentMessage {
+            target: "default".to_string(),
+            message_id: MessageId(1),
+            channel_id: ChannelId(2),
+            via_webhook: true,
+            embed_index: 0,
+            embed_count: 1,
+            field_index: None,
+        };
+        let retry_at = Utc::now() + Duration::minutes(1);
+        db::fail_outbox_attempt(&pool, &entry, &["discord:default".to_string()], &[message], "slack: timeout", Some(retry_at))
+            .await
+            .unwrap();
+        assert!(!db::get_coupon_by_id(&pool, coupon_id).await.unwrap().unwrap().is_posted);
+
+        tokio::time::sleep(StdDuration::from_millis(1100)).await;
+        let notifier = RetractRecorder::default();
+        run_cleanup_task(&state, &notifier, &webhooks).await.unwrap();
+
+        assert_eq!(*notifier.retracted.lock().unwrap(), vec!["1".to_string()]);
+    }
+
+    /// Scraper listing one coupon the database rejects and one it accepts
+    struct ListingScraper;
+
+    #[async_trait]
+    impl Scraper for ListingScraper {
+        fn name(&self) -> &str {
+            "Listing"
+        }
+
+        fn source(&self) -> String {
+            "Listing".to_string()
+        }
+
+        async fn scrape(&self, _client: &HttpClient) -> Result<Vec<Coupon>> {
+            // Expired, so validation doesn't need the network
+            let expired = Some(Utc::now() - Duration::days(1));
+            let coupon = |name: &str, code: &str| {
+                Coupon::new(
+                    name.to_string(),
+                    String::new(),
+                    None,
+                    code.to_string(),
+                    format!("https://example.com/{}", code),
+                    "Listing".to_string(),
+                    expired,
+                )
+            };
+            Ok(vec![coupon("Broken", "BROKEN"), coupon("Working", "WORKING")])
+        }
+    }
+
+    #[tokio::test]
+    async fn scrapes_keep_going_after_a_coupon_fails() {
+        let pool = memory_database().await;
+        sqlx::query(
+            "CREATE TRIGGER reject_broken BEFORE INSERT ON coupons WHEN NEW.code = 'BROKEN' \
+             BEGIN SELECT RAISE(ABORT, 'rejected'); END",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let toml = "[discord]\nwebhook_url = \"https://discord.com/api/webhooks/1/token\"\n";
+        let config = config::config_from_toml(toml).unwrap();
+        let state = Arc::new(Mutex::new(AppState {
+            config: config.clone(),
+            db_pool: pool.clone(),
+            started_at: Utc::now(),
+            discord_auth: DiscordAuth::NotConfigured,
+        }));
+        let client = crate::scraper::create_http_client(&config, None).unwrap();
+        let validator = Validator::new(config.clone(), client.clone());
+        let webhooks = WebhookEmitter::new(&[], reqwest::Client::new(), pool.clone(), CancellationToken::new());
+        let scrapers: Vec<Arc<dyn Scraper>> = vec![Arc::new(ListingScraper)];
+
+        run_scrape_task(&state, &scrapers, &client, &validator, &webhooks, &config, &CancellationToken::new())
+            .await
+            .unwrap();
+
+        let coupons = db::get_all_coupons(&pool).await.unwrap();
+        assert_eq!(coupons.len(), 1);
+        assert_eq!(coupons[0].code, "WORKING");
+        assert!(coupons[0].last_seen.is_some());
+    }
+}
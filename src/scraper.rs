@@ -1,150 +1,52 @@
 use anyhow::{Context as AnyhowContext, Result};
 use async_trait::async_trait;
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
 use regex::Regex;
-use reqwest::Client;
 use scraper::{Html, Selector};
 use std::sync::Arc;
 use std::time::Duration as StdDuration;
-use tracing::{debug, error, info, warn};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::{info, warn};
 
-use crate::models::{Config, Coupon, CouponSource};
+use crate::declarative::{self, DeclarativeScraper};
+use crate::models::{Config, Coupon, CouponSource, SourceConfig};
+use crate::proxy::{HttpClient, ProxyPool};
 
 /// Trait defining the interface for all scrapers
 #[async_trait]
 pub trait Scraper: Send + Sync {
     /// Returns the name of the scraper
-    fn name(&self) -> &'static str;
+    fn name(&self) -> &str;
     
     /// Returns the source of the scraper
+    #[allow(dead_code)]
     fn source(&self) -> String;
     
     /// Scrapes coupons from the source
-    async fn scrape(&self, client: &Client) -> Result<Vec<Coupon>>;
+    async fn scrape(&self, client: &HttpClient) -> Result<Vec<Coupon>>;
 }
 
-/// Cursor AI scraper
-pub struct CursorAIScraper;
+/// Base URL of GitHub Education
+const GITHUB_EDUCATION_URL: &str = "https://education.github.com";
 
-#[async_trait]
-impl Scraper for CursorAIScraper {
-    fn name(&self) -> &'static str {
-        "Cursor AI"
-    }
-    
-    fn source(&self) -> String {
-        CouponSource::CursorAI.to_string()
-    }
-    
-    async fn scrape(&self, client: &Client) -> Result<Vec<Coupon>> {
-        info!("Scraping coupons from Cursor AI");
-        let mut coupons = Vec::new();
-        
-        // First check the student page
-        let student_url = "https://cursor.sh/student";
-        let response = client
-            .get(student_url)
-            .send()
-            .await
-            .context("Failed to fetch Cursor AI student page")?;
-        
-        if !response.status().is_success() {
-            warn!("Failed to fetch Cursor AI student page: HTTP {}", response.status());
-            return Ok(coupons);
-        }
-        
-        let html = response.text().await.context("Failed to get response text")?;
-        let document = Html::parse_document(&html);
-        
-        // Try to find student discount information
-        if let Some(student_coupon) = extract_cursor_student_coupon(&document, student_url) {
-            coupons.push(student_coupon);
-        }
-        
-        // Also check the pricing page for other promotions
-        let pricing_url = "https://cursor.sh/pricing";
-        let response = client
-            .get(pricing_url)
-            .send()
-            .await
-            .context("Failed to fetch Cursor AI pricing page")?;
-        
-        if !response.status().is_success() {
-            warn!("Failed to fetch Cursor AI pricing page: HTTP {}", response.status());
-            return Ok(coupons);
-        }
-        
-        let html = response.text().await.context("Failed to get response text")?;
-        let document = Html::parse_document(&html);
-        
-        // Try to find promotion codes
-        if let Some(promo_coupons) = extract_cursor_promo_coupons(&document, pricing_url) {
-            coupons.extend(promo_coupons);
-        }
-        
-        info!("Found {} coupons from Cursor AI", coupons.len());
-        Ok(coupons)
-    }
-}
-
-/// Helper function to extract student coupon from Cursor AI
-fn extract_cursor_student_coupon(document: &Html, url: &str) -> Option<Coupon> {
-    // This is a simplified example - in reality, we'd use more complex selectors
-    // to find the student discount information
-    let selector = Selector::parse("div.student-discount").ok()?;
-    
-    if let Some(element) = document.select(&selector).next() {
-        // Example: found student discount information
-        Some(Coupon::new(
-            "Cursor AI Student Plan".to_string(),
-            "Free Pro features for verified students".to_string(),
-            Some(100.0), // 100% discount
-            "STUDENT".to_string(),
-            url.to_string(),
-            CouponSource::CursorAI.to_string(),
-            Some(Utc::now() + Duration::days(365)), // Assume 1 year validity
-        ))
-    } else {
-        None
-    }
+/// GitHub education/student scraper
+pub struct GitHubScraper {
+    base_url: String,
 }
 
-/// Helper function to extract promotion coupons from Cursor AI
-fn extract_cursor_promo_coupons(document: &Html, url: &str) -> Option<Vec<Coupon>> {
-    // Again, this is simplified - real implementation would parse the page
-    let selector = Selector::parse("div.promotion-code").ok()?;
-    let mut coupons = Vec::new();
-    
-    for element in document.select(&selector) {
-        // Extract code, discount, etc.
-        let code = element.value().attr("data-code").unwrap_or("PROMO");
-        let discount = element.value().attr("data-discount").unwrap_or("10");
-        let discount_pct = discount.parse::<f64>().unwrap_or(10.0);
-        
-        coupons.push(Coupon::new(
-            format!("Cursor AI Promotion: {}% Off", discount),
-            "Limited time promotion for Cursor AI Pro".to_string(),
-            Some(discount_pct),
-            code.to_string(),
-            url.to_string(),
-            CouponSource::CursorAI.to_string(),
-            Some(Utc::now() + Duration::days(30)), // Assume 30 days validity
-        ));
-    }
-    
-    if coupons.is_empty() {
-        None
-    } else {
-        Some(coupons)
+impl GitHubScraper {
+    /// Create a scraper for GitHub Education served at `base_url`
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
     }
 }
 
-/// GitHub education/student scraper
-pub struct GitHubScraper;
-
 #[async_trait]
 impl Scraper for GitHubScraper {
-    fn name(&self) -> &'static str {
+    fn name(&self) -> &str {
         "GitHub"
     }
     
@@ -152,14 +54,13 @@ impl Scraper for GitHubScraper {
         CouponSource::GitHub.to_string()
     }
     
-    async fn scrape(&self, client: &Client) -> Result<Vec<Coupon>> {
+    async fn scrape(&self, client: &HttpClient) -> Result<Vec<Coupon>> {
         info!("Scraping coupons from GitHub Education");
         let mut coupons = Vec::new();
         
-        let url = "https://education.github.com/pack";
+        let url = format!("{}/pack", self.base_url);
         let response = client
-            .get(url)
-            .send()
+            .get(&url)
             .await
             .context("Failed to fetch GitHub Education page")?;
         
@@ -168,14 +69,14 @@ impl Scraper for GitHubScraper {
             return Ok(coupons);
         }
         
-        let html = response.text().await.context("Failed to get response text")?;
+        let html = client.text(response).await.context("Failed to get response text")?;
         let document = Html::parse_document(&html);
         
         // Extract GitHub Student Developer Pack offers
         let offers_selector = Selector::parse("div.d-flex.flex-wrap.gutter").ok();
         if let Some(selector) = offers_selector {
             for offer_element in document.select(&selector) {
-                if let Some(coupon) = extract_github_offer(&offer_element, url) {
+                if let Some(coupon) = extract_github_offer(&offer_element, &url) {
                     coupons.push(coupon);
                 }
             }
@@ -220,211 +121,197 @@ fn extract_github_offer(element: &scraper::ElementRef, url: &str) -> Option<Coup
     ))
 }
 
-/// Replit scraper
-pub struct ReplitScraper;
+/// Generic AI tools scraper for the deal sites configured in `[[scraping.sources]]`
+pub struct GenericAIScraper {
+    sources: Vec<SourceConfig>,
+    max_concurrent: usize,
+}
+
+impl GenericAIScraper {
+    pub fn new(sources: Vec<SourceConfig>, max_concurrent: usize) -> Self {
+        Self {
+            sources,
+            max_concurrent: max_concurrent.max(1),
+        }
+    }
+}
 
 #[async_trait]
-impl Scraper for ReplitScraper {
-    fn name(&self) -> &'static str {
-        "Replit"
+impl Scraper for GenericAIScraper {
+    fn name(&self) -> &str {
+        "Generic AI Tools"
     }
     
     fn source(&self) -> String {
-        CouponSource::Replit.to_string()
+        CouponSource::Generic.to_string()
     }
     
-    async fn scrape(&self, client: &Client) -> Result<Vec<Coupon>> {
-        info!("Scraping coupons from Replit");
+    async fn scrape(&self, client: &HttpClient) -> Result<Vec<Coupon>> {
+        info!("Scraping coupons from generic AI tool sources");
         let mut coupons = Vec::new();
         
-        // Check education page
-        let edu_url = "https://replit.com/site/teams-for-education";
-        let response = client
-            .get(edu_url)
-            .send()
-            .await
-            .context("Failed to fetch Replit education page")?;
+        // Fetch all sources in parallel, at most `max_concurrent` at a time
+        let semaphore = Arc::new(Semaphore::new(self.max_concurrent));
+        let mut tasks = JoinSet::new();
         
-        if !response.status().is_success() {
-            warn!("Failed to fetch Replit education page: HTTP {}", response.status());
-            return Ok(coupons);
+        for source in &self.sources {
+            let source = source.clone();
+            let client = client.clone();
+            let semaphore = semaphore.clone();
+            
+            tasks.spawn(async move {
+                let _permit = semaphore.acquire_owned().await.ok();
+                scrape_generic_source(&client, &source).await
+            });
         }
         
-        let html = response.text().await.context("Failed to get response text")?;
-        let document = Html::parse_document(&html);
-        
-        // Extract education offers
-        // Simplified - real implementation would be more complex
-        let selector = Selector::parse("div.education-discount").ok();
-        if let Some(sel) = selector {
-            if let Some(element) = document.select(&sel).next() {
-                coupons.push(Coupon::new(
-                    "Replit Teams for Education".to_string(),
-                    "Special pricing for educational institutions".to_string(),
-                    Some(50.0), // Assumed discount
-                    "EDUCATION".to_string(),
-                    edu_url.to_string(),
-                    CouponSource::Replit.to_string(),
-                    None,
-                ));
+        while let Some(result) = tasks.join_next().await {
+            match result {
+                Ok(new_coupons) => coupons.extend(new_coupons),
+                Err(e) => warn!("Generic scrape task failed: {}", e),
             }
         }
         
-        info!("Found {} coupons from Replit", coupons.len());
+        info!("Found {} coupons from generic sources", coupons.len());
         Ok(coupons)
     }
 }
 
-/// Warp terminal scraper
-pub struct WarpScraper;
-
-#[async_trait]
-impl Scraper for WarpScraper {
-    fn name(&self) -> &'static str {
-        "Warp"
-    }
+/// Scrape a single generic source page, logging and skipping failures
+async fn scrape_generic_source(client: &HttpClient, source: &SourceConfig) -> Vec<Coupon> {
+    let url = source.url.as_str();
+    info!("Scraping {} from URL: {}", source.name, url);
     
-    fn source(&self) -> String {
-        CouponSource::Warp.to_string()
+    let response = match client.get(url).await {
+        Ok(response) => response,
+        Err(e) => {
+            warn!("Failed to fetch {}: {}", url, e);
+            return Vec::new();
+        }
+    };
+    
+    if !response.status().is_success() {
+        warn!("Failed to fetch {}: HTTP {}", url, response.status());
+        return Vec::new();
     }
     
-    async fn scrape(&self, client: &Client) -> Result<Vec<Coupon>> {
-        info!("Scraping coupons from Warp terminal");
-        let mut coupons = Vec::new();
-        
-        // Check student page
-        let student_url = "https://www.warp.dev/students";
-        let response = client
-            .get(student_url)
-            .send()
-            .await
-            .context("Failed to fetch Warp student page")?;
-        
-        if !response.status().is_success() {
-            warn!("Failed to fetch Warp student page: HTTP {}", response.status());
-            return Ok(coupons);
+    match client.text(response).await {
+        Ok(html) => {
+            let document = Html::parse_document(&html);
+            
+            // Use the configured selectors if any, otherwise look for coupon code patterns
+            if source.selectors.code.is_some() {
+                extract_selector_coupons(&document, source)
+            } else {
+                extract_generic_coupons(&document, url).unwrap_or_default()
+            }
+        }
+        Err(e) => {
+            warn!("Failed to get text from {}: {}", url, e);
+            Vec::new()
         }
-        
-        // Create a coupon for Warp student program
-        coupons.push(Coupon::new(
-            "Warp Terminal Student Plan".to_string(),
-            "Free Warp Premium subscription for verified students".to_string(),
-            Some(100.0), // 100% discount
-            "AUTO-APPLIED".to_string(),
-            student_url.to_string(),
-            CouponSource::Warp.to_string(),
-            Some(Utc::now() + Duration::days(365)), // Assume 1 year validity
-        ));
-        
-        info!("Found {} coupons from Warp", coupons.len());
-        Ok(coupons)
     }
 }
 
-/// Tabnine scraper
-pub struct TabnineScraper;
-
-#[async_trait]
-impl Scraper for TabnineScraper {
-    fn name(&self) -> &'static str {
-        "Tabnine"
-    }
+/// Helper function to extract coupons from a source using its configured CSS selectors
+fn extract_selector_coupons(document: &Html, source: &SourceConfig) -> Vec<Coupon> {
+    let selectors = &source.selectors;
+    let parse = |selector: &Option<String>| selector.as_deref().and_then(|s| Selector::parse(s).ok());
     
-    fn source(&self) -> String {
-        CouponSource::Tabnine.to_string()
-    }
+    let Some(code_selector) = parse(&selectors.code) else {
+        return Vec::new();
+    };
+    let item_selector = parse(&selectors.item);
+    let discount_selector = parse(&selectors.discount);
+    let title_selector = parse(&selectors.title);
+    let expiry_selector = parse(&selectors.expiry);
     
-    async fn scrape(&self, client: &Client) -> Result<Vec<Coupon>> {
-        info!("Scraping coupons from Tabnine");
-        let mut coupons = Vec::new();
+    // Without an item selector the whole page is treated as a single deal
+    let items: Vec<scraper::ElementRef> = match &item_selector {
+        Some(selector) => document.select(selector).collect(),
+        None => vec![document.root_element()],
+    };
+    
+    let percent_regex = Regex::new(r"(\d+(?:\.\d+)?)\s*%").expect("valid regex");
+    let mut coupons = Vec::new();
+    
+    for item in items {
+        let first_text = |selector: &Option<Selector>| {
+            selector
+                .as_ref()
+                .and_then(|s| item.select(s).next())
+                .map(element_text)
+                .filter(|text| !text.is_empty())
+        };
         
-        // Check student page
-        let student_url = "https://www.tabnine.com/students";
-        let response = client
-            .get(student_url)
-            .send()
-            .await
-            .context("Failed to fetch Tabnine student page")?;
+        let discount = first_text(&discount_selector)
+            .and_then(|text| {
+                percent_regex
+                    .captures(&text)
+                    .and_then(|cap| cap.get(1))
+                    .and_then(|m| m.as_str().parse::<f64>().ok())
+            });
+        let title = first_text(&title_selector);
+        let expiry = first_text(&expiry_selector).and_then(|text| parse_expiry(&text));
         
-        if !response.status().is_success() {
-            warn!("Failed to fetch Tabnine student page: HTTP {}", response.status());
-            return Ok(coupons);
+        for code_element in item.select(&code_selector) {
+            // Codes are often rendered in inputs or copy buttons rather than as text
+            let code = Some(element_text(code_element))
+                .filter(|text| !text.is_empty())
+                .or_else(|| code_element.value().attr("data-code").map(str::to_string))
+                .or_else(|| code_element.value().attr("value").map(str::to_string));
+            
+            let Some(code) = code.map(|c| c.trim().to_string()).filter(|c| !c.is_empty()) else {
+                continue;
+            };
+            
+            let name = match &title {
+                Some(title) => format!("{}: {}", source.name, title),
+                None => format!("{} Deal", source.name),
+            };
+            let description = match discount {
+                Some(discount) => format!("Use code {} for {}% off", code, discount),
+                None => format!("Use code {}", code),
+            };
+            
+            coupons.push(Coupon::new(
+                name,
+                description,
+                discount,
+                code,
+                source.url.clone(),
+                CouponSource::Generic.to_string(),
+                expiry.or_else(|| Some(Utc::now() + Duration::days(30))), // Assume 30 days validity
+            ));
         }
-        
-        // Create a coupon for Tabnine student program
-        coupons.push(Coupon::new(
-            "Tabnine Pro Student Plan".to_string(),
-            "Free Tabnine Pro for verified students".to_string(),
-            Some(100.0), // 100% discount
-            "STUDENT".to_string(),
-            student_url.to_string(),
-            CouponSource::Tabnine.to_string(),
-            Some(Utc::now() + Duration::days(365)), // Assume 1 year validity
-        ));
-        
-        info!("Found {} coupons from Tabnine", coupons.len());
-        Ok(coupons)
     }
+    
+    coupons
 }
 
-/// Generic AI tools scraper
-pub struct GenericAIScraper {
-    urls: Vec<String>,
-}
-
-impl GenericAIScraper {
-    pub fn new(urls: Vec<String>) -> Self {
-        Self { urls }
-    }
+/// Collect an element's text with whitespace collapsed
+pub fn element_text(element: scraper::ElementRef) -> String {
+    element.text().collect::<Vec<_>>().join(" ").split_whitespace().collect::<Vec<_>>().join(" ")
 }
 
-#[async_trait]
-impl Scraper for GenericAIScraper {
-    fn name(&self) -> &'static str {
-        "Generic AI Tools"
-    }
+/// Parse an expiry date in one of the formats commonly used on deal sites
+pub fn parse_expiry(text: &str) -> Option<DateTime<Utc>> {
+    let text = text
+        .trim()
+        .trim_start_matches("Expires")
+        .trim_start_matches("expires")
+        .trim_start_matches(':')
+        .trim();
     
-    fn source(&self) -> String {
-        CouponSource::Generic.to_string()
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(text) {
+        return Some(datetime.with_timezone(&Utc));
     }
     
-    async fn scrape(&self, client: &Client) -> Result<Vec<Coupon>> {
-        info!("Scraping coupons from generic AI tool sources");
-        let mut coupons = Vec::new();
-        
-        for url in &self.urls {
-            info!("Scraping from URL: {}", url);
-            
-            match client.get(url).send().await {
-                Ok(response) => {
-                    if !response.status().is_success() {
-                        warn!("Failed to fetch {}: HTTP {}", url, response.status());
-                        continue;
-                    }
-                    
-                    match response.text().await {
-                        Ok(html) => {
-                            let document = Html::parse_document(&html);
-                            
-                            // Look for coupon code patterns
-                            if let Some(new_coupons) = extract_generic_coupons(&document, url) {
-                                coupons.extend(new_coupons);
-                            }
-                        }
-                        Err(e) => {
-                            warn!("Failed to get text from {}: {}", url, e);
-                        }
-                    }
-                }
-                Err(e) => {
-                    warn!("Failed to fetch {}: {}", url, e);
-                }
-            }
-        }
-        
-        info!("Found {} coupons from generic sources", coupons.len());
-        Ok(coupons)
-    }
+    ["%Y-%m-%d", "%B %d, %Y", "%b %d, %Y", "%d %B %Y", "%m/%d/%Y"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(text, format).ok())
+        .and_then(|date| date.and_hms_opt(23, 59, 59))
+        .map(|datetime| datetime.and_utc())
 }
 
 /// Helper function to extract coupons from generic pages
@@ -469,40 +356,206 @@ fn extract_generic_coupons(document: &Html, url: &str) -> Option<Vec<Coupon>> {
 }
 
 /// Initialize all scrapers based on configuration
-pub fn initialize_scrapers(config: &Config) -> Result<Vec<Box<dyn Scraper>>> {
+pub fn initialize_scrapers(config: &Config) -> Result<Vec<Arc<dyn Scraper>>> {
     info!("Initializing scrapers");
-    let mut scrapers: Vec<Box<dyn Scraper>> = Vec::new();
+    let mut scrapers: Vec<Arc<dyn Scraper>> = Vec::new();
     
     // Add built-in scrapers
-    scrapers.push(Box::new(CursorAIScraper));
-    scrapers.push(Box::new(GitHubScraper));
-    scrapers.push(Box::new(ReplitScraper));
-    scrapers.push(Box::new(WarpScraper));
-    scrapers.push(Box::new(TabnineScraper));
+    scrapers.push(Arc::new(GitHubScraper::new(GITHUB_EDUCATION_URL)));
+    
+    // Add scrapers defined in scraping.definitions_dir
+    for definition in declarative::load_definitions(&config.scraping.definitions_dir)? {
+        if !definition.enabled {
+            info!("Skipping disabled scraper definition {}", definition.name);
+            continue;
+        }
+        
+        if scrapers.iter().any(|s| s.name().eq_ignore_ascii_case(&definition.name)) {
+            return Err(anyhow::anyhow!(
+                "Duplicate scraper name {} in {}",
+                definition.name,
+                config.scraping.definitions_dir
+            ));
+        }
+        
+        scrapers.push(Arc::new(DeclarativeScraper::new(&definition)?));
+        info!("Loaded scraper definition {}", definition.name);
+    }
+    
+    // Add generic scraper for the deal sites configured in [[scraping.sources]]
+    let sources: Vec<SourceConfig> = config
+        .scraping
+        .sources
+        .iter()
+        .filter(|source| source.enabled)
+        .cloned()
+        .collect();
     
-    // Add generic scraper with configurable URLs
-    // In a real implementation, these URLs would come from config
-    let generic_urls = vec![
-        "https://aidevtools.com/deals".to_string(),
-        "https://llmdeals.net".to_string(),
-        "https://devsoftwaredeals.com".to_string(),
-    ];
-    scrapers.push(Box::new(GenericAIScraper::new(generic_urls)));
+    if sources.is_empty() {
+        info!("No generic scraping sources enabled");
+    } else {
+        info!("Scraping {} generic sources", sources.len());
+        scrapers.push(Arc::new(GenericAIScraper::new(
+            sources,
+            config.scraping.max_concurrent as usize,
+        )));
+    }
     
     info!("Initialized {} scrapers", scrapers.len());
     Ok(scrapers)
 }
 
 /// Create an HTTP client for scraping
-pub fn create_http_client(config: &Config) -> Result<Client> {
-    let user_agent = &config.scraping.user_agent;
-    
-    let client = Client::builder()
-        .timeout(StdDuration::from_secs(30))
-        .user_agent(user_agent)
-        .build()
-        .context("Failed to build HTTP client")?;
+pub fn create_http_client(config: &Config, proxy_pool: Option<Arc<ProxyPool>>) -> Result<HttpClient> {
+    let user_agent = config.scraping.user_agent.clone();
     
-    Ok(client)
+    HttpClient::new(
+        |builder| {
+            builder
+                .timeout(StdDuration::from_secs(30))
+                .user_agent(user_agent.as_str())
+        },
+        proxy_pool,
+    )
+    .context("Failed to build HTTP client")
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::SourceSelectors;
+    use crate::testing::{fixture, serve_fixtures, test_client};
+
+    fn source(name: &str, url: String, selectors: SourceSelectors) -> SourceConfig {
+        SourceConfig {
+            name: name.to_string(),
+            url,
+            enabled: true,
+            selectors,
+        }
+    }
+
+    #[tokio::test]
+    async fn github_scraper_keeps_only_ai_offers() {
+        let base_url = serve_fixtures(&[("/pack", "github_pack.html")]).await;
+
+        let coupons = GitHubScraper::new(&base_url)
+            .scrape(&test_client())
+            .await
+            .unwrap();
+
+        let names: Vec<&str> = coupons.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "GitHub Student Pack: OpenAI Credits",
+                "GitHub Student Pack: Tabnine AI Assistant",
+            ]
+        );
+        assert_eq!(coupons[0].code, "GITHUB-STUDENT");
+        assert_eq!(coupons[0].url, format!("{}/pack#openai-credits", base_url));
+        assert_eq!(coupons[0].source, "GitHub");
+    }
+
+    #[tokio::test]
+    async fn github_scraper_returns_nothing_on_http_error() {
+        let base_url = serve_fixtures(&[]).await;
+
+        let coupons = GitHubScraper::new(&base_url)
+            .scrape(&test_client())
+            .await
+            .unwrap();
+
+        assert!(coupons.is_empty());
+    }
+
+    #[test]
+    fn generic_extraction_finds_codes_in_page_text() {
+        let document = Html::parse_document(&fixture("generic_deals.html"));
+
+        let coupons = extract_generic_coupons(&document, "https://deals.example").unwrap();
+
+        let codes: Vec<&str> = coupons.iter().map(|c| c.code.as_str()).collect();
+        assert_eq!(codes, ["AIDEV20", "LLM-50"]);
+        // The first discount on the page is applied to every code
+        assert!(coupons.iter().all(|c| c.discount_percentage == Some(20.0)));
+        assert!(coupons.iter().all(|c| c.url == "https://deals.example"));
+    }
+
+    #[test]
+    fn generic_extraction_without_codes_returns_none() {
+        let document = Html::parse_document(&fixture("student_program.html"));
+
+        assert!(extract_generic_coupons(&document, "https://deals.example").is_none());
+    }
+
+    #[tokio::test]
+    async fn generic_scraper_uses_configured_selectors() {
+        let base_url = serve_fixtures(&[
+            ("/deals", "deal_site.html"),
+            ("/text", "generic_deals.html"),
+        ])
+        .await;
+
+        let selectors = SourceSelectors {
+            item: Some("li.deal".to_string()),
+            code: Some(".coupon-code".to_string()),
+            discount: Some(".discount".to_string()),
+            title: Some("h3".to_string()),
+            expiry: Some(".expires".to_string()),
+        };
+        let scraper = GenericAIScraper::new(
+            vec![
+                source("Deal Site", format!("{}/deals", base_url), selectors),
+                source("Text Site", format!("{}/text", base_url), SourceSelectors::default()),
+                source("Missing", format!("{}/missing", base_url), SourceSelectors::default()),
+            ],
+            2,
+        );
+
+        let mut coupons = scraper.scrape(&test_client()).await.unwrap();
+        coupons.sort_by(|a, b| a.code.cmp(&b.code));
+
+        let codes: Vec<&str> = coupons.iter().map(|c| c.code.as_str()).collect();
+        assert_eq!(codes, ["AIDEV20", "LLM-50", "PROMPT15", "VECTOR25"]);
+
+        let vector = &coupons[3];
+        assert_eq!(vector.name, "Deal Site: Vector Database Pro");
+        assert_eq!(vector.discount_percentage, Some(25.0));
+        assert_eq!(
+            vector.expiry.map(|e| e.date_naive()),
+            NaiveDate::from_ymd_opt(2027, 1, 31)
+        );
+
+        let prompt = &coupons[2];
+        assert_eq!(prompt.discount_percentage, Some(15.5));
+        assert_eq!(
+            prompt.expiry.map(|e| e.date_naive()),
+            NaiveDate::from_ymd_opt(2027, 3, 1)
+        );
+    }
+
+    #[test]
+    fn parse_expiry_accepts_common_formats() {
+        let expected = NaiveDate::from_ymd_opt(2027, 1, 31);
+
+        for text in [
+            "2027-01-31",
+            "Expires: 2027-01-31",
+            "January 31, 2027",
+            "Jan 31, 2027",
+            "31 January 2027",
+            "01/31/2027",
+        ] {
+            assert_eq!(parse_expiry(text).map(|e| e.date_naive()), expected, "{}", text);
+        }
+
+        assert_eq!(
+            parse_expiry("2027-01-31T12:00:00+02:00"),
+            DateTime::parse_from_rfc3339("2027-01-31T10:00:00Z")
+                .ok()
+                .map(|e| e.with_timezone(&Utc))
+        );
+        assert!(parse_expiry("soon").is_none());
+    }
+}
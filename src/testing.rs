@@ -0,0 +1,167 @@
+use axum::{
+    http::{HeaderMap, Method, StatusCode, Uri},
+    response::Html,
+    routing::get,
+    Router,
+};
+use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+use crate::db;
+use crate::proxy::HttpClient;
+
+/// Read a saved HTML snapshot from `tests/fixtures`
+pub fn fixture(name: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+
+    std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Failed to read fixture {}: {}", path.display(), e))
+}
+
+/// Open an in-memory database with the application's schema
+pub async fn memory_database() -> SqlitePool {
+    // A single connection, since every connection to `sqlite::memory:` is a new database
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .expect("Failed to open in-memory database");
+
+    db::prepare_schema(&pool)
+        .await
+        .expect("Failed to create database schema");
+
+    pool
+}
+
+/// Serve fixtures from a local HTTP server and return its base URL.
+/// Each route maps a request path to a fixture file; other paths answer 404.
+pub async fn serve_fixtures(routes: &[(&str, &str)]) -> String {
+    let mut router = Router::new();
+
+    for (path, name) in routes {
+        let body = fixture(name);
+        router = router.route(path, get(move || async move { Html(body) }));
+    }
+
+    let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+        .serve(router.into_make_service());
+    let addr = server.local_addr();
+    tokio::spawn(server);
+
+    format!("http://{}", addr)
+}
+
+/// A request captured by [`record_requests`]
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: Method,
+    /// Path and query, still percent-encoded
+    pub path: String,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+/// Requests captured by a mock server
+pub type Recorded = Arc<Mutex<Vec<RecordedRequest>>>;
+
+/// Serve a mock API that records every request and answers it with `response` as JSON.
+/// Returns the server's base URL and the recorded requests.
+pub async fn record_requests(response: &'static str) -> (String, Recorded) {
+    record_requests_with(vec![(StatusCode::OK, response)]).await
+}
+
+/// Like [`record_requests`], answering the n-th request with the n-th status and JSON body;
+/// the last response is repeated once the list runs out
+pub async fn record_requests_with(responses: Vec<(StatusCode, &'static str)>) -> (String, Recorded) {
+    let recorded = Recorded::default();
+    let requests = recorded.clone();
+    let responses = Arc::new(responses);
+
+    let router = Router::new().fallback(
+        move |method: Method, uri: Uri, headers: HeaderMap, body: String| {
+            let requests = requests.clone();
+            let responses = responses.clone();
+            async move {
+                let mut requests = requests.lock().unwrap();
+                requests.push(RecordedRequest {
+                    method,
+                    path: uri.path_and_query().map(|p| p.to_string()).unwrap_or_default(),
+                    headers,
+                    body,
+                });
+
+                let (status, body) = responses[(requests.len() - 1).min(responses.len() - 1)];
+                (status, [("content-type", "application/json")], body)
+            }
+        },
+    );
+
+    let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+        .serve(router.into_make_service());
+    let addr = server.local_addr();
+    tokio::spawn(server);
+
+    (format!("http://{}", addr), recorded)
+}
+
+/// Serve a minimal plain-text SMTP server that accepts every message.
+/// Returns its port and the DATA of every received message.
+pub async fn serve_smtp() -> (u16, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("Failed to bind SMTP server");
+    let port = listener.local_addr().unwrap().port();
+    let messages = Arc::new(Mutex::new(Vec::new()));
+    let received = messages.clone();
+
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let received = received.clone();
+            tokio::spawn(async move {
+                let (reader, mut writer) = stream.into_split();
+                let mut lines = BufReader::new(reader).lines();
+                let mut data: Option<String> = None;
+
+                writer.write_all(b"220 localhost ESMTP\r\n").await.ok();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if let Some(message) = data.as_mut() {
+                        if line == "." {
+                            received.lock().unwrap().push(data.take().unwrap());
+                            writer.write_all(b"250 Queued\r\n").await.ok();
+                        } else {
+                            message.push_str(&line);
+                            message.push('\n');
+                        }
+                        continue;
+                    }
+
+                    let command = line.to_ascii_uppercase();
+                    let reply: &[u8] = if command.starts_with("EHLO") {
+                        b"250 localhost\r\n"
+                    } else if command.starts_with("DATA") {
+                        data = Some(String::new());
+                        b"354 End data with <CR><LF>.<CR><LF>\r\n"
+                    } else if command.starts_with("QUIT") {
+                        writer.write_all(b"221 Bye\r\n").await.ok();
+                        break;
+                    } else {
+                        b"250 OK\r\n"
+                    };
+                    writer.write_all(reply).await.ok();
+                }
+            });
+        }
+    });
+
+    (port, messages)
+}
+
+/// HTTP client without proxies
+pub fn test_client() -> HttpClient {
+    HttpClient::new(|builder| builder, None).expect("Failed to build test HTTP client")
+}
@@ -1,13 +1,15 @@
 use anyhow::{Context as AnyhowContext, Result};
 use async_trait::async_trait;
 use chrono::Utc;
-use reqwest::{Client, StatusCode};
-use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Duration;
+use sqlx::SqlitePool;
+use std::time::{Duration, Instant};
 use tracing::{debug, error, info, warn};
 
-use crate::models::{Config, Coupon, CouponSource, ValidationResult};
+use crate::db;
+use crate::metrics;
+use crate::models::{Config, Coupon, CouponSource, ValidationResult, ValidationRun};
+use crate::proxy::{HttpClient, ProxyPool};
 
 /// Trait for coupon validators
 #[async_trait]
@@ -19,27 +21,28 @@ pub trait CouponValidator: Send + Sync {
     fn can_validate(&self, source: &str) -> bool;
     
     /// Validates a coupon
-    async fn validate(&self, coupon: &Coupon, client: &Client) -> Result<ValidationResult>;
+    async fn validate(&self, coupon: &Coupon, client: &HttpClient) -> Result<ValidationResult>;
 }
 
 /// Main validator that manages all validation strategies
 pub struct Validator {
     validators: Vec<Box<dyn CouponValidator>>,
+    #[allow(dead_code)]
     config: Arc<Config>,
-    client: Client,
+    client: HttpClient,
 }
 
 impl Validator {
-    pub fn new(config: Arc<Config>, client: Client) -> Self {
-        let mut validators: Vec<Box<dyn CouponValidator>> = Vec::new();
-        
+    pub fn new(config: Arc<Config>, client: HttpClient) -> Self {
         // Add validators for different sources
-        validators.push(Box::new(CursorAIValidator::new()));
-        validators.push(Box::new(GitHubValidator::new()));
-        validators.push(Box::new(ReplitValidator::new()));
-        validators.push(Box::new(WarpValidator::new()));
-        validators.push(Box::new(TabnineValidator::new()));
-        validators.push(Box::new(GenericValidator::new()));
+        let validators: Vec<Box<dyn CouponValidator>> = vec![
+            Box::new(CursorAIValidator::new()),
+            Box::new(GitHubValidator::new()),
+            Box::new(ReplitValidator::new()),
+            Box::new(WarpValidator::new()),
+            Box::new(TabnineValidator::new()),
+            Box::new(GenericValidator::new()),
+        ];
         
         Self {
             validators,
@@ -48,32 +51,86 @@ impl Validator {
         }
     }
     
-    /// Validate a coupon
-    pub async fn validate_coupon(&self, coupon: &Coupon) -> Result<ValidationResult> {
+    /// Validate a coupon, recording the attempt in the validation history
+    pub async fn validate_coupon(
+        &self,
+        db_pool: &SqlitePool,
+        coupon_id: i64,
+        coupon: &Coupon,
+    ) -> Result<ValidationResult> {
+        let started = Instant::now();
+        let (validator, result) = self.run_validator(coupon).await;
+        let duration_ms = started.elapsed().as_millis() as i64;
+        
+        let run = match &result {
+            Ok(validation_result) => ValidationRun {
+                id: None,
+                coupon_id,
+                validator: validator.to_string(),
+                is_valid: Some(validation_result.is_valid),
+                message: validation_result.message.clone(),
+                http_status: validation_result.http_status.map(i64::from),
+                duration_ms,
+                validated_at: validation_result.validated_at,
+            },
+            Err(e) => ValidationRun {
+                id: None,
+                coupon_id,
+                validator: validator.to_string(),
+                is_valid: None,
+                message: Some(format!("{:#}", e)),
+                http_status: e
+                    .downcast_ref::<reqwest::Error>()
+                    .and_then(|e| e.status())
+                    .map(|status| i64::from(status.as_u16())),
+                duration_ms,
+                validated_at: Utc::now(),
+            },
+        };
+        
+        metrics::coupon_validated(validator, run.is_valid);
+        
+        if let Err(e) = db::insert_validation_run(db_pool, &run).await {
+            error!("Failed to record validation of coupon {}: {}", coupon_id, e);
+        }
+        
+        result
+    }
+    
+    /// Pick the validator for a coupon and run it, returning the validator's name with the result
+    async fn run_validator(&self, coupon: &Coupon) -> (&'static str, Result<ValidationResult>) {
         // First check if the coupon is expired
         if coupon.is_expired() {
-            return Ok(ValidationResult {
-                is_valid: false,
-                message: Some("Coupon has expired".to_string()),
-                validated_at: Utc::now(),
-            });
+            return (
+                "Expiry Check",
+                Ok(ValidationResult {
+                    is_valid: false,
+                    message: Some("Coupon has expired".to_string()),
+                    validated_at: Utc::now(),
+                    http_status: None,
+                }),
+            );
         }
         
         // Find a validator for this coupon's source
         for validator in &self.validators {
             if validator.can_validate(&coupon.source) {
                 debug!("Using {} validator for coupon: {}", validator.name(), coupon.name);
-                return validator.validate(coupon, &self.client).await;
+                return (validator.name(), validator.validate(coupon, &self.client).await);
             }
         }
         
         // If no specific validator is found, use a fallback approach
         warn!("No validator found for source: {}", coupon.source);
-        Ok(ValidationResult {
-            is_valid: true, // Assume valid if we can't validate
-            message: Some(format!("No validator available for source: {}", coupon.source)),
-            validated_at: Utc::now(),
-        })
+        (
+            "Fallback",
+            Ok(ValidationResult {
+                is_valid: true, // Assume valid if we can't validate
+                message: Some(format!("No validator available for source: {}", coupon.source)),
+                validated_at: Utc::now(),
+                http_status: None,
+            }),
+        )
     }
 }
 
@@ -96,12 +153,11 @@ impl CouponValidator for CursorAIValidator {
         source == CouponSource::CursorAI.to_string()
     }
     
-    async fn validate(&self, coupon: &Coupon, client: &Client) -> Result<ValidationResult> {
+    async fn validate(&self, coupon: &Coupon, client: &HttpClient) -> Result<ValidationResult> {
         // For student offers, we just verify the student page exists
         if coupon.code == "STUDENT" && coupon.url.contains("/student") {
             let response = client
                 .get(&coupon.url)
-                .send()
                 .await
                 .context("Failed to fetch Cursor AI student page")?;
             
@@ -110,6 +166,7 @@ impl CouponValidator for CursorAIValidator {
                     is_valid: true,
                     message: Some("Student program verified as active".to_string()),
                     validated_at: Utc::now(),
+                    http_status: Some(response.status().as_u16()),
                 });
             } else {
                 return Ok(ValidationResult {
@@ -119,6 +176,7 @@ impl CouponValidator for CursorAIValidator {
                         response.status()
                     )),
                     validated_at: Utc::now(),
+                    http_status: Some(response.status().as_u16()),
                 });
             }
         }
@@ -135,12 +193,14 @@ impl CouponValidator for CursorAIValidator {
                 is_valid: true,
                 message: Some("Coupon code format is valid".to_string()),
                 validated_at: Utc::now(),
+                http_status: None,
             })
         } else {
             Ok(ValidationResult {
                 is_valid: false,
                 message: Some("Invalid coupon code format".to_string()),
                 validated_at: Utc::now(),
+                http_status: None,
             })
         }
     }
@@ -165,16 +225,16 @@ impl CouponValidator for GitHubValidator {
         source == CouponSource::GitHub.to_string()
     }
     
-    async fn validate(&self, coupon: &Coupon, client: &Client) -> Result<ValidationResult> {
+    async fn validate(&self, coupon: &Coupon, client: &HttpClient) -> Result<ValidationResult> {
         // For GitHub Student Developer Pack, we mainly verify the offer still exists
         let response = client
             .get(&coupon.url)
-            .send()
             .await
             .context("Failed to fetch GitHub offer page")?;
         
         if response.status().is_success() {
             // Check if the page contains the offer name
+            let status = response.status().as_u16();
             let html = response.text().await.context("Failed to get response text")?;
             
             if html.contains(&coupon.name) {
@@ -182,12 +242,14 @@ impl CouponValidator for GitHubValidator {
                     is_valid: true,
                     message: Some("Offer found on GitHub Education page".to_string()),
                     validated_at: Utc::now(),
+                    http_status: Some(status),
                 });
             } else {
                 return Ok(ValidationResult {
                     is_valid: false,
                     message: Some("Offer not found on GitHub Education page".to_string()),
                     validated_at: Utc::now(),
+                    http_status: Some(status),
                 });
             }
         } else {
@@ -198,6 +260,7 @@ impl CouponValidator for GitHubValidator {
                     response.status()
                 )),
                 validated_at: Utc::now(),
+                http_status: Some(response.status().as_u16()),
             });
         }
     }
@@ -222,11 +285,10 @@ impl CouponValidator for ReplitValidator {
         source == CouponSource::Replit.to_string()
     }
     
-    async fn validate(&self, coupon: &Coupon, client: &Client) -> Result<ValidationResult> {
+    async fn validate(&self, coupon: &Coupon, client: &HttpClient) -> Result<ValidationResult> {
         // For Replit, verify the education program page exists
         let response = client
             .get(&coupon.url)
-            .send()
             .await
             .context("Failed to fetch Replit education page")?;
         
@@ -235,6 +297,7 @@ impl CouponValidator for ReplitValidator {
                 is_valid: true,
                 message: Some("Education program verified as active".to_string()),
                 validated_at: Utc::now(),
+                http_status: Some(response.status().as_u16()),
             });
         } else {
             return Ok(ValidationResult {
@@ -244,6 +307,7 @@ impl CouponValidator for ReplitValidator {
                     response.status()
                 )),
                 validated_at: Utc::now(),
+                http_status: Some(response.status().as_u16()),
             });
         }
     }
@@ -268,11 +332,10 @@ impl CouponValidator for WarpValidator {
         source == CouponSource::Warp.to_string()
     }
     
-    async fn validate(&self, coupon: &Coupon, client: &Client) -> Result<ValidationResult> {
+    async fn validate(&self, coupon: &Coupon, client: &HttpClient) -> Result<ValidationResult> {
         // For Warp, verify the student program page exists
         let response = client
             .get(&coupon.url)
-            .send()
             .await
             .context("Failed to fetch Warp student page")?;
         
@@ -281,6 +344,7 @@ impl CouponValidator for WarpValidator {
                 is_valid: true,
                 message: Some("Student program verified as active".to_string()),
                 validated_at: Utc::now(),
+                http_status: Some(response.status().as_u16()),
             });
         } else {
             return Ok(ValidationResult {
@@ -290,6 +354,7 @@ impl CouponValidator for WarpValidator {
                     response.status()
                 )),
                 validated_at: Utc::now(),
+                http_status: Some(response.status().as_u16()),
             });
         }
     }
@@ -314,11 +379,10 @@ impl CouponValidator for TabnineValidator {
         source == CouponSource::Tabnine.to_string()
     }
     
-    async fn validate(&self, coupon: &Coupon, client: &Client) -> Result<ValidationResult> {
+    async fn validate(&self, coupon: &Coupon, client: &HttpClient) -> Result<ValidationResult> {
         // For Tabnine, verify the student program page exists
         let response = client
             .get(&coupon.url)
-            .send()
             .await
             .context("Failed to fetch Tabnine student page")?;
         
@@ -327,6 +391,7 @@ impl CouponValidator for TabnineValidator {
                 is_valid: true,
                 message: Some("Student program verified as active".to_string()),
                 validated_at: Utc::now(),
+                http_status: Some(response.status().as_u16()),
             });
         } else {
             return Ok(ValidationResult {
@@ -336,6 +401,7 @@ impl CouponValidator for TabnineValidator {
                     response.status()
                 )),
                 validated_at: Utc::now(),
+                http_status: Some(response.status().as_u16()),
             });
         }
     }
@@ -360,14 +426,13 @@ impl CouponValidator for GenericValidator {
         source == CouponSource::Generic.to_string()
     }
     
-    async fn validate(&self, coupon: &Coupon, client: &Client) -> Result<ValidationResult> {
+    async fn validate(&self, coupon: &Coupon, client: &HttpClient) -> Result<ValidationResult> {
         // For generic coupons, we:
         // 1. Verify the source page is still accessible
         // 2. Check if the coupon code is still mentioned on the page
         
         let response = client
             .get(&coupon.url)
-            .send()
             .await
             .context("Failed to fetch coupon source page")?;
         
@@ -379,9 +444,11 @@ impl CouponValidator for GenericValidator {
                     response.status()
                 )),
                 validated_at: Utc::now(),
+                http_status: Some(response.status().as_u16()),
             });
         }
         
+        let status = response.status().as_u16();
         let html = response.text().await.context("Failed to get response text")?;
         
         // Check if the coupon code is still mentioned on the page
@@ -390,28 +457,31 @@ impl CouponValidator for GenericValidator {
                 is_valid: true,
                 message: Some("Coupon code found on source page".to_string()),
                 validated_at: Utc::now(),
+                http_status: Some(status),
             });
         } else {
             return Ok(ValidationResult {
                 is_valid: false,
                 message: Some("Coupon code not found on source page".to_string()),
                 validated_at: Utc::now(),
+                http_status: Some(status),
             });
         }
     }
 }
 
 /// Initialize the validator
-pub fn initialize_validator(config: &Config) -> Result<Validator> {
+pub fn initialize_validator(config: &Config, proxy_pool: Option<Arc<ProxyPool>>) -> Result<Validator> {
     info!("Initializing coupon validator");
     
     // Create HTTP client for validation
-    let client = Client::builder()
-        .timeout(Duration::from_secs(config.validation.timeout))
-        .user_agent(&config.scraping.user_agent)
-        .build()
-        .context("Failed to build HTTP client for validator")?;
+    let timeout = Duration::from_secs(config.validation.timeout);
+    let user_agent = config.scraping.user_agent.clone();
+    let client = HttpClient::new(
+        |builder| builder.timeout(timeout).user_agent(user_agent.as_str()),
+        proxy_pool,
+    )
+    .context("Failed to build HTTP client for validator")?;
     
     Ok(Validator::new(Arc::new(config.clone()), client))
 }
-
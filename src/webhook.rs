@@ -0,0 +1,514 @@
+use anyhow::{Context as AnyhowContext, Result};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::Serialize;
+use sha2::Sha256;
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::{debug, error, info, warn};
+
+use crate::db;
+use crate::models::{Config, Coupon, CouponEvent, WebhookConfig, WebhookDeadLetter};
+
+/// Version of the event payload, bumped on incompatible changes
+pub const EVENT_VERSION: u32 = 1;
+
+/// Delay before the first retry; doubled after every failed attempt
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Longest delay between two attempts
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// Timeout of a single delivery attempt
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// JSON body of a webhook event
+#[derive(Debug, Serialize)]
+struct EventPayload<'a> {
+    version: u32,
+    id: &'a str,
+    #[serde(rename = "type")]
+    event_type: &'static str,
+    created_at: DateTime<Utc>,
+    coupon: &'a Coupon,
+    /// Why the coupon stopped working, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'a str>,
+}
+
+/// Serialize the JSON body of an event
+pub fn event_body(
+    event: CouponEvent,
+    event_id: &str,
+    coupon: &Coupon,
+    reason: Option<&str>,
+) -> serde_json::Result<String> {
+    serde_json::to_string(&EventPayload {
+        version: EVENT_VERSION,
+        id: event_id,
+        event_type: event.as_str(),
+        created_at: Utc::now(),
+        coupon,
+        reason,
+    })
+}
+
+/// Build the signed POST of an event. Every attempt is signed with a fresh timestamp
+/// so receivers can reject replays.
+pub fn event_request(
+    client: &Client,
+    url: &str,
+    secret: &str,
+    event: CouponEvent,
+    event_id: &str,
+    body: &str,
+) -> RequestBuilder {
+    let timestamp = Utc::now().timestamp();
+    client
+        .post(url)
+        .header("Content-Type", "application/json")
+        .header("X-Rin-Event", event.as_str())
+        .header("X-Rin-Delivery", event_id)
+        .header("X-Rin-Timestamp", timestamp.to_string())
+        .header("X-Rin-Signature", sign(secret, timestamp, body))
+        .body(body.to_string())
+}
+
+/// Delay a `429` or `503` response asks for in its `Retry-After` header, given in
+/// seconds or as an HTTP date
+pub fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(reqwest::header::RETRY_AFTER)?.to_str().ok()?;
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.with_timezone(&Utc) - Utc::now()).to_std().unwrap_or_default())
+}
+
+/// A configured webhook endpoint
+#[derive(Debug)]
+struct Endpoint {
+    url: String,
+    secret: String,
+    events: Vec<CouponEvent>,
+    max_attempts: u32,
+    retry_delay: Duration,
+}
+
+impl Endpoint {
+    fn from_config(webhook: &WebhookConfig) -> Self {
+        Self {
+            url: webhook.url.clone(),
+            secret: webhook.secret.clone(),
+            events: webhook.events.clone(),
+            max_attempts: webhook.max_attempts.max(1),
+            retry_delay: INITIAL_RETRY_DELAY,
+        }
+    }
+
+    /// Checks if the endpoint subscribed to an event
+    fn wants(&self, event: CouponEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+/// Outcome of the last attempt of a delivery that gave up
+#[derive(Debug)]
+struct DeliveryFailure {
+    attempts: u32,
+    error: String,
+    http_status: Option<u16>,
+}
+
+/// Sends signed coupon events to the configured webhooks
+#[derive(Clone)]
+pub struct WebhookEmitter {
+    endpoints: Vec<Arc<Endpoint>>,
+    client: Client,
+    db_pool: SqlitePool,
+    /// Deliveries in progress, waited for on shutdown
+    deliveries: TaskTracker,
+    shutdown: CancellationToken,
+    /// Cancelled when shutdown stops waiting for the deliveries
+    aborted: CancellationToken,
+}
+
+impl WebhookEmitter {
+    pub fn new(
+        webhooks: &[WebhookConfig],
+        client: Client,
+        db_pool: SqlitePool,
+        shutdown: CancellationToken,
+    ) -> Self {
+        Self {
+            endpoints: webhooks
+                .iter()
+                .map(|webhook| Arc::new(Endpoint::from_config(webhook)))
+                .collect(),
+            client,
+            db_pool,
+            deliveries: TaskTracker::new(),
+            shutdown,
+            aborted: CancellationToken::new(),
+        }
+    }
+
+    /// Wait for the deliveries in progress. Once shutdown is cancelled, deliveries stop
+    /// retrying and store their event as a dead letter instead.
+    pub async fn drain(&self) {
+        self.deliveries.close();
+        if !self.deliveries.is_empty() {
+            info!("Waiting for {} webhook deliveries", self.deliveries.len());
+        }
+        self.deliveries.wait().await;
+    }
+
+    /// Stop the deliveries in progress without storing their events, for when draining
+    /// takes too long. Wait for them to stop with `drain`.
+    pub fn abort(&self) {
+        self.aborted.cancel();
+    }
+
+    /// Send an event to every subscribed endpoint. Deliveries and their retries run in the
+    /// background; events that can't be delivered end up in the dead-letter table.
+    pub fn emit(&self, event: CouponEvent, coupon: &Coupon) {
+        let endpoints: Vec<Arc<Endpoint>> = self
+            .endpoints
+            .iter()
+            .filter(|endpoint| endpoint.wants(event))
+            .cloned()
+            .collect();
+        if endpoints.is_empty() {
+            return;
+        }
+
+        let event_id = format!("{:032x}", rand::random::<u128>());
+        let body = match event_body(event, &event_id, coupon, None) {
+            Ok(body) => body,
+            Err(e) => {
+                error!("Failed to serialize {} event for {}: {}", event.as_str(), coupon.name, e);
+                return;
+            }
+        };
+        debug!("Emitting {} event {} for {}", event.as_str(), event_id, coupon.name);
+
+        for endpoint in endpoints {
+            let client = self.client.clone();
+            let db_pool = self.db_pool.clone();
+            let event_id = event_id.clone();
+            let body = body.clone();
+            let shutdown = self.shutdown.clone();
+            let aborted = self.aborted.clone();
+
+            self.deliveries.spawn(async move {
+                let result = tokio::select! {
+                    result = deliver(&client, &endpoint, event, &event_id, &body, &shutdown) => result,
+                    _ = aborted.cancelled() => {
+                        warn!("Dropped {} event {} for {} on shutdown", event.as_str(), event_id, endpoint.url);
+                        return;
+                    }
+                };
+                let Err(failure) = result else {
+                    return;
+                };
+
+                error!(
+                    "Giving up on {} event {} for {} after {} attempts: {}",
+                    event.as_str(),
+                    event_id,
+                    endpoint.url,
+                    failure.attempts,
+                    failure.error
+                );
+
+                let letter = WebhookDeadLetter {
+                    url: endpoint.url.clone(),
+                    event_id,
+                    event_type: event.as_str().to_string(),
+                    payload: body,
+                    attempts: failure.attempts as i64,
+                    last_error: failure.error,
+                    http_status: failure.http_status.map(i64::from),
+                    failed_at: Utc::now(),
+                };
+                if let Err(e) = db::insert_webhook_dead_letter(&db_pool, &letter).await {
+                    error!("Failed to store webhook dead letter: {}", e);
+                }
+            });
+        }
+    }
+}
+
+/// Compute the signature header value for a request body:
+/// `sha256=` followed by the hex HMAC-SHA256 of `{timestamp}.{body}`
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// POST an event until it is accepted, retrying network errors, timeouts, 429 and 5xx
+/// responses with exponential backoff until `shutdown` is cancelled. A `Retry-After`
+/// header makes the next attempt wait at least that long.
+async fn deliver(
+    client: &Client,
+    endpoint: &Endpoint,
+    event: CouponEvent,
+    event_id: &str,
+    body: &str,
+    shutdown: &CancellationToken,
+) -> std::result::Result<(), DeliveryFailure> {
+    let mut delay = endpoint.retry_delay;
+    let mut attempts = 0;
+
+    loop {
+        attempts += 1;
+
+        let result = event_request(client, &endpoint.url, &endpoint.secret, event, event_id, body)
+            .send()
+            .await;
+
+        let (error, http_status, retryable, wait) = match result {
+            Ok(response) if response.status().is_success() => {
+                debug!("Delivered event {} to {}", event_id, endpoint.url);
+                return Ok(());
+            }
+            Ok(response) => {
+                let status = response.status();
+                let retryable = status.is_server_error()
+                    || status == StatusCode::TOO_MANY_REQUESTS
+                    || status == StatusCode::REQUEST_TIMEOUT;
+                let wait = retry_after(&response).map_or(delay, |after| after.max(delay));
+                (format!("HTTP {}", status), Some(status.as_u16()), retryable, wait)
+            }
+            Err(e) => (e.to_string(), None, true, delay),
+        };
+
+        if !retryable || attempts >= endpoint.max_attempts || shutdown.is_cancelled() {
+            return Err(DeliveryFailure {
+                attempts,
+                error,
+                http_status,
+            });
+        }
+
+        warn!(
+            "Delivering event {} to {} failed ({}), retrying in {:?}",
+            event_id, endpoint.url, error, wait
+        );
+        tokio::select! {
+            _ = sleep(wait) => {}
+            _ = shutdown.cancelled() => {
+                return Err(DeliveryFailure {
+                    attempts,
+                    error: format!("{} (not retried, shutting down)", error),
+                    http_status,
+                });
+            }
+        }
+        delay = (delay * 2).min(MAX_RETRY_DELAY);
+    }
+}
+
+/// Initialize the webhook emitter for the `[[webhooks]]` endpoints
+pub fn initialize_webhooks(
+    config: &Config,
+    db_pool: SqlitePool,
+    shutdown: CancellationToken,
+) -> Result<WebhookEmitter> {
+    let client = Client::builder()
+        .timeout(DELIVERY_TIMEOUT)
+        .user_agent(&config.scraping.user_agent)
+        .build()
+        .context("Failed to build webhook HTTP client")?;
+
+    if !config.webhooks.is_empty() {
+        info!("Sending coupon events to {} webhooks", config.webhooks.len());
+    }
+
+    Ok(WebhookEmitter::new(&config.webhooks, client, db_pool, shutdown))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::record_requests_with;
+    use axum::http::StatusCode as MockStatus;
+
+    fn endpoint(url: String, max_attempts: u32) -> Endpoint {
+        Endpoint {
+            url,
+            secret: "s3cret".to_string(),
+            events: Vec::new(),
+            max_attempts,
+            retry_delay: Duration::from_millis(10),
+        }
+    }
+
+    #[test]
+    fn signature_is_hmac_of_timestamp_and_body() {
+        // echo -n '1700000000.{"a":1}' | openssl dgst -sha256 -hmac s3cret
+        assert_eq!(
+            sign("s3cret", 1_700_000_000, r#"{"a":1}"#),
+            "sha256=1698a50bc74d1ff1db85c4e0a5297c2ad9fdba245d5737cdb789e4cc6e098940"
+        );
+    }
+
+    #[test]
+    fn retry_after_accepts_seconds_and_dates() {
+        let response = |value: &str| {
+            Response::from(
+                axum::http::Response::builder()
+                    .status(429)
+                    .header("Retry-After", value)
+                    .body("")
+                    .unwrap(),
+            )
+        };
+
+        assert_eq!(retry_after(&response("120")), Some(Duration::from_secs(120)));
+        assert_eq!(
+            retry_after(&response("Wed, 21 Oct 2015 07:28:00 GMT")),
+            Some(Duration::ZERO)
+        );
+        let later = (Utc::now() + chrono::Duration::minutes(10)).to_rfc2822();
+        assert!(retry_after(&response(&later)).unwrap() > Duration::from_secs(9 * 60));
+        assert_eq!(retry_after(&response("soon")), None);
+    }
+
+    #[tokio::test]
+    async fn delivery_signs_and_retries_server_errors() {
+        let (base_url, requests) = record_requests_with(vec![
+            (MockStatus::INTERNAL_SERVER_ERROR, "{}"),
+            (MockStatus::OK, "{}"),
+        ])
+        .await;
+        let endpoint = endpoint(format!("{}/events", base_url), 3);
+
+        let body = r#"{"a":1}"#;
+        let shutdown = CancellationToken::new();
+        deliver(&Client::new(), &endpoint, CouponEvent::Discovered, "abc", body, &shutdown)
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+
+        let request = &requests[1];
+        assert_eq!(request.headers["x-rin-event"], "coupon.discovered");
+        assert_eq!(request.headers["x-rin-delivery"], "abc");
+        let timestamp: i64 = request.headers["x-rin-timestamp"].to_str().unwrap().parse().unwrap();
+        assert_eq!(
+            request.headers["x-rin-signature"],
+            sign("s3cret", timestamp, &request.body).as_str()
+        );
+    }
+
+    #[tokio::test]
+    async fn delivery_gives_up_after_max_attempts() {
+        let (base_url, requests) =
+            record_requests_with(vec![(MockStatus::SERVICE_UNAVAILABLE, "{}")]).await;
+        let endpoint = endpoint(base_url, 3);
+
+        let shutdown = CancellationToken::new();
+        let failure = deliver(
+            &Client::new(),
+            &endpoint,
+            CouponEvent::Expired,
+            "abc",
+            "{}",
+            &shutdown,
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(failure.attempts, 3);
+        assert_eq!(failure.http_status, Some(503));
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn delivery_does_not_retry_client_errors() {
+        let (base_url, requests) = record_requests_with(vec![(MockStatus::GONE, "{}")]).await;
+        let endpoint = endpoint(base_url, 5);
+
+        let shutdown = CancellationToken::new();
+        let failure = deliver(
+            &Client::new(),
+            &endpoint,
+            CouponEvent::Expired,
+            "abc",
+            "{}",
+            &shutdown,
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(failure.attempts, 1);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn delivery_stops_retrying_on_shutdown() {
+        let (base_url, requests) =
+            record_requests_with(vec![(MockStatus::SERVICE_UNAVAILABLE, "{}")]).await;
+        let endpoint = endpoint(base_url, 5);
+
+        let shutdown = CancellationToken::new();
+        shutdown.cancel();
+        let failure = deliver(
+            &Client::new(),
+            &endpoint,
+            CouponEvent::Expired,
+            "abc",
+            "{}",
+            &shutdown,
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(failure.attempts, 1);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn aborted_deliveries_stop_without_waiting_for_retries() {
+        let (base_url, requests) =
+            record_requests_with(vec![(MockStatus::SERVICE_UNAVAILABLE, "{}")]).await;
+        let webhook = WebhookConfig {
+            url: base_url,
+            secret: "s3cret".to_string(),
+            events: Vec::new(),
+            max_attempts: 5,
+        };
+        let pool = crate::testing::memory_database().await;
+        let emitter = WebhookEmitter::new(&[webhook], Client::new(), pool.clone(), CancellationToken::new());
+
+        let coupon = Coupon::new(
+            "Cursor Pro".to_string(),
+            String::new(),
+            None,
+            "SAVE20".to_string(),
+            "https://cursor.com".to_string(),
+            "Cursor AI".to_string(),
+            None,
+        );
+        emitter.emit(CouponEvent::Discovered, &coupon);
+        while requests.lock().unwrap().is_empty() {
+            sleep(Duration::from_millis(10)).await;
+        }
+
+        // The delivery is waiting to retry and shutdown wasn't cancelled
+        emitter.abort();
+        tokio::time::timeout(Duration::from_secs(1), emitter.drain()).await.unwrap();
+        assert_eq!(db::get_queue_sizes(&pool).await.unwrap().webhook_dead_letters, 0);
+    }
+}